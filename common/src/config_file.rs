@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, CommandFactory};
+use config::{Config, Environment, File, Value};
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Prefix of the environment variables holding configuration keys
+const ENV_PREFIX: &str = "BIRDLG";
+
+/// Environment variable naming the configuration file when `--config` is not given
+const CONFIG_ENV: &str = "BIRDLG_CONFIG";
+
+/// How often the configuration file is checked for changes
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Command line and environment the configuration is built from, kept so the
+/// configuration can be rebuilt when the file changes
+pub struct Loader {
+    command: Command,
+    matches: ArgMatches,
+    vars: HashMap<String, String>,
+    env_aliases: &'static [(&'static str, &'static str)],
+}
+
+impl Loader {
+    /// Parse the command line of `T` and capture the environment
+    pub fn from_env<T: CommandFactory>() -> Self {
+        let command = T::command();
+        let matches = command.clone().get_matches();
+        let vars = std::env::vars().collect();
+        Loader { command, matches, vars, env_aliases: &[] }
+    }
+
+    /// Parse the given command line of `T` with the given environment instead
+    pub fn from_args<T: CommandFactory>(argv: &[&str], vars: &[(&str, &str)]) -> Result<Self> {
+        let command = T::command();
+        let matches = command.clone().try_get_matches_from(argv)?;
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        Ok(Loader { command, matches, vars, env_aliases: &[] })
+    }
+
+    /// Also read the keys paired with these environment variable names, such
+    /// as the names bird-lg-go used
+    pub fn with_env_aliases(mut self, env_aliases: &'static [(&'static str, &'static str)]) -> Self {
+        self.env_aliases = env_aliases;
+        self
+    }
+
+    /// Merge the command line with the configuration file and the environment.
+    /// Later sources win: built-in defaults, then the YAML/TOML file given with
+    /// `--config`, then aliased and `BIRDLG_*` variables, then command line
+    /// flags.
+    pub fn load<T: DeserializeOwned>(&self) -> Result<T> {
+        let mut builder = Config::builder();
+
+        // Flags from the command line override everything, clap defaults lose to everything
+        for arg in self.command.get_arguments() {
+            let id = arg.get_id().as_str();
+            if id == "config" {
+                continue;
+            }
+            let Some(value) = raw_value(&self.matches, id) else {
+                continue;
+            };
+            builder = match self.matches.value_source(id) {
+                Some(ValueSource::CommandLine) => builder.set_override(id, value)?,
+                Some(ValueSource::DefaultValue) => builder.set_default(id, value)?,
+                _ => builder,
+            };
+        }
+
+        if let Some(path) = self.path() {
+            builder = builder.add_source(File::from(Path::new(&path)).required(true));
+        }
+
+        let aliased = self
+            .env_aliases
+            .iter()
+            .filter_map(|(name, key)| self.vars.get(*name).map(|value| (key.to_string(), value.clone())))
+            .collect();
+        builder = builder.add_source(Environment::default().ignore_empty(true).source(Some(aliased)));
+
+        let prefixed = self
+            .vars
+            .iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        builder = builder.add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .ignore_empty(true)
+                .source(Some(prefixed)),
+        );
+
+        builder
+            .build()
+            .and_then(Config::try_deserialize)
+            .context("Invalid configuration")
+    }
+
+    /// Configuration file path, from `--config` or `BIRDLG_CONFIG`
+    pub fn path(&self) -> Option<String> {
+        self.matches
+            .try_get_one::<String>("config")
+            .ok()
+            .flatten()
+            .or_else(|| self.vars.get(CONFIG_ENV))
+            .cloned()
+    }
+}
+
+/// Call `reload` on SIGHUP and whenever the configuration file changes.
+/// Errors are logged and leave the running configuration in place.
+pub fn watch<F>(loader: Loader, reload: F)
+where
+    F: Fn(&Loader) -> Result<()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let path = loader.path();
+        let mut last_modified = path.as_deref().and_then(modified_time);
+        let mut poll = tokio::time::interval(FILE_POLL_INTERVAL);
+
+        #[cfg(unix)]
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => Some(hangup),
+            Err(e) => {
+                warn!("Failed to listen for SIGHUP: {}", e);
+                None
+            }
+        };
+
+        loop {
+            #[cfg(unix)]
+            let sighup = tokio::select! {
+                Some(()) = async { hangup.as_mut()?.recv().await } => true,
+                _ = poll.tick() => false,
+            };
+            #[cfg(not(unix))]
+            let sighup = {
+                poll.tick().await;
+                false
+            };
+
+            let modified = path.as_deref().and_then(modified_time);
+            let file_changed = modified.is_some() && modified != last_modified;
+            last_modified = modified;
+            if !sighup && !file_changed {
+                continue;
+            }
+
+            let trigger = if sighup { "SIGHUP" } else { "file change" };
+            match reload(&loader) {
+                Ok(()) => info!("Configuration reloaded ({})", trigger),
+                Err(e) => warn!("Keeping the running configuration, reload failed ({}): {:#}", trigger, e),
+            }
+        }
+    });
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Raw command line value of an argument: a list when it was given several
+/// values, a single string otherwise
+fn raw_value(matches: &ArgMatches, id: &str) -> Option<Value> {
+    let values: Vec<String> = matches
+        .try_get_raw(id)
+        .ok()
+        .flatten()?
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+
+    match values.len() {
+        0 => None,
+        1 => values.into_iter().next().map(Value::from),
+        _ => Some(Value::from(values)),
+    }
+}
+
+/// Deserialize a list given either as a sequence or as one comma separated
+/// string, the form used by flags and environment variables
+pub fn comma_separated<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(String),
+    }
+
+    match OneOrMany::<T>::deserialize(deserializer)? {
+        OneOrMany::Many(items) => Ok(items),
+        OneOrMany::One(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse().map_err(de::Error::custom))
+            .collect(),
+    }
+}
+
+/// `comma_separated` for optional lists
+pub fn comma_separated_opt<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    comma_separated(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser, Deserialize)]
+    struct Args {
+        #[arg(long)]
+        config: Option<String>,
+        #[arg(long, default_value = "1")]
+        #[serde(default)]
+        level: u32,
+        #[arg(long, value_delimiter = ',')]
+        #[serde(default, deserialize_with = "comma_separated")]
+        names: Vec<String>,
+    }
+
+    const ALIASES: &[(&str, &str)] = &[("OLD_LEVEL", "level")];
+
+    #[test]
+    fn test_sources_and_lists() {
+        let loader = Loader::from_args::<Args>(&["test"], &[]).unwrap();
+        let args: Args = loader.load().unwrap();
+        assert_eq!(args.level, 1);
+        assert!(args.names.is_empty());
+
+        let vars = [("BIRDLG_NAMES", "a, b,,c"), ("OLD_LEVEL", "2")];
+        let args: Args = Loader::from_args::<Args>(&["test"], &vars).unwrap().load().unwrap();
+        assert_eq!(args.names, vec!["a", "b", "c"]);
+        // Aliases are only read when asked for
+        assert_eq!(args.level, 1);
+
+        let loader = Loader::from_args::<Args>(&["test"], &vars).unwrap().with_env_aliases(ALIASES);
+        assert_eq!(loader.load::<Args>().unwrap().level, 2);
+
+        let vars = [("OLD_LEVEL", "2"), ("BIRDLG_LEVEL", "3")];
+        let loader = Loader::from_args::<Args>(&["test", "--names", "x,y"], &vars).unwrap().with_env_aliases(ALIASES);
+        let args: Args = loader.load().unwrap();
+        assert_eq!(args.level, 3);
+        assert_eq!(args.names, vec!["x", "y"]);
+    }
+}
@@ -1,10 +1,13 @@
 use axum::{
-    extract::Path,
-    response::{IntoResponse, Json},
-    http::StatusCode,
+    extract::{Path, Query},
+    response::{IntoResponse, Json, Response},
+    http::{header, StatusCode},
 };
-use serde_json::json;
-use crate::{proxy_client, whois, settings::Settings};
+use serde_json::{json, Value};
+use std::future::Future;
+use crate::bird_command::{BirdQuery, ProtocolName};
+use crate::dns::DnsParams;
+use crate::{proxy_client, protocol_parser, route_parser, summary_parser, whois, streaming, fanout, settings::Settings};
 
 pub async fn bird_api(Path((servers, command)): Path<(String, String)>) -> impl IntoResponse {
     let settings = Settings::global();
@@ -29,22 +32,200 @@ pub async fn bird_api(Path((servers, command)): Path<(String, String)>) -> impl
         ).into_response();
     }
     
+    // All servers are queried at once; their results are written in order
+    let replies = fanout::bird_stream_all(&server_list, &command);
+    let (tx, body) = streaming::channel_body();
+    tokio::spawn(async move {
+        let head = format!(
+            "{{\"servers\":{},\"command\":{},\"results\":[",
+            json!(server_list),
+            json!(command)
+        );
+        if tx.send(head).await.is_err() {
+            return;
+        }
+
+        for (i, (server, reply)) in server_list.iter().zip(replies).enumerate() {
+            if i > 0 && tx.send(",".to_string()).await.is_err() {
+                return;
+            }
+            if !stream_bird_result(&tx, server, reply).await {
+                return;
+            }
+        }
+
+        let _ = tx.send("]}".to_string()).await;
+    });
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        body,
+    ).into_response()
+}
+
+// Stream one server's result object, writing the output into the "result"
+// string as it arrives. Returns false once the client is gone.
+async fn stream_bird_result(tx: &streaming::BodySender, server: &str, mut reply: fanout::TextReceiver) -> bool {
+    let first = match reply.recv().await {
+        Some(Err(e)) => {
+            let result = json!({
+                "server": server,
+                "result": null,
+                "error": e.to_string(),
+                "error_kind": proxy_client::error_kind(&e)
+            });
+            return tx.send(result.to_string()).await.is_ok();
+        }
+        first => first,
+    };
+
+    if tx.send(format!("{{\"server\":{},\"result\":\"", json!(server))).await.is_err() {
+        return false;
+    }
+
+    let mut error = None;
+    let mut next = first;
+    while let Some(item) = next {
+        match item {
+            Ok(text) => {
+                // Serialize as a JSON string and strip the quotes to get the escaped contents
+                let quoted = json!(text).to_string();
+                if tx.send(quoted[1..quoted.len() - 1].to_string()).await.is_err() {
+                    return false;
+                }
+            }
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+        next = reply.recv().await;
+    }
+
+    let tail = format!(
+        "\",\"error\":{},\"error_kind\":{}}}",
+        json!(error.as_ref().map(|e| e.to_string())),
+        json!(error.as_ref().and_then(proxy_client::error_kind))
+    );
+    tx.send(tail).await.is_ok()
+}
+
+pub async fn summary_api(Path(servers): Path<String>) -> impl IntoResponse {
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    // Validate request before processing
+    if server_list.len() > settings.servers.len() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid request: too many servers specified"
+            }))
+        ).into_response();
+    }
+    
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": e.to_string()
+            }))
+        ).into_response();
+    }
+    
+    let mut results = Vec::new();
+    let replies = fanout::query_all(&server_list, |server| async move {
+        proxy_client::bird_query(&server, "show protocols").await
+    }).await;
+    
+    for (server, reply) in server_list.iter().zip(replies) {
+        let parsed = reply.and_then(|result| {
+            summary_parser::parse_summary(&result, settings.get_server_display_name(server), &settings.protocol_filter)
+        });
+        match parsed {
+            Ok(summary) => {
+                results.push(json!({
+                    "server": server,
+                    "protocols": summary.rows,
+                    "error": null,
+                    "error_kind": null
+                }));
+            }
+            Err(e) => {
+                results.push(json!({
+                    "server": server,
+                    "protocols": null,
+                    "error": e.to_string(),
+                    "error_kind": proxy_client::error_kind(&e)
+                }));
+            }
+        }
+    }
+    
+    (
+        StatusCode::OK,
+        Json(json!({
+            "servers": server_list,
+            "results": results
+        }))
+    ).into_response()
+}
+
+pub async fn routes_api(Path((servers, command)): Path<(String, String)>) -> impl IntoResponse {
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    // Validate request before processing
+    if server_list.len() > settings.servers.len() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid request: too many servers specified"
+            }))
+        ).into_response();
+    }
+    
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": e.to_string()
+            }))
+        ).into_response();
+    }
+
+    if !command.trim_start().starts_with("show route") {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid request: only 'show route' commands can be parsed"
+            }))
+        ).into_response();
+    }
+    
     let mut results = Vec::new();
+    let command_ref = command.as_str();
+    let replies = fanout::query_all(&server_list, |server| async move {
+        proxy_client::bird_query(&server, command_ref).await
+    }).await;
     
-    for server in &server_list {
-        match proxy_client::bird_query(server, &command).await {
+    for (server, reply) in server_list.iter().zip(replies) {
+        match reply {
             Ok(result) => {
                 results.push(json!({
                     "server": server,
-                    "result": result,
-                    "error": null
+                    "routes": route_parser::parse_routes(&result),
+                    "error": null,
+                    "error_kind": null
                 }));
             }
             Err(e) => {
                 results.push(json!({
                     "server": server,
-                    "result": null,
-                    "error": e.to_string()
+                    "routes": null,
+                    "error": e.to_string(),
+                    "error_kind": proxy_client::error_kind(&e)
                 }));
             }
         }
@@ -60,9 +241,20 @@ pub async fn bird_api(Path((servers, command)): Path<(String, String)>) -> impl
     ).into_response()
 }
 
-pub async fn traceroute_api(Path((servers, target)): Path<(String, String)>) -> impl IntoResponse {
+pub async fn detail_api(Path((servers, protocol)): Path<(String, String)>) -> impl IntoResponse {
     let settings = Settings::global();
     let server_list = settings.resolve_servers_from_display_names(&servers);
+    let command = match ProtocolName::parse(&protocol) {
+        Ok(protocol) => BirdQuery::ProtocolDetail(protocol).to_string(),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!("Invalid request: {}", e)
+                }))
+            ).into_response();
+        }
+    };
     
     // Validate request before processing
     if server_list.len() > settings.servers.len() {
@@ -84,16 +276,99 @@ pub async fn traceroute_api(Path((servers, target)): Path<(String, String)>) ->
     }
     
     let mut results = Vec::new();
+    let command_ref = command.as_str();
+    let replies = fanout::query_all(&server_list, |server| async move {
+        proxy_client::bird_query(&server, command_ref).await
+    }).await;
     
-    for server in &server_list {
-        match proxy_client::traceroute_query(server, &target).await {
+    for (server, reply) in server_list.iter().zip(replies) {
+        match reply {
             Ok(result) => {
                 results.push(json!({
                     "server": server,
-                    "result": result,
-                    "error": null
+                    "protocols": protocol_parser::parse_protocols(&result),
+                    "error": null,
+                    "error_kind": null
                 }));
             }
+            Err(e) => {
+                results.push(json!({
+                    "server": server,
+                    "protocols": null,
+                    "error": e.to_string(),
+                    "error_kind": proxy_client::error_kind(&e)
+                }));
+            }
+        }
+    }
+    
+    (
+        StatusCode::OK,
+        Json(json!({
+            "servers": server_list,
+            "protocol": protocol,
+            "results": results
+        }))
+    ).into_response()
+}
+
+pub async fn traceroute_api(Path((servers, target)): Path<(String, String)>) -> impl IntoResponse {
+    tool_api(servers, target, |server, target| async move {
+        let report = proxy_client::traceroute_report(&server, &target).await?;
+        Ok(json!({
+            "result": report.text,
+            "hops": report.hops
+        }))
+    }).await
+}
+
+pub async fn ping_api(Path((servers, target)): Path<(String, String)>) -> impl IntoResponse {
+    tool_api(servers, target, |server, target| async move {
+        Ok(json!({
+            "result": proxy_client::ping_query(&server, &target).await?
+        }))
+    }).await
+}
+
+// Run a network tool such as traceroute on each server and return its output,
+// given as the fields of each server's result
+async fn tool_api<F, Fut>(servers: String, target: String, query: F) -> Response
+where
+    F: Fn(String, String) -> Fut,
+    Fut: Future<Output = anyhow::Result<Value>>,
+{
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    // Validate request before processing
+    if server_list.len() > settings.servers.len() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid request: too many servers specified"
+            }))
+        ).into_response();
+    }
+    
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": e.to_string()
+            }))
+        ).into_response();
+    }
+    
+    let mut results = Vec::new();
+    let replies = fanout::query_all(&server_list, |server| query(server, target.clone())).await;
+    
+    for (server, reply) in server_list.iter().zip(replies) {
+        match reply {
+            Ok(mut fields) => {
+                fields["server"] = json!(server);
+                fields["error"] = Value::Null;
+                results.push(fields);
+            }
             Err(e) => {
                 results.push(json!({
                     "server": server,
@@ -114,8 +389,70 @@ pub async fn traceroute_api(Path((servers, target)): Path<(String, String)>) ->
     ).into_response()
 }
 
+pub async fn dns_api(
+    Path((servers, target)): Path<(String, String)>,
+    Query(params): Query<DnsParams>,
+) -> impl IntoResponse {
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    // Validate request before processing
+    if server_list.len() > settings.servers.len() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid request: too many servers specified"
+            }))
+        ).into_response();
+    }
+    
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": e.to_string()
+            }))
+        ).into_response();
+    }
+    
+    let mut results = Vec::new();
+    let target_ref = target.as_str();
+    let record_type = params.record_type.as_deref();
+    let replies = fanout::query_all(&server_list, |server| async move {
+        proxy_client::dns_query(&server, target_ref, record_type).await
+    }).await;
+    
+    for (server, reply) in server_list.iter().zip(replies) {
+        match reply {
+            Ok(reply) => {
+                results.push(json!({
+                    "server": server,
+                    "answers": reply.answers,
+                    "error": null
+                }));
+            }
+            Err(e) => {
+                results.push(json!({
+                    "server": server,
+                    "answers": null,
+                    "error": e.to_string()
+                }));
+            }
+        }
+    }
+    
+    (
+        StatusCode::OK,
+        Json(json!({
+            "servers": server_list,
+            "target": target,
+            "results": results
+        }))
+    ).into_response()
+}
+
 pub async fn whois_api(Path(target): Path<String>) -> impl IntoResponse {
-    match whois::query(&target).await {
+    match whois::lookup(&target).await {
         Ok(result) => {
             Json(json!({
                 "target": target,
@@ -0,0 +1,340 @@
+use anyhow::Result;
+use futures_util::future::join_all;
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use crate::settings::Settings;
+use crate::whois;
+
+static ASN_RESOLVER: OnceLock<AsnResolver> = OnceLock::new();
+
+const DEFAULT_FIELDS: &str = "asn,as-name,ASName,descr";
+
+/// Resolves AS numbers to bgpmap labels from DNS TXT records, falling back to
+/// whois, and caches the labels for a limited time
+pub struct AsnResolver {
+    resolver: TokioAsyncResolver,
+    /// Zone queried as `AS<asn>.<zone>`; empty disables DNS lookups
+    zone: String,
+    /// Empty disables the whois fallback
+    whois_server: String,
+    /// Whois fields shown in labels, in order; `asn` stands for the AS number
+    fields: Vec<String>,
+    ttl: Duration,
+    cache: Mutex<HashMap<u32, (String, Instant)>>,
+    semaphore: Semaphore,
+}
+
+impl AsnResolver {
+    pub fn new(resolver: TokioAsyncResolver, zone: &str, whois_server: &str, fields: &str, ttl: Duration, max_concurrent: usize) -> Self {
+        // bird-lg-go falls back to the same default when the option is empty
+        let fields = if fields.trim().is_empty() { DEFAULT_FIELDS } else { fields };
+
+        Self {
+            resolver,
+            zone: zone.trim_matches('.').to_string(),
+            whois_server: whois_server.to_string(),
+            fields: fields
+                .split(',')
+                .map(|field| field.trim().to_string())
+                .filter(|field| !field.is_empty())
+                .collect(),
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+            semaphore: Semaphore::new(max_concurrent.max(1)),
+        }
+    }
+
+    fn cached(&self, asn: u32) -> Option<String> {
+        let cache = self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache
+            .get(&asn)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(label, _)| label.clone())
+    }
+
+    fn store(&self, asn: u32, label: &str) {
+        let now = Instant::now();
+        let mut cache = self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cache.retain(|_, (_, expires)| *expires > now);
+        cache.insert(asn, (label.to_string(), now + self.ttl));
+    }
+
+    /// Label for an AS, such as `AS4242423914\nKIOUBIT-NETWORK`, or just `AS4242423914`
+    /// when neither DNS nor whois knows it
+    pub async fn lookup(&self, asn: u32) -> String {
+        if let Some(label) = self.cached(asn) {
+            return label;
+        }
+
+        let _permit = self.semaphore.acquire().await;
+
+        // Another lookup may have resolved it while this one was waiting
+        if let Some(label) = self.cached(asn) {
+            return label;
+        }
+
+        let label = match self.lookup_dns(asn).await {
+            Some(label) => label,
+            None => self.lookup_whois(asn).await.unwrap_or_else(|| format!("AS{}", asn)),
+        };
+        self.store(asn, &label);
+        label
+    }
+
+    /// Resolve several ASes at once, within the concurrency limit
+    pub async fn lookup_all(&self, asns: &[u32]) -> HashMap<u32, String> {
+        let labels = join_all(asns.iter().map(|&asn| async move { (asn, self.lookup(asn).await) })).await;
+        labels.into_iter().collect()
+    }
+
+    async fn lookup_dns(&self, asn: u32) -> Option<String> {
+        if self.zone.is_empty() {
+            return None;
+        }
+
+        // Fully qualified, so search domains are not tried
+        let name = format!("AS{}.{}.", asn, self.zone);
+        let records = match self.resolver.txt_lookup(name.as_str()).await {
+            Ok(records) => records,
+            Err(e) => {
+                tracing::debug!("TXT lookup for {} failed: {}", name, e);
+                return None;
+            }
+        };
+
+        let text = records.iter().map(|txt| txt.to_string()).collect::<Vec<_>>().join(" ");
+        format_txt_label(asn, &text)
+    }
+
+    async fn lookup_whois(&self, asn: u32) -> Option<String> {
+        if self.whois_server.is_empty() {
+            return None;
+        }
+
+        let records = whois::query_server(&self.whois_server, &format!("AS{}", asn)).await.ok()?;
+        whois_label(asn, &records, &self.fields)
+    }
+}
+
+/// Turn `23028 | US | arin | 2002-01-04 | TEAMCYMRU - SAUNET, US` into one
+/// label line per field after the AS number
+fn format_txt_label(asn: u32, text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let fields: Vec<&str> = text.split(" | ").collect();
+    let details = if fields.len() > 1 {
+        fields[1..].join("\n")
+    } else {
+        text.to_string()
+    };
+    Some(format!("AS{}\n{}", asn, details))
+}
+
+/// Combine the selected whois fields into label lines, in the order given.
+/// Repeated fields keep all their values; fields missing from the record are skipped.
+fn whois_label(asn: u32, records: &str, fields: &[String]) -> Option<String> {
+    let mut values: HashMap<&str, Vec<&str>> = HashMap::new();
+    for line in records.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() || value.is_empty() || key.starts_with('%') {
+            continue;
+        }
+        values.entry(key).or_default().push(value);
+    }
+
+    let lines: Vec<String> = fields
+        .iter()
+        .filter_map(|field| match field.as_str() {
+            "asn" => Some(format!("AS{}", asn)),
+            field => values.get(field).map(|v| v.join("\n")),
+        })
+        .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+pub fn init() -> Result<()> {
+    let settings = Settings::global();
+
+    let resolver = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+        tracing::warn!("Failed to read system DNS configuration, using defaults: {}", e);
+        TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+    });
+
+    let asn_resolver = AsnResolver::new(
+        resolver,
+        &settings.dns_interface,
+        &settings.whois_server,
+        &settings.bgpmap_info,
+        Duration::from_secs(settings.asn_cache_ttl),
+        settings.asn_max_concurrent,
+    );
+
+    ASN_RESOLVER.set(asn_resolver).map_err(|_| anyhow::anyhow!("ASN resolver already initialized"))?;
+    Ok(())
+}
+
+pub fn global() -> &'static AsnResolver {
+    ASN_RESOLVER.get().expect("ASN resolver not initialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::config::NameServerConfigGroup;
+    use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use hickory_resolver::proto::rr::rdata::TXT;
+    use hickory_resolver::proto::rr::{RData, Record};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::UdpSocket;
+
+    /// Answer TXT queries from a fixed table, counting every query received
+    async fn stub_dns(records: &'static [(&'static str, &'static str)]) -> (SocketAddr, Arc<AtomicUsize>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let Ok(request) = Message::from_vec(&buf[..len]) else {
+                    continue;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(request.op_code())
+                    .set_recursion_desired(true)
+                    .set_recursion_available(true)
+                    .add_queries(request.queries().to_vec());
+
+                let query = &request.queries()[0];
+                let name = query.name().to_ascii().to_lowercase();
+                match records.iter().find(|(record, _)| name == *record) {
+                    Some((_, text)) => {
+                        let rdata = RData::TXT(TXT::new(vec![text.to_string()]));
+                        response.add_answer(Record::from_rdata(query.name().clone(), 300, rdata));
+                    }
+                    None => {
+                        response.set_response_code(ResponseCode::NXDomain);
+                    }
+                }
+
+                let _ = socket.send_to(&response.to_vec().unwrap(), peer).await;
+            }
+        });
+
+        (addr, queries)
+    }
+
+    fn test_resolver(addr: SocketAddr, ttl: Duration, max_concurrent: usize) -> AsnResolver {
+        let config = ResolverConfig::from_parts(
+            None,
+            vec![],
+            NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true),
+        );
+        let mut options = ResolverOpts::default();
+        options.cache_size = 0;
+        options.attempts = 1;
+        options.timeout = Duration::from_secs(1);
+
+        AsnResolver::new(TokioAsyncResolver::tokio(config, options), "asn.example", "", "", ttl, max_concurrent)
+    }
+
+    const RECORDS: &[(&str, &str)] = &[
+        ("as23028.asn.example.", "23028 | US | arin | 2002-01-04 | TEAMCYMRU - SAUNET, US"),
+        ("as4242423914.asn.example.", "KIOUBIT-NETWORK"),
+    ];
+
+    #[tokio::test]
+    async fn test_lookup_txt_records() {
+        let (addr, _) = stub_dns(RECORDS).await;
+        let resolver = test_resolver(addr, Duration::from_secs(60), 4);
+
+        assert_eq!(resolver.lookup(23028).await, "AS23028\nUS\narin\n2002-01-04\nTEAMCYMRU - SAUNET, US");
+        assert_eq!(resolver.lookup(4242423914).await, "AS4242423914\nKIOUBIT-NETWORK");
+        // Unknown AS without a whois fallback
+        assert_eq!(resolver.lookup(64512).await, "AS64512");
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_ttl() {
+        let (addr, queries) = stub_dns(RECORDS).await;
+
+        let resolver = test_resolver(addr, Duration::from_secs(60), 4);
+        resolver.lookup(23028).await;
+        resolver.lookup(23028).await;
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // Expired entries are looked up again
+        let resolver = test_resolver(addr, Duration::ZERO, 4);
+        resolver.lookup(23028).await;
+        resolver.lookup(23028).await;
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lookups_share_results() {
+        let (addr, queries) = stub_dns(RECORDS).await;
+        let resolver = test_resolver(addr, Duration::from_secs(60), 1);
+
+        let labels = resolver.lookup_all(&[23028, 23028, 4242423914, 23028]).await;
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[&4242423914], "AS4242423914\nKIOUBIT-NETWORK");
+        // Waiting lookups find the cached label instead of querying again
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+    }
+
+    fn fields(list: &str) -> Vec<String> {
+        list.split(',').map(str::to_string).collect()
+    }
+
+    const WHOIS: &str = "% This is the dn42 whois query service.
+
+aut-num:            AS4242423914
+as-name:            KIOUBIT-NETWORK
+descr:              Kioubit.com
+descr:              Germany
+admin-c:            KIOUBIT-DN42
+mnt-by:             KIOUBIT-MNT
+source:             DN42
+";
+
+    #[test]
+    fn test_whois_label_follows_field_order() {
+        assert_eq!(
+            whois_label(4242423914, WHOIS, &fields(DEFAULT_FIELDS)).as_deref(),
+            Some("AS4242423914\nKIOUBIT-NETWORK\nKioubit.com\nGermany")
+        );
+        assert_eq!(
+            whois_label(4242423914, WHOIS, &fields("mnt-by,asn")).as_deref(),
+            Some("KIOUBIT-MNT\nAS4242423914")
+        );
+    }
+
+    #[test]
+    fn test_whois_label_skips_missing_fields() {
+        assert_eq!(
+            whois_label(4242423914, WHOIS, &fields("asn,ASName,org,as-name")).as_deref(),
+            Some("AS4242423914\nKIOUBIT-NETWORK")
+        );
+        assert_eq!(whois_label(4242423914, WHOIS, &fields("ASName,org")), None);
+        assert_eq!(whois_label(4242423914, "% No entries found", &fields("as-name")), None);
+    }
+}
@@ -1,6 +1,6 @@
-use regex::Regex;
 use std::collections::HashMap;
 use base64::{Engine as _, engine::general_purpose};
+use crate::{asn, route_parser};
 
 #[derive(Debug, Clone)]
 pub struct RouteAttrs {
@@ -32,7 +32,7 @@ impl RouteAttrs {
         self.attrs.is_empty()
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<String, String> {
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, String> {
         self.attrs.iter()
     }
 }
@@ -143,9 +143,17 @@ impl RouteGraph {
         format!("[{}]", attr_strings.join(","))
     }
 
-    pub fn to_graphviz(&self) -> String {
+    /// AS numbers of the points that should be labelled with AS information
+    pub fn lookup_asns(&self) -> Vec<u32> {
+        self.points
+            .iter()
+            .filter(|(_, point)| point.perform_lookup)
+            .filter_map(|(name, _)| name.parse().ok())
+            .collect()
+    }
+
+    pub fn to_graphviz(&self, asn_labels: &HashMap<u32, String>) -> String {
         let mut result = String::new();
-        let mut asn_cache = ASNCache::new();
 
         // Add graph attributes
         result.push_str("  rankdir=LR;\n");
@@ -154,7 +162,10 @@ impl RouteGraph {
         // Add points
         for (name, point) in &self.points {
             let representation = if point.perform_lookup {
-                asn_cache.lookup(name)
+                name.parse()
+                    .ok()
+                    .and_then(|asn| asn_labels.get(&asn).cloned())
+                    .unwrap_or_else(|| format!("AS{}", name))
             } else {
                 name.clone()
             };
@@ -217,32 +228,6 @@ impl RouteGraph {
     }
 }
 
-// ASN Cache for lookup functionality
-#[derive(Debug)]
-pub struct ASNCache {
-    cache: HashMap<String, String>,
-}
-
-impl ASNCache {
-    pub fn new() -> Self {
-        Self {
-            cache: HashMap::new(),
-        }
-    }
-
-    pub fn lookup(&mut self, asn: &str) -> String {
-        if let Some(cached_value) = self.cache.get(asn) {
-            return cached_value.clone();
-        }
-
-        // For now, use simplified implementation
-        // In a full implementation, this would do DNS/WHOIS lookups
-        let result = format!("AS{}", asn);
-        self.cache.insert(asn.to_string(), result.clone());
-        result
-    }
-}
-
 fn make_edge_attrs(preferred: bool) -> RouteAttrs {
     let mut attrs = RouteAttrs::new();
     attrs.insert("fontsize".to_string(), "12.0".to_string());
@@ -269,12 +254,6 @@ fn bird_route_to_graph(servers: &[String], responses: &[String], target: &str) -
     target_attrs.insert("shape".to_string(), "diamond".to_string());
     graph.add_point(target.to_string(), false, target_attrs);
 
-    // Compile regex patterns
-    let protocol_name_re = Regex::new(r"\[(.*?) .*\]").expect("Invalid regex pattern");
-    let route_split_re = Regex::new(r"(unicast|blackhole|unreachable|prohibited)").expect("Invalid regex pattern");
-    let route_via_re = Regex::new(r"(?m)^\t(via .*?)$").expect("Invalid regex pattern");
-    let route_as_path_re = Regex::new(r"(?mi)^\tBGP(?:\.as)?_path: (.*?)$").expect("Invalid regex pattern");
-
     for (server_id, server) in servers.iter().enumerate() {
         if let Some(response) = responses.get(server_id) {
             if response.is_empty() {
@@ -287,48 +266,16 @@ fn bird_route_to_graph(servers: &[String], responses: &[String], target: &str) -
             server_attrs.insert("shape".to_string(), "box".to_string());
             graph.add_point(server.clone(), false, server_attrs);
 
-            // Split routes
-            let routes: Vec<&str> = route_split_re.split(response).collect();
-
-            for (route_index, route) in routes.iter().enumerate() {
-                if route_index == 0 {
-                    continue;
-                }
+            for route in route_parser::parse_routes(response) {
+                let route_preferred = route.primary;
+                let via = route.nexthops.first().map(|n| n.to_string()).unwrap_or_default();
+                let paths: Vec<String> = route.as_path.iter().map(|asn| asn.to_string()).collect();
 
-                let route_preferred = route.contains('*');
-                let mut via = String::new();
-                let mut paths = Vec::new();
-                let mut protocol_name = String::new();
-
-                // Extract via information
-                if let Some(captures) = route_via_re.captures(route) {
-                    if let Some(via_match) = captures.get(1) {
-                        via = via_match.as_str().trim().to_string();
-                    }
-                }
-
-                // Extract AS path
-                if let Some(captures) = route_as_path_re.captures(route) {
-                    if let Some(path_match) = captures.get(1) {
-                        let path_string = path_match.as_str().trim();
-                        if !path_string.is_empty() {
-                            paths = path_string
-                                .split_whitespace()
-                                .map(|p| p.trim_start_matches('(').trim_end_matches(')').to_string())
-                                .collect();
-                        }
-                    }
-                }
-
-                // Extract protocol name
-                if let Some(captures) = protocol_name_re.captures(route) {
-                    if let Some(protocol_match) = captures.get(1) {
-                        protocol_name = protocol_match.as_str().trim().to_string();
-                        if route_preferred {
-                            protocol_name = format!("{}*", protocol_name);
-                        }
-                    }
-                }
+                let protocol_name = if route_preferred {
+                    format!("{}*", route.protocol)
+                } else {
+                    route.protocol.clone()
+                };
 
                 if paths.is_empty() {
                     // Direct connection
@@ -371,9 +318,10 @@ fn bird_route_to_graph(servers: &[String], responses: &[String], target: &str) -
     graph
 }
 
-pub fn bird_route_to_graphviz(servers: &[String], responses: &[String], target: &str) -> String {
+pub async fn bird_route_to_graphviz(servers: &[String], responses: &[String], target: &str) -> String {
     let graph = bird_route_to_graph(servers, responses, target);
-    graph.to_graphviz()
+    let asn_labels = asn::global().lookup_all(&graph.lookup_asns()).await;
+    graph.to_graphviz(&asn_labels)
 }
 
 #[allow(dead_code)]
@@ -398,8 +346,8 @@ pub fn debug_graphviz_generation() {
     println!("Input servers: {:?}", servers);
     println!("Input responses length: {}", responses[0].len());
     
-    // Generate the DOT graph (matches handlers.rs logic)
-    let dot_graph = bird_route_to_graphviz(&servers, &responses, "172.20.0.53");
+    // Generate the DOT graph (matches handlers.rs logic, without ASN lookups)
+    let dot_graph = bird_route_to_graph(&servers, &responses, "172.20.0.53").to_graphviz(&HashMap::new());
     
     println!("\n=== Generated DOT Graph ===");
     println!("{}", dot_graph);
@@ -454,15 +402,14 @@ mod tests {
     #[test]
     fn test_bird_route_to_graph_xss() {
         let fake_result = r#"<script>alert("evil!")</script>"#;
-        let result = bird_route_to_graphviz(
+        let result = bird_route_to_graph(
             &[String::from("alpha")],
             &[fake_result.to_string()],
             fake_result,
-        );
+        ).to_graphviz(&HashMap::new());
 
-        // Decode the base64 result to check for XSS
-        let decoded = String::from_utf8(general_purpose::STANDARD.decode(result).expect("Failed to decode base64")).expect("Failed to convert to string");
-        assert!(!decoded.contains(fake_result), "XSS injection succeeded: {}", decoded);
+        // The DOT output must not carry the raw payload through unescaped
+        assert!(!result.contains(fake_result), "XSS injection succeeded: {}", result);
     }
 
     #[test]
@@ -518,7 +465,7 @@ mod tests {
 	BGP.as_path: 4242423914
 	BGP.next_hop: 172.20.229.122"#;
 
-        let dot_result = bird_route_to_graphviz(&[String::from("node")], &[input.to_string()], "target");
+        let dot_result = bird_route_to_graph(&[String::from("node")], &[input.to_string()], "target").to_graphviz(&HashMap::new());
         let base64_result = general_purpose::STANDARD.encode(&dot_result);
         
         // Decode the base64 result
@@ -537,7 +484,7 @@ mod tests {
 	BGP.as_path: 4242423914
 	BGP.next_hop: 172.20.229.122"#;
 
-        let dot_result = bird_route_to_graphviz(&[String::from("node")], &[input.to_string()], "target");
+        let dot_result = bird_route_to_graph(&[String::from("node")], &[input.to_string()], "target").to_graphviz(&HashMap::new());
         let base64_result = general_purpose::STANDARD.encode(&dot_result);
         
         // Decode the base64 result
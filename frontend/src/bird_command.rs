@@ -0,0 +1,366 @@
+use crate::net_mode::NetSpecificMode;
+use ipnet::IpNet;
+use std::fmt;
+use std::net::IpAddr;
+
+/// User input that no BIRD command can be built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidInput(String);
+
+impl fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidInput {}
+
+/// A protocol name: letters, digits and underscores, not starting with a digit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolName(String);
+
+impl ProtocolName {
+    pub fn parse(input: &str) -> Result<Self, InvalidInput> {
+        let name = input.trim();
+        let mut chars = name.chars();
+        let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        if valid_start && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Ok(ProtocolName(name.to_string()))
+        } else {
+            Err(InvalidInput(format!(
+                "'{}' is not a protocol name: use letters, digits and underscores, like bgp_peer1",
+                input
+            )))
+        }
+    }
+}
+
+/// An IP address or prefix to look up routes for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteTarget {
+    Ip(IpAddr),
+    Net(IpNet),
+}
+
+impl RouteTarget {
+    pub fn parse(input: &str) -> Result<Self, InvalidInput> {
+        let target = input.trim();
+        if let Ok(ip) = target.parse::<IpAddr>() {
+            Ok(RouteTarget::Ip(ip))
+        } else if let Ok(net) = target.parse::<IpNet>() {
+            Ok(RouteTarget::Net(net.trunc()))
+        } else {
+            Err(InvalidInput(format!(
+                "'{}' is not an IP address or prefix, like 192.0.2.1 or 2001:db8::/32",
+                input
+            )))
+        }
+    }
+}
+
+/// A prefix with the more or less specific networks it should match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetPattern {
+    net: IpNet,
+    /// `+` for the prefix and its more specifics, `-` for its less specifics
+    suffix: Option<char>,
+}
+
+impl NetPattern {
+    pub fn parse(input: &str) -> Result<Self, InvalidInput> {
+        let pattern = input.trim();
+        let (prefix, suffix) = match pattern.strip_suffix(['+', '-']) {
+            Some(prefix) => (prefix, pattern.chars().last()),
+            None => (pattern, None),
+        };
+
+        let net = match RouteTarget::parse(prefix) {
+            Ok(RouteTarget::Net(net)) => net,
+            // A single address is its host prefix
+            Ok(RouteTarget::Ip(ip)) => IpNet::from(ip),
+            Err(_) => {
+                return Err(InvalidInput(format!(
+                    "'{}' is not a prefix, like 10.0.0.0/8, optionally followed by + or -",
+                    input
+                )))
+            }
+        };
+        Ok(NetPattern { net, suffix })
+    }
+}
+
+/// An AS number, with the `AS` prefix and dn42 short ASNs accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Asn(u32);
+
+impl Asn {
+    pub fn parse(input: &str, mode: NetSpecificMode) -> Result<Self, InvalidInput> {
+        mode.origin_asn(input)
+            .map(Asn)
+            .ok_or_else(|| InvalidInput(format!("'{}' is not an AS number, like 64512 or AS64512", input)))
+    }
+}
+
+/// Subcommands of `show` the generic view may run
+const SHOW_KEYWORDS: &[&str] = &[
+    "status", "memory", "protocols", "interfaces", "route", "symbols", "ospf", "rip", "babel", "bfd", "static",
+];
+
+/// Options of `show route` the proxy accepts, besides a prefix
+const ROUTE_KEYWORDS: &[&str] = &["for", "table", "protocol", "filtered", "where", "all", "primary", "count"];
+
+/// Options of `show route` followed by a value
+const ROUTE_VALUE_KEYWORDS: &[&str] = &["for", "table", "protocol"];
+
+/// Arguments of a free-form `show` or `show route` command, held to the
+/// proxy's route grammar: known keywords, addresses and prefixes only.
+/// `where` conditions, and `show` commands other than route, are left to
+/// the proxy's own checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowArgs(String);
+
+impl ShowArgs {
+    /// Arguments of `show`, like `protocols all bgp_peer1`
+    pub fn parse(input: &str) -> Result<Self, InvalidInput> {
+        Self::parse_with(input, |words| match words {
+            ["route", options @ ..] => check_route_options(options),
+            [first, ..] if SHOW_KEYWORDS.contains(first) => Ok(()),
+            [first, ..] => Err(format!("'{}' is not a known keyword", first)),
+            [] => Ok(()),
+        })
+        .map_err(|reason| {
+            InvalidInput(format!("'{}' is not a show command, like protocols or status: {}", input, reason))
+        })
+    }
+
+    /// Arguments of `show route`, like `for 192.0.2.1 all` or `10.0.0.0/8 table master4`
+    pub fn parse_route(input: &str) -> Result<Self, InvalidInput> {
+        Self::parse_with(input, check_route_options).map_err(|reason| {
+            InvalidInput(format!("'{}' is not a route query, like for 192.0.2.1 all: {}", input, reason))
+        })
+    }
+
+    fn parse_with(input: &str, check: impl Fn(&[&str]) -> Result<(), String>) -> Result<Self, String> {
+        if let Some(c) = input
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !" .:/_-+*?[],=~\"".contains(*c))
+        {
+            return Err(format!("character '{}' is not allowed", c.escape_default()));
+        }
+
+        let words: Vec<&str> = input.split_whitespace().collect();
+        if words.is_empty() {
+            return Err("it is empty".to_string());
+        }
+        check(&words)?;
+        Ok(ShowArgs(words.join(" ")))
+    }
+}
+
+fn check_route_options(words: &[&str]) -> Result<(), String> {
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        let keyword = word.to_ascii_lowercase();
+        let is_prefix = word.contains('/') && word.parse::<IpNet>().is_ok();
+        if keyword == "where" {
+            return Ok(());
+        }
+        if ROUTE_VALUE_KEYWORDS.contains(&keyword.as_str()) {
+            words.next().ok_or_else(|| format!("'{}' needs a value", word))?;
+        } else if !ROUTE_KEYWORDS.contains(&keyword.as_str()) && !is_prefix {
+            return Err(format!("'{}' is not allowed in show route", word));
+        }
+    }
+    Ok(())
+}
+
+/// Options shared by route views
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteOptions {
+    /// Show all attributes
+    pub all: bool,
+    /// Only the best route of each network
+    pub primary: bool,
+}
+
+impl RouteOptions {
+    pub const NONE: Self = RouteOptions { all: false, primary: false };
+    pub const ALL: Self = RouteOptions { all: true, primary: false };
+    pub const PRIMARY: Self = RouteOptions { all: false, primary: true };
+    pub const ALL_PRIMARY: Self = RouteOptions { all: true, primary: true };
+}
+
+/// A BIRD command built from validated values only
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BirdQuery {
+    Summary,
+    /// `show protocols all` for every protocol
+    AllProtocolDetails,
+    ProtocolDetail(ProtocolName),
+    RouteFor(RouteTarget, RouteOptions),
+    RouteWhere(NetPattern, RouteOptions),
+    FromProtocol(ProtocolName, RouteOptions),
+    FilteredFromProtocol(ProtocolName, RouteOptions),
+    FromOrigin(Asn, RouteOptions),
+    /// `show <args>` from the generic view
+    Show(ShowArgs),
+    /// `show route <args>` from the generic route view
+    ShowRoute(ShowArgs),
+}
+
+impl fmt::Display for ProtocolName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for RouteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteTarget::Ip(ip) => write!(f, "{}", ip),
+            RouteTarget::Net(net) => write!(f, "{}", net),
+        }
+    }
+}
+
+impl fmt::Display for NetPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.net)?;
+        if let Some(suffix) = self.suffix {
+            write!(f, "{}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ShowArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Asn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for RouteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.all {
+            write!(f, " all")?;
+        }
+        if self.primary {
+            write!(f, " primary")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BirdQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BirdQuery::Summary => write!(f, "show protocols"),
+            BirdQuery::AllProtocolDetails => write!(f, "show protocols all"),
+            BirdQuery::ProtocolDetail(protocol) => write!(f, "show protocols all {}", protocol),
+            BirdQuery::RouteFor(target, options) => write!(f, "show route for {}{}", target, options),
+            BirdQuery::RouteWhere(pattern, options) => write!(f, "show route where net ~ [ {} ]{}", pattern, options),
+            BirdQuery::FromProtocol(protocol, options) => write!(f, "show route protocol {}{}", protocol, options),
+            BirdQuery::FilteredFromProtocol(protocol, options) => {
+                write!(f, "show route filtered protocol {}{}", protocol, options)
+            }
+            BirdQuery::FromOrigin(asn, options) => write!(f, "show route where bgp_path.last = {}{}", asn, options),
+            BirdQuery::Show(args) => write!(f, "show {}", args),
+            BirdQuery::ShowRoute(args) => write!(f, "show route {}", args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(input: &str) -> RouteTarget {
+        RouteTarget::parse(input).unwrap()
+    }
+
+    #[test]
+    fn test_route_commands() {
+        assert_eq!(BirdQuery::RouteFor(target("192.0.2.1"), RouteOptions::NONE).to_string(), "show route for 192.0.2.1");
+        assert_eq!(
+            BirdQuery::RouteFor(target(" 2001:db8::/32 "), RouteOptions::ALL_PRIMARY).to_string(),
+            "show route for 2001:db8::/32 all primary"
+        );
+        // Host bits are dropped from prefixes
+        assert_eq!(target("10.1.2.3/8"), target("10.0.0.0/8"));
+
+        let pattern = NetPattern::parse("10.0.0.0/8+").unwrap();
+        assert_eq!(BirdQuery::RouteWhere(pattern, RouteOptions::ALL).to_string(), "show route where net ~ [ 10.0.0.0/8+ ] all");
+        assert_eq!(NetPattern::parse("192.0.2.1").unwrap().to_string(), "192.0.2.1/32");
+        assert_eq!(NetPattern::parse("fd00::/8-").unwrap().to_string(), "fd00::/8-");
+    }
+
+    #[test]
+    fn test_protocol_commands() {
+        let protocol = ProtocolName::parse("bgp_peer1").unwrap();
+        assert_eq!(BirdQuery::ProtocolDetail(protocol.clone()).to_string(), "show protocols all bgp_peer1");
+        assert_eq!(BirdQuery::AllProtocolDetails.to_string(), "show protocols all");
+        assert_eq!(BirdQuery::FromProtocol(protocol.clone(), RouteOptions::PRIMARY).to_string(), "show route protocol bgp_peer1 primary");
+        assert_eq!(
+            BirdQuery::FilteredFromProtocol(protocol, RouteOptions::ALL).to_string(),
+            "show route filtered protocol bgp_peer1 all"
+        );
+    }
+
+    #[test]
+    fn test_origin_commands() {
+        let asn = Asn::parse("AS3914", NetSpecificMode::Dn42).unwrap();
+        assert_eq!(BirdQuery::FromOrigin(asn, RouteOptions::NONE).to_string(), "show route where bgp_path.last = 4242423914");
+        assert_eq!(Asn::parse("as13335", NetSpecificMode::None).unwrap(), Asn(13335));
+    }
+
+    #[test]
+    fn test_generic_commands() {
+        let args = ShowArgs::parse(" protocols  all bgp_peer1 ").unwrap();
+        assert_eq!(BirdQuery::Show(args).to_string(), "show protocols all bgp_peer1");
+        let args = ShowArgs::parse_route("for 192.0.2.1 all").unwrap();
+        assert_eq!(BirdQuery::ShowRoute(args).to_string(), "show route for 192.0.2.1 all");
+        let args = ShowArgs::parse_route("10.0.0.0/8 table master4 where net ~ [ 10.0.0.0/8+ ]").unwrap();
+        assert_eq!(BirdQuery::ShowRoute(args).to_string(), "show route 10.0.0.0/8 table master4 where net ~ [ 10.0.0.0/8+ ]");
+        assert!(ShowArgs::parse_route("filtered protocol bgp1 all primary count").is_ok());
+
+        assert!(ShowArgs::parse("").is_err());
+        assert!(ShowArgs::parse("configure").is_err());
+        assert!(ShowArgs::parse("status\ndown").is_err());
+        assert!(ShowArgs::parse("status; down").is_err());
+        assert!(ShowArgs::parse_route("export bgp1 'x'").is_err());
+        assert!(ShowArgs::parse_route("restart all").is_err());
+
+        // Options the proxy refuses
+        for input in ["export bgp1", "for 192.0.2.1 export bgp1", "noexport bgp1", "stats", "filter f1", "192.0.2.1", "table"] {
+            assert!(ShowArgs::parse_route(input).is_err(), "{} was accepted", input);
+        }
+        assert!(ShowArgs::parse("route export bgp1").is_err());
+        assert!(ShowArgs::parse_route("where net ~ [ 10.0.0.0/8{16,24} ]").is_err());
+
+        let error = ShowArgs::parse("reload all").unwrap_err();
+        assert_eq!(error.to_string(), "'reload all' is not a show command, like protocols or status: 'reload' is not a known keyword");
+    }
+
+    #[test]
+    fn test_rejects_invalid_input() {
+        assert!(RouteTarget::parse("192.0.2.1 all; configure").is_err());
+        assert!(RouteTarget::parse("example.com").is_err());
+        assert!(RouteTarget::parse("").is_err());
+        assert!(NetPattern::parse("10.0.0.0/8 ] || [ 0.0.0.0/0").is_err());
+        assert!(NetPattern::parse("10.0.0.0/8{16,24}").is_err());
+        assert!(ProtocolName::parse("bgp1 all").is_err());
+        assert!(ProtocolName::parse("1bgp").is_err());
+        assert!(ProtocolName::parse("bgp-1").is_err());
+        assert!(Asn::parse("AS64512 all", NetSpecificMode::None).is_err());
+        assert!(Asn::parse("-1", NetSpecificMode::None).is_err());
+
+        let error = RouteTarget::parse("not-an-ip").unwrap_err();
+        assert_eq!(error.to_string(), "'not-an-ip' is not an IP address or prefix, like 192.0.2.1 or 2001:db8::/32");
+    }
+}
@@ -0,0 +1,134 @@
+pub use bird_lg_common::config_file::{comma_separated, comma_separated_opt, watch, Loader};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use crate::settings::ServerEntry;
+    use crate::Args;
+    use std::io::Write;
+
+    fn load_with(argv: &[&str], vars: &[(&str, &str)]) -> Result<Args> {
+        Loader::from_args::<Args>(argv, vars)?.load()
+    }
+
+    fn write_config(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("bird-lg-rs-{}-{}", std::process::id(), name));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_defaults_without_file() {
+        let args = load_with(&["bird-lg-rs", "--servers", "node1,node2"], &[]).unwrap();
+        assert_eq!(args.servers.len(), 2);
+        assert_eq!(args.timeout, 120);
+        assert_eq!(args.proxy_port, 8000);
+        assert!(!args.auth_enabled);
+        assert!(args.auth_token.is_none());
+        assert!(args.protocol_filter.is_none());
+    }
+
+    #[test]
+    fn test_yaml_file_with_structured_servers() {
+        let path = write_config(
+            "servers.yaml",
+            r#"
+servers:
+  - "Tokyo<tyo1>"
+  - name: fra1
+    display: Frankfurt
+    url: https://fra1.example.com:8443/lg
+    token: fra-secret
+    tags: [eu, transit]
+protocol_filter: [bgp, ospf]
+timeout: 30
+"#,
+        );
+        let args = load_with(&["bird-lg-rs", "--config", &path], &[]).unwrap();
+
+        assert!(matches!(&args.servers[0], ServerEntry::Spec(spec) if spec == "Tokyo<tyo1>"));
+        let ServerEntry::Detailed(fra1) = &args.servers[1] else {
+            panic!("expected a structured entry");
+        };
+        assert_eq!(fra1.name, "fra1");
+        assert_eq!(fra1.display.as_deref(), Some("Frankfurt"));
+        assert_eq!(fra1.url.as_deref(), Some("https://fra1.example.com:8443/lg"));
+        assert_eq!(fra1.token.as_deref(), Some("fra-secret"));
+        assert_eq!(fra1.tags, vec!["eu", "transit"]);
+        assert_eq!(args.protocol_filter, Some(vec!["bgp".to_string(), "ospf".to_string()]));
+        assert_eq!(args.timeout, 30);
+        // Keys missing from the file keep their defaults
+        assert_eq!(args.max_concurrent, 10);
+    }
+
+    #[test]
+    fn test_precedence_file_env_cli() {
+        let path = write_config(
+            "precedence.toml",
+            r#"
+servers = "node1,node2"
+timeout = 30
+max_concurrent = 4
+title_brand = "From file"
+"#,
+        );
+        let vars = [("BIRDLG_TIMEOUT", "60"), ("BIRDLG_TITLE_BRAND", "From env")];
+
+        let args = load_with(&["bird-lg-rs", "--config", &path, "--timeout", "90"], &vars).unwrap();
+        assert_eq!(args.servers.len(), 2);
+        assert_eq!(args.max_concurrent, 4);
+        assert_eq!(args.title_brand, "From env");
+        assert_eq!(args.timeout, 90);
+    }
+
+    #[test]
+    fn test_config_path_from_env() {
+        let path = write_config("env-path.yaml", "navbar_brand: From file\n");
+        let args = load_with(&["bird-lg-rs"], &[("BIRDLG_CONFIG", &path)]).unwrap();
+        assert_eq!(args.navbar_brand, "From file");
+    }
+
+    #[test]
+    fn test_env_lists_and_flags() {
+        let vars = [
+            ("BIRDLG_SERVERS", "node1,node2,node3"),
+            ("BIRDLG_AUTH_ENABLED", "true"),
+            ("BIRDLG_AUTH_TOKEN", "12345"),
+        ];
+        let args = load_with(&["bird-lg-rs"], &vars).unwrap();
+        assert_eq!(args.servers.len(), 3);
+        assert!(args.auth_enabled);
+        assert_eq!(args.auth_token.as_deref(), Some("12345"));
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let path = write_config("invalid.yaml", "timeout: soon\n");
+        assert!(load_with(&["bird-lg-rs", "--config", &path], &[]).is_err());
+
+        let missing = load_with(&["bird-lg-rs", "--config", "/nonexistent/bird-lg.yaml"], &[]);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_loader_reads_file_again() {
+        let path = write_config("reload.yaml", "servers: node1\ntimeout: 30\n");
+        let loader = Loader::from_args::<Args>(&["bird-lg-rs", "--config", &path], &[]).unwrap();
+        assert_eq!(loader.path().as_deref(), Some(path.as_str()));
+
+        let args: Args = loader.load().unwrap();
+        assert_eq!(args.servers.len(), 1);
+        assert_eq!(args.timeout, 30);
+
+        std::fs::write(&path, "servers: node1,node2\ntimeout: 45\n").unwrap();
+        let args: Args = loader.load().unwrap();
+        assert_eq!(args.servers.len(), 2);
+        assert_eq!(args.timeout, 45);
+
+        // A broken file fails to load, so the caller keeps what it has
+        std::fs::write(&path, "servers: [node1\n").unwrap();
+        assert!(loader.load::<Args>().is_err());
+    }
+}
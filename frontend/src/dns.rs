@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+/// Query string of DNS pages and the DNS API
+#[derive(Debug, Deserialize)]
+pub struct DnsParams {
+    /// Record type; every type that applies to the target when unset
+    #[serde(default, rename = "type")]
+    pub record_type: Option<String>,
+}
+
+/// One record of a proxy's DNS answer
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// Records of one type, or why there are none
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DnsAnswer {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub records: Vec<DnsRecord>,
+    pub error: Option<String>,
+}
+
+/// A proxy's `/dns` reply
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DnsReply {
+    pub query: String,
+    pub answers: Vec<DnsAnswer>,
+}
+
+/// One server's answer in a comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsCell {
+    pub server: String,
+    pub records: Vec<DnsRecord>,
+    pub error: Option<String>,
+    /// Whether the server answered, as opposed to the request failing
+    pub answered: bool,
+}
+
+/// The answers of every server for one record type
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsComparison {
+    pub record_type: String,
+    /// Whether every server that answered returned the same records, TTLs aside
+    pub consistent: bool,
+    pub cells: Vec<DnsCell>,
+}
+
+/// What a cell is compared by: its error, and its records without TTLs
+fn fingerprint(cell: &DnsCell) -> (Option<&str>, Vec<(&str, &str, &str)>) {
+    let mut records: Vec<_> = cell
+        .records
+        .iter()
+        .map(|record| (record.name.as_str(), record.record_type.as_str(), record.data.as_str()))
+        .collect();
+    records.sort_unstable();
+    (cell.error.as_deref(), records)
+}
+
+/// Line up the replies of several servers by record type, in the order the
+/// types first appear
+pub fn compare(results: &[(String, Result<DnsReply, String>)]) -> Vec<DnsComparison> {
+    let mut record_types: Vec<&str> = Vec::new();
+    for answer in results.iter().filter_map(|(_, reply)| reply.as_ref().ok()).flat_map(|reply| &reply.answers) {
+        if !record_types.contains(&answer.record_type.as_str()) {
+            record_types.push(&answer.record_type);
+        }
+    }
+
+    record_types
+        .into_iter()
+        .map(|record_type| {
+            let cells: Vec<DnsCell> = results
+                .iter()
+                .map(|(server, reply)| match reply {
+                    Ok(reply) => match reply.answers.iter().find(|answer| answer.record_type == record_type) {
+                        Some(answer) => DnsCell {
+                            server: server.clone(),
+                            records: answer.records.clone(),
+                            error: answer.error.clone(),
+                            answered: true,
+                        },
+                        None => DnsCell {
+                            server: server.clone(),
+                            records: Vec::new(),
+                            error: Some("not queried".to_string()),
+                            answered: false,
+                        },
+                    },
+                    Err(e) => DnsCell {
+                        server: server.clone(),
+                        records: Vec::new(),
+                        error: Some(e.clone()),
+                        answered: false,
+                    },
+                })
+                .collect();
+
+            let mut answered = cells.iter().filter(|cell| cell.answered).map(fingerprint);
+            let consistent = match answered.next() {
+                Some(first) => answered.all(|other| other == first),
+                None => true,
+            };
+
+            DnsComparison {
+                record_type: record_type.to_string(),
+                consistent,
+                cells,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: &str, ttl: u32, data: &str) -> DnsRecord {
+        DnsRecord {
+            name: "example.com.".to_string(),
+            record_type: record_type.to_string(),
+            ttl,
+            data: data.to_string(),
+        }
+    }
+
+    fn reply(answers: Vec<(&str, Vec<DnsRecord>, Option<&str>)>) -> Result<DnsReply, String> {
+        Ok(DnsReply {
+            query: "example.com".to_string(),
+            answers: answers
+                .into_iter()
+                .map(|(record_type, records, error)| DnsAnswer {
+                    record_type: record_type.to_string(),
+                    records,
+                    error: error.map(str::to_string),
+                })
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn test_compare_split_horizon() {
+        let results = vec![
+            (
+                "fra1".to_string(),
+                reply(vec![
+                    ("A", vec![record("A", 300, "192.0.2.1"), record("A", 300, "192.0.2.2")], None),
+                    ("MX", vec![], Some("NODATA")),
+                ]),
+            ),
+            (
+                "sjc1".to_string(),
+                reply(vec![
+                    // Same records in another order and with another TTL
+                    ("A", vec![record("A", 120, "192.0.2.2"), record("A", 120, "192.0.2.1")], None),
+                    ("MX", vec![record("MX", 300, "10 mail.example.com.")], None),
+                ]),
+            ),
+            ("hkg1".to_string(), Err("request timed out after 120s".to_string())),
+        ];
+
+        let comparisons = compare(&results);
+        assert_eq!(comparisons.len(), 2);
+
+        let a = &comparisons[0];
+        assert_eq!(a.record_type, "A");
+        // The failed server does not count as a difference
+        assert!(a.consistent);
+        assert_eq!(a.cells.len(), 3);
+        assert_eq!(a.cells[2].error.as_deref(), Some("request timed out after 120s"));
+        assert!(!a.cells[2].answered);
+
+        let mx = &comparisons[1];
+        assert_eq!(mx.record_type, "MX");
+        assert!(!mx.consistent);
+        assert_eq!(mx.cells[0].error.as_deref(), Some("NODATA"));
+        assert_eq!(mx.cells[1].records[0].data, "10 mail.example.com.");
+    }
+
+    #[test]
+    fn test_compare_all_failed() {
+        let results = vec![("fra1".to_string(), Err("connection refused".to_string()))];
+        assert!(compare(&results).is_empty());
+    }
+}
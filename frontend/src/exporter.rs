@@ -0,0 +1,319 @@
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use crate::bird_command::BirdQuery;
+use crate::fanout;
+use crate::protocol_parser::{self, RouteCounts};
+use crate::proxy_client;
+use crate::settings::Settings;
+use crate::summary_parser::{self, ProtocolFilter};
+
+/// How often to check again whether polling was enabled by a reload
+const DISABLED_RECHECK: Duration = Duration::from_secs(60);
+
+/// Metric name, help text and the count it exports
+type RouteCountMetric = (&'static str, &'static str, fn(&RouteCounts) -> u64);
+
+const ROUTE_COUNT_METRICS: [RouteCountMetric; 3] = [
+    ("birdlg_protocol_imported_routes", "Routes imported by the channel", |routes| routes.imported),
+    ("birdlg_protocol_exported_routes", "Routes exported by the channel", |routes| routes.exported),
+    ("birdlg_protocol_filtered_routes", "Routes rejected by the channel's import filter", |routes| routes.filtered),
+];
+
+/// A protocol as seen by the last poll
+#[derive(Debug, Clone, PartialEq)]
+struct ProtocolState {
+    name: String,
+    proto: String,
+    up: bool,
+    /// Last state change, in the frontend's local time
+    since: Option<NaiveDateTime>,
+    /// Route counts by channel name
+    routes: Vec<(String, RouteCounts)>,
+}
+
+/// One server's protocols, or `None` when the last poll failed
+#[derive(Debug, Clone, PartialEq)]
+struct ServerState {
+    server: String,
+    protocols: Option<Vec<ProtocolState>>,
+}
+
+static SNAPSHOT: OnceLock<ArcSwap<Vec<ServerState>>> = OnceLock::new();
+
+fn snapshot() -> &'static ArcSwap<Vec<ServerState>> {
+    SNAPSHOT.get_or_init(|| ArcSwap::from_pointee(Vec::new()))
+}
+
+/// Parse a BIRD `Since` column: a date, a date and time, or a time of day,
+/// which is in the past day relative to `now`
+fn parse_since(since: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let since = since.trim();
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(since, format) {
+            return Some(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0);
+    }
+
+    let time = NaiveTime::parse_from_str(since, "%H:%M:%S%.f").ok()?;
+    let today = now.date().and_time(time);
+    if today <= now {
+        Some(today)
+    } else {
+        Some(today - chrono::Duration::days(1))
+    }
+}
+
+/// Combine `show protocols` and `show protocols all` output of one server.
+/// Protocols hidden from summaries are left out.
+fn server_state(
+    server: String,
+    summary: &str,
+    details: &str,
+    filter: &ProtocolFilter,
+    now: NaiveDateTime,
+) -> Result<ServerState> {
+    let summary = summary_parser::parse_summary(summary, server.clone(), filter)?;
+    let mut routes: HashMap<String, Vec<(String, RouteCounts)>> = protocol_parser::parse_protocols(details)
+        .into_iter()
+        .map(|protocol| {
+            let channels = protocol
+                .channels
+                .into_iter()
+                .filter_map(|channel| Some((channel.name, channel.routes?)))
+                .collect();
+            (protocol.name, channels)
+        })
+        .collect();
+
+    let protocols = summary
+        .rows
+        .into_iter()
+        .map(|row| ProtocolState {
+            up: row.state == "up",
+            since: parse_since(&row.since, now),
+            routes: routes.remove(&row.name).unwrap_or_default(),
+            name: row.name,
+            proto: row.proto,
+        })
+        .collect();
+
+    Ok(ServerState {
+        server,
+        protocols: Some(protocols),
+    })
+}
+
+async fn poll_server(server: String) -> Result<ServerState> {
+    let settings = Settings::global();
+    let summary = proxy_client::bird_query(&server, &BirdQuery::Summary.to_string()).await?;
+    let details = proxy_client::bird_query(&server, &BirdQuery::AllProtocolDetails.to_string()).await?;
+    server_state(
+        settings.get_server_display_name(&server),
+        &summary,
+        &details,
+        &settings.protocol_filter,
+        Local::now().naive_local(),
+    )
+}
+
+/// Query every server once and replace the snapshot
+async fn poll() {
+    let settings = Settings::global();
+    let replies = fanout::query_all(&settings.servers, poll_server).await;
+
+    let states = settings
+        .servers
+        .iter()
+        .zip(replies)
+        .map(|(server, reply)| {
+            reply.unwrap_or_else(|e| {
+                tracing::warn!("Failed to poll {} for metrics: {}", server, e);
+                ServerState {
+                    server: settings.get_server_display_name(server),
+                    protocols: None,
+                }
+            })
+        })
+        .collect();
+    snapshot().store(Arc::new(states));
+}
+
+/// Poll all servers every `metrics_interval` seconds in the background
+pub fn spawn_poller() {
+    tokio::spawn(async {
+        loop {
+            let interval = Settings::global().metrics_interval;
+            if interval == 0 {
+                snapshot().store(Arc::new(Vec::new()));
+                tokio::time::sleep(DISABLED_RECHECK).await;
+                continue;
+            }
+
+            poll().await;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
+
+/// Escape a label value of the Prometheus text format
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Text exposition of the polled states, with state durations up to `now`
+fn render(servers: &[ServerState], now: NaiveDateTime) -> String {
+    let mut out = String::new();
+
+    header(&mut out, "birdlg_server_up", "gauge", "Whether the last poll of the server succeeded");
+    for state in servers {
+        let _ = writeln!(out, "birdlg_server_up{{server=\"{}\"}} {}", label(&state.server), u8::from(state.protocols.is_some()));
+    }
+
+    let protocols: Vec<(&str, &ProtocolState)> = servers
+        .iter()
+        .filter_map(|state| Some((state.server.as_str(), state.protocols.as_ref()?)))
+        .flat_map(|(server, protocols)| protocols.iter().map(move |protocol| (server, protocol)))
+        .collect();
+
+    header(&mut out, "birdlg_protocol_up", "gauge", "Whether the protocol is up");
+    for (server, protocol) in &protocols {
+        let _ = writeln!(
+            out,
+            "birdlg_protocol_up{{server=\"{}\",protocol=\"{}\",proto=\"{}\"}} {}",
+            label(server),
+            label(&protocol.name),
+            label(&protocol.proto),
+            u8::from(protocol.up)
+        );
+    }
+
+    header(&mut out, "birdlg_protocol_state_seconds", "gauge", "Time since the protocol last changed state");
+    for (server, protocol) in &protocols {
+        let Some(since) = protocol.since else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "birdlg_protocol_state_seconds{{server=\"{}\",protocol=\"{}\",proto=\"{}\"}} {}",
+            label(server),
+            label(&protocol.name),
+            label(&protocol.proto),
+            (now - since).num_seconds().max(0)
+        );
+    }
+
+    for (name, help, count) in ROUTE_COUNT_METRICS {
+        header(&mut out, name, "gauge", help);
+        for (server, protocol) in &protocols {
+            for (channel, routes) in &protocol.routes {
+                let _ = writeln!(
+                    out,
+                    "{}{{server=\"{}\",protocol=\"{}\",channel=\"{}\"}} {}",
+                    name,
+                    label(server),
+                    label(&protocol.name),
+                    label(channel),
+                    count(routes)
+                );
+            }
+        }
+    }
+
+    out
+}
+
+// Handler for BGP session metrics
+pub async fn metrics_handler() -> Response {
+    if Settings::global().metrics_interval == 0 {
+        return (StatusCode::NOT_FOUND, "Metrics are disabled").into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&snapshot().load(), Local::now().naive_local()),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROTOCOLS_ALL_BGP: &str = include_str!("../tests/fixtures/show_protocols_all_bgp.txt");
+    const PROTOCOLS_ALL_DOWN: &str = include_str!("../tests/fixtures/show_protocols_all_down.txt");
+
+    const SUMMARY: &str = "Name       Proto      Table      State  Since         Info
+dn42_kioubit BGP      ---        up     2024-03-02 11:45:21  Established
+dn42_broken BGP       ---        start  2024-03-02 12:01:09  Active        Socket: Connection refused
+device1    Device     ---        up     2024-03-01 09:00:00";
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-03-02 12:45:21", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn poll_fixture(filter: &ProtocolFilter) -> ServerState {
+        let details = format!("{}\n{}", PROTOCOLS_ALL_BGP, PROTOCOLS_ALL_DOWN);
+        server_state("node1".to_string(), SUMMARY, &details, filter, now()).unwrap()
+    }
+
+    #[test]
+    fn test_render_session_metrics() {
+        let servers = vec![
+            poll_fixture(&ProtocolFilter::default()),
+            ServerState {
+                server: "node\"2".to_string(),
+                protocols: None,
+            },
+        ];
+        let text = render(&servers, now());
+
+        assert!(text.contains("birdlg_server_up{server=\"node1\"} 1\n"));
+        assert!(text.contains("birdlg_server_up{server=\"node\\\"2\"} 0\n"));
+        assert!(text.contains("birdlg_protocol_up{server=\"node1\",protocol=\"dn42_kioubit\",proto=\"BGP\"} 1\n"));
+        assert!(text.contains("birdlg_protocol_up{server=\"node1\",protocol=\"dn42_broken\",proto=\"BGP\"} 0\n"));
+        assert!(text.contains("birdlg_protocol_state_seconds{server=\"node1\",protocol=\"dn42_kioubit\",proto=\"BGP\"} 3600\n"));
+        assert!(text.contains("birdlg_protocol_imported_routes{server=\"node1\",protocol=\"dn42_kioubit\",channel=\"ipv4\"} 812\n"));
+        assert!(text.contains("birdlg_protocol_exported_routes{server=\"node1\",protocol=\"dn42_kioubit\",channel=\"ipv4\"} 1204\n"));
+        assert!(text.contains("birdlg_protocol_filtered_routes{server=\"node1\",protocol=\"dn42_kioubit\",channel=\"ipv4\"} 3\n"));
+        // Down channels have no route counts
+        assert!(!text.contains("protocol=\"dn42_broken\",channel"));
+        assert!(text.contains("# TYPE birdlg_protocol_up gauge\n"));
+    }
+
+    #[test]
+    fn test_hidden_protocols_are_not_exported() {
+        let filter = ProtocolFilter::new(&["BGP".to_string()], "broken").unwrap();
+        let state = poll_fixture(&filter);
+        let names: Vec<&str> = state.protocols.as_ref().unwrap().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["dn42_kioubit"]);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let time = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").unwrap();
+        assert_eq!(parse_since("2024-03-02 11:45:21", now()), Some(time("2024-03-02 11:45:21.0")));
+        assert_eq!(parse_since("2024-03-02 11:45:21.500", now()), Some(time("2024-03-02 11:45:21.5")));
+        assert_eq!(parse_since("2024-03-01", now()), Some(time("2024-03-01 00:00:00.0")));
+        assert_eq!(parse_since("12:00:00.123", now()), Some(time("2024-03-02 12:00:00.123")));
+        // A later time of day was yesterday
+        assert_eq!(parse_since("13:00:00", now()), Some(time("2024-03-01 13:00:00.0")));
+        assert_eq!(parse_since("", now()), None);
+    }
+}
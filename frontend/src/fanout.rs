@@ -0,0 +1,247 @@
+use anyhow::Result;
+use futures_util::future::join_all;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+use crate::proxy_client::{self, ProxyError};
+use crate::settings::Settings;
+use crate::traceroute::TracerouteEvent;
+
+/// Events of one server's traceroute as they arrive, or why it failed
+pub type EventReceiver = mpsc::Receiver<Result<TracerouteEvent>>;
+
+/// Text of one server's reply as it arrives. An error as the first item means
+/// the request itself failed; a later error broke the reply mid-stream.
+pub type TextReceiver = mpsc::Receiver<Result<String>>;
+
+/// Items buffered per server before its reader waits for the page to catch up
+const STREAM_BUFFER: usize = 32;
+
+fn timeout_error(timeout: Duration) -> anyhow::Error {
+    ProxyError {
+        status: StatusCode::GATEWAY_TIMEOUT,
+        kind: Some("timeout".to_string()),
+        message: format!("request timed out after {}s", timeout.as_secs()),
+    }
+    .into()
+}
+
+fn limits() -> (usize, Duration) {
+    let settings = Settings::global();
+    (settings.max_concurrent.max(1), Duration::from_secs(settings.timeout))
+}
+
+/// Run `query` against every server concurrently, within the configured
+/// concurrency limit and per-server timeout. Results keep the order of `servers`.
+pub async fn query_all<T, F, Fut>(servers: &[String], query: F) -> Vec<Result<T>>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let (max_concurrent, timeout) = limits();
+    run(servers, max_concurrent, timeout, query).await
+}
+
+async fn run<T, F, Fut>(servers: &[String], max_concurrent: usize, timeout: Duration, query: F) -> Vec<Result<T>>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let semaphore = Semaphore::new(max_concurrent);
+    let tasks = servers.iter().map(|server| {
+        let future = query(server.clone());
+        let semaphore = &semaphore;
+        async move {
+            let _permit = semaphore.acquire().await?;
+            tokio::time::timeout(timeout, future)
+                .await
+                .unwrap_or_else(|_| Err(timeout_error(timeout)))
+        }
+    });
+    join_all(tasks).await
+}
+
+/// Run `forward` for every server at once, within the concurrency limit and
+/// per-server timeout. Each call sends its server's output into the given
+/// sender as it arrives, and is dropped once the receiver is.
+///
+/// Senders wait while their receiver is full. Permits are handed out in the
+/// order of `servers`, so a server being read in order always holds one.
+fn stream_all<T, F, Fut>(servers: &[String], forward: F) -> Vec<mpsc::Receiver<Result<T>>>
+where
+    T: Send + 'static,
+    F: Fn(String, mpsc::Sender<Result<T>>) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let (max_concurrent, timeout) = limits();
+    spawn_streams(servers, max_concurrent, timeout, forward)
+}
+
+fn spawn_streams<T, F, Fut>(
+    servers: &[String],
+    max_concurrent: usize,
+    timeout: Duration,
+    forward: F,
+) -> Vec<mpsc::Receiver<Result<T>>>
+where
+    T: Send + 'static,
+    F: Fn(String, mpsc::Sender<Result<T>>) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(max_concurrent));
+
+    let (tasks, receivers): (Vec<_>, Vec<_>) = servers
+        .iter()
+        .map(|server| {
+            let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+            let forward = forward(server.clone(), tx.clone());
+            ((tx, forward), rx)
+        })
+        .unzip();
+
+    tokio::spawn(async move {
+        for (tx, forward) in tasks {
+            let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                return;
+            };
+            tokio::spawn(async move {
+                let _permit = permit;
+                let result = tokio::select! {
+                    // The page was closed; stop reading
+                    _ = tx.closed() => return,
+                    result = tokio::time::timeout(timeout, forward) => result,
+                };
+                if let Err(e) = result.unwrap_or_else(|_| Err(timeout_error(timeout))) {
+                    let _ = tx.send(Err(e)).await;
+                }
+            });
+        }
+    });
+
+    receivers
+}
+
+/// Start streaming a BIRD command from every server at once. Each server's
+/// output is read ahead into its receiver, so callers can forward them in
+/// order while later servers are still being read.
+pub fn bird_stream_all(servers: &[String], command: &str) -> Vec<TextReceiver> {
+    let command = command.to_string();
+    stream_all(servers, move |server, tx| {
+        let command = command.clone();
+        async move {
+            let mut reply = proxy_client::bird_query_stream(&server, &command).await?;
+            while let Some(text) = reply.next_text().await? {
+                if tx.send(Ok(text)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    })
+}
+
+/// Start a streaming traceroute from every server at once
+pub fn traceroute_stream_all(servers: &[String], target: &str) -> Vec<EventReceiver> {
+    let target = target.to_string();
+    stream_all(servers, move |server, tx| {
+        let target = target.clone();
+        async move {
+            let mut traceroute = proxy_client::traceroute_stream(&server, &target).await?;
+            while let Some(event) = traceroute.next_event().await? {
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    fn servers(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("node{}", i)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_results_keep_server_order() {
+        // Later servers answer first
+        let results = run(&servers(5), 5, Duration::from_secs(5), |server| async move {
+            let index: u64 = server.trim_start_matches("node").parse()?;
+            tokio::time::sleep(Duration::from_millis(50 - index * 10)).await;
+            Ok(server)
+        })
+        .await;
+
+        let results: Vec<String> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, servers(5));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let results = run(&servers(6), 2, Duration::from_secs(5), |_| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_streams_read_in_order() {
+        // Every server fills its buffer many times over, with one permit
+        let receivers = spawn_streams(&servers(3), 1, Duration::from_secs(5), |server, tx| async move {
+            for i in 0..STREAM_BUFFER * 4 {
+                if tx.send(Ok(format!("{} {}", server, i))).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        for (server, mut rx) in servers(3).into_iter().zip(receivers) {
+            let mut count = 0;
+            while let Some(text) = rx.recv().await {
+                assert!(text.unwrap().starts_with(&server));
+                count += 1;
+            }
+            assert_eq!(count, STREAM_BUFFER * 4);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_server_times_out_alone() {
+        let started = Instant::now();
+        let results = run(&servers(3), 3, Duration::from_millis(100), |server| async move {
+            if server == "node1" {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Ok(server)
+        })
+        .await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(results[0].as_deref().unwrap(), "node0");
+        assert_eq!(results[2].as_deref().unwrap(), "node2");
+
+        let error = results[1].as_ref().unwrap_err();
+        assert_eq!(proxy_client::error_kind(error), Some("timeout"));
+    }
+}
@@ -1,12 +1,17 @@
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
 };
+use crate::bird_command::{Asn, BirdQuery, InvalidInput, NetPattern, ProtocolName, RouteOptions, RouteTarget, ShowArgs};
+use crate::dns::{self, DnsParams, DnsReply};
 use crate::settings::Settings;
-use crate::templates::{PageContext, BirdContext, WhoisContext, BgpmapContext};
-use crate::{proxy_client, whois, bgpmap, templates, summary_parser};
+use crate::traceroute::{TracerouteEvent, TracerouteReport};
+use crate::templates::{PageContext, BirdContext, WhoisContext, BgpmapContext, ProtocolContext, DnsContext, TracerouteStreamContext};
+use crate::{proxy_client, whois, bgpmap, templates, summary_parser, protocol_parser, streaming, fanout};
 use base64::{Engine as _, engine::general_purpose};
+use futures_util::{stream, StreamExt};
+use std::future::Future;
 
 // Redirect to summary page
 pub async fn redirect_to_summary() -> impl IntoResponse {
@@ -20,124 +25,215 @@ pub async fn redirect_to_summary() -> impl IntoResponse {
 
 // Bird summary handler
 pub async fn bird_summary(Path(servers): Path<String>) -> Result<impl IntoResponse, Response> {
-    handle_bird_command(servers, "summary", "show protocols".to_string()).await
+    handle_bird_command(servers, "summary", BirdQuery::Summary.to_string()).await
 }
 
 // Bird detail handler
 pub async fn bird_detail(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show protocols all {}", protocol);
-    handle_bird_command(servers, "detail", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("detail", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "detail", BirdQuery::ProtocolDetail(protocol).to_string()).await
 }
 
 // Bird route handler
 pub async fn bird_route(Path((servers, route)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route for {}", route);
-    handle_bird_command(servers, "route", command).await
+    let target = RouteTarget::parse(&route).map_err(|e| invalid_input_page("route", &servers, &route, &e))?;
+    handle_bird_command(servers, "route", BirdQuery::RouteFor(target, RouteOptions::NONE).to_string()).await
 }
 
 // Bird route all handler
 pub async fn bird_route_all(Path((servers, route)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route for {} all", route);
-    handle_bird_command(servers, "route_all", command).await
+    let target = RouteTarget::parse(&route).map_err(|e| invalid_input_page("route_all", &servers, &route, &e))?;
+    handle_bird_command(servers, "route_all", BirdQuery::RouteFor(target, RouteOptions::ALL).to_string()).await
 }
 
 // Bird route where handler
 pub async fn bird_route_where(Path((servers, prefix)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where net ~ [ {} ]", prefix);
-    handle_bird_command(servers, "route_where", command).await
+    let pattern = NetPattern::parse(&prefix).map_err(|e| invalid_input_page("route_where", &servers, &prefix, &e))?;
+    handle_bird_command(servers, "route_where", BirdQuery::RouteWhere(pattern, RouteOptions::NONE).to_string()).await
 }
 
 // Bird route where all handler
 pub async fn bird_route_where_all(Path((servers, prefix)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where net ~ [ {} ] all", prefix);
-    handle_bird_command(servers, "route_where_all", command).await
+    let pattern = NetPattern::parse(&prefix).map_err(|e| invalid_input_page("route_where_all", &servers, &prefix, &e))?;
+    handle_bird_command(servers, "route_where_all", BirdQuery::RouteWhere(pattern, RouteOptions::ALL).to_string()).await
 }
 
 // Bird route from protocol handler
 pub async fn bird_route_from_protocol(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route protocol {}", protocol);
-    handle_bird_command(servers, "route_from_protocol", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_from_protocol", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_from_protocol", BirdQuery::FromProtocol(protocol, RouteOptions::NONE).to_string()).await
 }
 
 // Bird route from protocol all handler
 pub async fn bird_route_from_protocol_all(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route protocol {} all", protocol);
-    handle_bird_command(servers, "route_from_protocol_all", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_from_protocol_all", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_from_protocol_all", BirdQuery::FromProtocol(protocol, RouteOptions::ALL).to_string()).await
 }
 
 // Bird route from protocol primary handler
 pub async fn bird_route_from_protocol_primary(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route protocol {} primary", protocol);
-    handle_bird_command(servers, "route_from_protocol_primary", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_from_protocol_primary", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_from_protocol_primary", BirdQuery::FromProtocol(protocol, RouteOptions::PRIMARY).to_string()).await
 }
 
 // Bird route from protocol all primary handler
 pub async fn bird_route_from_protocol_all_primary(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route protocol {} all primary", protocol);
-    handle_bird_command(servers, "route_from_protocol_all_primary", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_from_protocol_all_primary", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_from_protocol_all_primary", BirdQuery::FromProtocol(protocol, RouteOptions::ALL_PRIMARY).to_string()).await
 }
 
 // Bird route filtered from protocol handler
 pub async fn bird_route_filtered_from_protocol(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route filtered protocol {}", protocol);
-    handle_bird_command(servers, "route_filtered_from_protocol", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_filtered_from_protocol", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_filtered_from_protocol", BirdQuery::FilteredFromProtocol(protocol, RouteOptions::NONE).to_string()).await
 }
 
 // Bird route filtered from protocol all handler
 pub async fn bird_route_filtered_from_protocol_all(Path((servers, protocol)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route filtered protocol {} all", protocol);
-    handle_bird_command(servers, "route_filtered_from_protocol_all", command).await
+    let protocol = ProtocolName::parse(&protocol).map_err(|e| invalid_input_page("route_filtered_from_protocol_all", &servers, &protocol, &e))?;
+    handle_bird_command(servers, "route_filtered_from_protocol_all", BirdQuery::FilteredFromProtocol(protocol, RouteOptions::ALL).to_string()).await
 }
 
 // Bird route from origin handler
 pub async fn bird_route_from_origin(Path((servers, asn)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where bgp_path.last = {}", asn);
-    handle_bird_command(servers, "route_from_origin", command).await
+    let asn = Asn::parse(&asn, Settings::global().net_specific_mode).map_err(|e| invalid_input_page("route_from_origin", &servers, &asn, &e))?;
+    handle_bird_command(servers, "route_from_origin", BirdQuery::FromOrigin(asn, RouteOptions::NONE).to_string()).await
 }
 
 // Bird route from origin all handler
 pub async fn bird_route_from_origin_all(Path((servers, asn)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where bgp_path.last = {} all", asn);
-    handle_bird_command(servers, "route_from_origin_all", command).await
+    let asn = Asn::parse(&asn, Settings::global().net_specific_mode).map_err(|e| invalid_input_page("route_from_origin_all", &servers, &asn, &e))?;
+    handle_bird_command(servers, "route_from_origin_all", BirdQuery::FromOrigin(asn, RouteOptions::ALL).to_string()).await
 }
 
 // Bird route from origin primary handler
 pub async fn bird_route_from_origin_primary(Path((servers, asn)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where bgp_path.last = {} primary", asn);
-    handle_bird_command(servers, "route_from_origin_primary", command).await
+    let asn = Asn::parse(&asn, Settings::global().net_specific_mode).map_err(|e| invalid_input_page("route_from_origin_primary", &servers, &asn, &e))?;
+    handle_bird_command(servers, "route_from_origin_primary", BirdQuery::FromOrigin(asn, RouteOptions::PRIMARY).to_string()).await
 }
 
 // Bird route from origin all primary handler
 pub async fn bird_route_from_origin_all_primary(Path((servers, asn)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where bgp_path.last = {} all primary", asn);
-    handle_bird_command(servers, "route_from_origin_all_primary", command).await
+    let asn = Asn::parse(&asn, Settings::global().net_specific_mode).map_err(|e| invalid_input_page("route_from_origin_all_primary", &servers, &asn, &e))?;
+    handle_bird_command(servers, "route_from_origin_all_primary", BirdQuery::FromOrigin(asn, RouteOptions::ALL_PRIMARY).to_string()).await
 }
 
 // Bird generic command handler
 pub async fn bird_generic(Path((servers, command)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show {}", command);
-    handle_bird_command(servers, "generic", command).await
+    let args = ShowArgs::parse(&command).map_err(|e| invalid_input_page("generic", &servers, &command, &e))?;
+    handle_bird_command(servers, "generic", BirdQuery::Show(args).to_string()).await
 }
 
 // Bird route generic handler
 pub async fn bird_route_generic(Path((servers, command)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route {}", command);
-    handle_bird_command(servers, "route_generic", command).await
+    let args = ShowArgs::parse_route(&command).map_err(|e| invalid_input_page("route_generic", &servers, &command, &e))?;
+    handle_bird_command(servers, "route_generic", BirdQuery::ShowRoute(args).to_string()).await
 }
 
 // BGP Map handlers
 pub async fn bird_route_bgpmap(Path((servers, route)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route for {} all", route);
-    handle_bgpmap_command(servers, command, route).await
+    let target = RouteTarget::parse(&route).map_err(|e| invalid_input_page("route_bgpmap", &servers, &route, &e))?;
+    handle_bgpmap_command(servers, BirdQuery::RouteFor(target, RouteOptions::ALL).to_string(), route).await
 }
 
 pub async fn bird_route_where_bgpmap(Path((servers, prefix)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
-    let command = format!("show route where net ~ [ {} ] all", prefix);
-    handle_bgpmap_command(servers, command, prefix).await
+    let pattern = NetPattern::parse(&prefix).map_err(|e| invalid_input_page("route_where_bgpmap", &servers, &prefix, &e))?;
+    handle_bgpmap_command(servers, BirdQuery::RouteWhere(pattern, RouteOptions::ALL).to_string(), prefix).await
 }
 
-// Traceroute handler
-pub async fn traceroute(Path((servers, target)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
+// Traceroute handler, streaming the hops of every server into the page as they arrive
+pub async fn traceroute(Path((servers, target)): Path<(String, String)>) -> Result<Response, Response> {
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    if server_list.len() > settings.servers.len() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid request: too many servers specified").into_response());
+    }
+    
+    // Validate all servers before processing
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return Err((StatusCode::BAD_REQUEST, e.to_string()).into_response());
+    }
+
+    let stream_context = TracerouteStreamContext {
+        target: target.clone(),
+        servers: server_list.iter().map(|server| settings.get_server_display_name(server)).collect(),
+    };
+    let sections = templates::render_traceroute_stream(&stream_context)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response())?;
+    let page_context = build_page_context("traceroute", &servers, &target, &format!("{}{}", sections, streaming::CONTENT_MARKER));
+    let (head, tail) = match templates::render_page(&page_context) {
+        Ok(page) => streaming::split_at_marker(&page),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response()),
+    };
+
+    // Events of all servers are interleaved as they come; each goes to its own section
+    let receivers = fanout::traceroute_stream_all(&server_list, &target);
+    let mut events = stream::select_all(receivers.into_iter().enumerate().map(|(index, receiver)| {
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|event| (event, receiver))
+        })
+        .map(move |event| (index, event))
+        .boxed()
+    }));
+
+    let (tx, body) = streaming::channel_body();
+    tokio::spawn(async move {
+        if tx.send(head).await.is_err() {
+            return;
+        }
+        loop {
+            let next = tokio::select! {
+                // The browser went away while no event was due
+                _ = tx.closed() => return,
+                next = events.next() => next,
+            };
+            let Some((index, event)) = next else {
+                break;
+            };
+            let call = match event {
+                Ok(TracerouteEvent::Line(line)) => script_call("tracerouteAppend", index, &format!("{}\n", line)),
+                Ok(TracerouteEvent::Report(report)) => script_call("tracerouteDone", index, &format_traceroute(&report)),
+                Ok(TracerouteEvent::Error(e)) => script_call("tracerouteError", index, &e),
+                Err(e) => script_call("tracerouteError", index, &e.to_string()),
+            };
+            // Dropping the receivers stops the proxy requests, which ends the traceroutes
+            if tx.send(call).await.is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(tail).await;
+    });
+
+    Ok(Html(body).into_response())
+}
+
+// Call a function of the streamed page with a section index and a string argument
+fn script_call(function: &str, index: usize, argument: &str) -> String {
+    // A JSON string is a valid JS literal; escaping '<' keeps "</script>" out of it
+    let literal = serde_json::to_string(argument).unwrap_or_default().replace('<', "\\u003c");
+    format!("<script>{}({}, {});</script>\n", function, index, literal)
+}
+
+// Ping handler
+pub async fn ping(Path((servers, target)): Path<(String, String)>) -> Result<impl IntoResponse, Response> {
+    tool_page("ping", servers, target, |server, target| async move {
+        proxy_client::ping_query(&server, &target).await
+    }, |result| format!("<pre>{}</pre>", html_escape::encode_text(result))).await
+}
+
+// Run a network tool such as traceroute on each server and show its output
+async fn tool_page<F, Fut, T>(
+    option: &str,
+    servers: String,
+    target: String,
+    query: F,
+    format: impl Fn(&T) -> String,
+) -> Result<Html<String>, Response>
+where
+    F: Fn(String, String) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
     let settings = Settings::global();
     let server_list = settings.resolve_servers_from_display_names(&servers);
     
@@ -151,16 +247,17 @@ pub async fn traceroute(Path((servers, target)): Path<(String, String)>) -> Resu
     }
     
     let mut content = String::new();
+    let results = fanout::query_all(&server_list, |server| query(server, target.clone())).await;
     
-    for server in &server_list {
+    for (server, result) in server_list.iter().zip(results) {
         let display_name = settings.get_server_display_name(server);
         
-        match proxy_client::traceroute_query(server, &target).await {
+        match result {
             Ok(result) => {
                 let bird_context = BirdContext {
                     server_name: display_name,
                     target: target.clone(),
-                    result: format!("<pre>{}</pre>", html_escape::encode_text(&result)),
+                    result: format(&result),
                 };
                 
                 match templates::render_bird(&bird_context) {
@@ -169,12 +266,79 @@ pub async fn traceroute(Path((servers, target)): Path<(String, String)>) -> Resu
                 }
             }
             Err(e) => {
-                content.push_str(&format!("<h2>{}: traceroute {}</h2><p>Error: {}</p>", display_name, target, e));
+                content.push_str(&format!(
+                    "<h2>{}: {} {}</h2><p>Error: {}</p>",
+                    display_name,
+                    option,
+                    html_escape::encode_text(&target),
+                    html_escape::encode_text(&e.to_string())
+                ));
             }
         }
     }
     
-    let page_context = build_page_context("traceroute", &servers, &target, &content);
+    let page_context = build_page_context(option, &servers, &target, &content);
+    
+    match templates::render_page(&page_context) {
+        Ok(html) => Ok(Html(html)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response()),
+    }
+}
+
+// DNS handler, comparing the answers of each server's resolver
+pub async fn dns(
+    Path((servers, target)): Path<(String, String)>,
+    Query(params): Query<DnsParams>,
+) -> Result<impl IntoResponse, Response> {
+    let settings = Settings::global();
+    let server_list = settings.resolve_servers_from_display_names(&servers);
+    
+    if server_list.len() > settings.servers.len() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid request: too many servers specified").into_response());
+    }
+    
+    // Validate all servers before processing
+    if let Err(e) = proxy_client::validate_servers(&server_list) {
+        return Err((StatusCode::BAD_REQUEST, e.to_string()).into_response());
+    }
+    
+    let target_ref = target.as_str();
+    let record_type = params.record_type.as_deref();
+    let replies = fanout::query_all(&server_list, |server| async move {
+        proxy_client::dns_query(&server, target_ref, record_type).await
+    }).await;
+    
+    let results: Vec<(String, Result<DnsReply, String>)> = server_list
+        .iter()
+        .zip(replies)
+        .map(|(server, reply)| (settings.get_server_display_name(server), reply.map_err(|e| e.to_string())))
+        .collect();
+    let comparisons = dns::compare(&results);
+    
+    let content = if comparisons.is_empty() {
+        // No server answered, so there is nothing to compare
+        results
+            .iter()
+            .filter_map(|(server, reply)| reply.as_ref().err().map(|e| (server, e)))
+            .map(|(server, e)| {
+                format!(
+                    "<h2>{}: dns {}</h2><p>Error: {}</p>",
+                    server,
+                    html_escape::encode_text(&target),
+                    html_escape::encode_text(e)
+                )
+            })
+            .collect()
+    } else {
+        let dns_context = DnsContext {
+            target: target.clone(),
+            compared: results.iter().filter(|(_, reply)| reply.is_ok()).count() > 1,
+            comparisons,
+        };
+        templates::render_dns(&dns_context).unwrap_or_else(|e| format!("<p>Template error: {}</p>", e))
+    };
+    
+    let page_context = build_page_context("dns", &servers, &target, &content);
     
     match templates::render_page(&page_context) {
         Ok(html) => Ok(Html(html)),
@@ -184,7 +348,7 @@ pub async fn traceroute(Path((servers, target)): Path<(String, String)>) -> Resu
 
 // Whois handler
 pub async fn whois(Path(target): Path<String>) -> Result<impl IntoResponse, Response> {
-    match whois::query(&target).await {
+    match whois::lookup(&target).await {
         Ok(result) => {
             let whois_context = WhoisContext {
                 target: target.clone(),
@@ -215,8 +379,22 @@ pub async fn whois(Path(target): Path<String>) -> Result<impl IntoResponse, Resp
     }
 }
 
+// Friendly page for input no command can be built from, keeping it in the search box
+fn invalid_input_page(option: &str, servers: &str, input: &str, error: &InvalidInput) -> Response {
+    let content = format!(
+        "<h2>Invalid input</h2><p>{}</p>",
+        html_escape::encode_text(&error.to_string())
+    );
+    let page_context = build_page_context(option, servers, input, &content);
+
+    match templates::render_page(&page_context) {
+        Ok(html) => (StatusCode::BAD_REQUEST, Html(html)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, format!("{} (template error: {})", error, e)).into_response(),
+    }
+}
+
 // Helper function to handle bird commands
-async fn handle_bird_command(servers: String, option: &str, command: String) -> Result<impl IntoResponse, Response> {
+async fn handle_bird_command(servers: String, option: &str, command: String) -> Result<Response, Response> {
     let settings = Settings::global();
     let server_list = settings.resolve_servers_from_display_names(&servers);
     
@@ -228,16 +406,27 @@ async fn handle_bird_command(servers: String, option: &str, command: String) ->
     if let Err(e) = proxy_client::validate_servers(&server_list) {
         return Err((StatusCode::BAD_REQUEST, e.to_string()).into_response());
     }
+
+    // Only the summary table and protocol cards need the complete output; everything else is streamed
+    if option != "summary" && option != "detail" {
+        return Ok(stream_bird_command(server_list, &servers, option, command));
+    }
     
     let mut content = String::new();
+    let command_ref = command.as_str();
+    let results = fanout::query_all(&server_list, |server| async move {
+        proxy_client::bird_query(&server, command_ref).await
+    }).await;
     
-    for server in &server_list {
+    for (server, result) in server_list.iter().zip(results) {
         let display_name = settings.get_server_display_name(server);
         
-        match proxy_client::bird_query(server, &command).await {
+        match result {
             Ok(result) => {
                 let formatted_result = if option == "summary" && result.starts_with("Name") {
                     format_summary_table(&result, server)
+                } else if option == "detail" {
+                    format_protocol_detail(&result, server)
                 } else {
                     format!("<pre>{}</pre>", html_escape::encode_text(&result))
                 };
@@ -262,11 +451,90 @@ async fn handle_bird_command(servers: String, option: &str, command: String) ->
     let page_context = build_page_context(option, &servers, &command, &content);
     
     match templates::render_page(&page_context) {
-        Ok(html) => Ok(Html(html)),
+        Ok(html) => Ok(Html(html).into_response()),
         Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response()),
     }
 }
 
+// Helper function to stream raw bird output of each server into the page as it arrives
+fn stream_bird_command(server_list: Vec<String>, servers: &str, option: &str, command: String) -> Response {
+    let page_context = build_page_context(option, servers, &command, streaming::CONTENT_MARKER);
+    let (head, tail) = match templates::render_page(&page_context) {
+        Ok(page) => streaming::split_at_marker(&page),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)).into_response(),
+    };
+
+    // All servers are queried at once; their output is forwarded in order
+    let replies = fanout::bird_stream_all(&server_list, &command);
+    let (tx, body) = streaming::channel_body();
+    tokio::spawn(async move {
+        if tx.send(head).await.is_err() {
+            return;
+        }
+        for (server, reply) in server_list.iter().zip(replies) {
+            if !stream_server_output(&tx, server, &command, reply).await {
+                return;
+            }
+        }
+        let _ = tx.send(tail).await;
+    });
+
+    Html(body).into_response()
+}
+
+// Stream one server's output as an escaped <pre> block. Returns false once the client is gone.
+async fn stream_server_output(tx: &streaming::BodySender, server: &str, command: &str, mut reply: fanout::TextReceiver) -> bool {
+    let settings = Settings::global();
+    let display_name = settings.get_server_display_name(server);
+
+    let first = match reply.recv().await {
+        Some(Err(e)) => {
+            let html = format!(
+                "<h2>{}: {}</h2><p>Error: {}</p>",
+                html_escape::encode_text(&display_name),
+                html_escape::encode_text(command),
+                html_escape::encode_text(&e.to_string())
+            );
+            return tx.send(html).await.is_ok();
+        }
+        first => first,
+    };
+
+    let bird_context = BirdContext {
+        server_name: display_name,
+        target: command.to_string(),
+        result: streaming::CONTENT_MARKER.to_string(),
+    };
+    let (prefix, suffix) = match templates::render_bird(&bird_context) {
+        Ok(rendered) => streaming::split_at_marker(&rendered),
+        Err(e) => (format!("<p>Template error: {}</p>", e), String::new()),
+    };
+
+    if tx.send(format!("{}<pre>", prefix)).await.is_err() {
+        return false;
+    }
+
+    let mut next = first;
+    while let Some(item) = next {
+        let chunk = match item {
+            Ok(text) => html_escape::encode_text(&text).to_string(),
+            Err(e) => {
+                let notice = format!("\n[Error: {}]", html_escape::encode_text(&e.to_string()));
+                if tx.send(notice).await.is_err() {
+                    return false;
+                }
+                break;
+            }
+        };
+        if tx.send(chunk).await.is_err() {
+            return false;
+        }
+        next = reply.recv().await;
+    }
+
+    tx.send(format!("</pre>{}", suffix)).await.is_ok()
+}
+
 // Helper function to handle BGP map commands
 async fn handle_bgpmap_command(servers: String, command: String, target: String) -> Result<impl IntoResponse, Response> {
     let settings = Settings::global();
@@ -281,15 +549,18 @@ async fn handle_bgpmap_command(servers: String, command: String, target: String)
         return Err((StatusCode::BAD_REQUEST, e.to_string()).into_response());
     }
     
-    let mut responses = Vec::new();
-    for server in &server_list {
-        match proxy_client::bird_query(server, &command).await {
-            Ok(result) => responses.push(result),
-            Err(e) => responses.push(format!("Error from {}: {}", server, e)),
-        }
-    }
+    let command_ref = command.as_str();
+    let results = fanout::query_all(&server_list, |server| async move {
+        proxy_client::bird_query(&server, command_ref).await
+    }).await;
+    
+    let responses: Vec<String> = server_list
+        .iter()
+        .zip(results)
+        .map(|(server, result)| result.unwrap_or_else(|e| format!("Error from {}: {}", server, e)))
+        .collect();
     
-    let dot_graph = bgpmap::bird_route_to_graphviz(&server_list, &responses, &target);
+    let dot_graph = bgpmap::bird_route_to_graphviz(&server_list, &responses, &target).await;
     let encoded_graph = general_purpose::STANDARD.encode(dot_graph);
     
     let bgpmap_context = BgpmapContext {
@@ -375,6 +646,8 @@ fn get_options() -> Vec<(String, String)> {
         ("generic".to_string(), "Generic".to_string()),
         ("whois".to_string(), "Whois".to_string()),
         ("traceroute".to_string(), "Traceroute".to_string()),
+        ("ping".to_string(), "Ping".to_string()),
+        ("dns".to_string(), "DNS".to_string()),
         ("route_bgpmap".to_string(), "Route BGP map".to_string()),
         ("route_where_bgpmap".to_string(), "Route where BGP map".to_string()),
     ]
@@ -385,7 +658,7 @@ fn format_summary_table(result: &str, server: &str) -> String {
     let settings = Settings::global();
     let display_name = settings.get_server_display_name(server);
     
-    match summary_parser::parse_summary(result, display_name) {
+    match summary_parser::parse_summary(result, display_name, &settings.protocol_filter) {
         Ok(summary_context) => {
             match templates::render_summary(&summary_context) {
                 Ok(rendered) => rendered,
@@ -397,4 +670,37 @@ fn format_summary_table(result: &str, server: &str) -> String {
             format!("<pre>{}</pre>", html_escape::encode_text(result))
         }
     }
-} 
\ No newline at end of file
+} 
+
+// Format traceroute hops as a table, or the text when there are none
+fn format_traceroute(report: &TracerouteReport) -> String {
+    if report.hops.is_empty() {
+        return format!("<pre>{}</pre>", html_escape::encode_text(&report.text));
+    }
+
+    match templates::render_traceroute(report) {
+        Ok(rendered) => rendered,
+        Err(e) => format!("<p>Template error: {}</p>", e),
+    }
+}
+
+// Format protocol detail as cards, keeping the raw output alongside
+fn format_protocol_detail(result: &str, server: &str) -> String {
+    let settings = Settings::global();
+    let protocols = protocol_parser::parse_protocols(result);
+
+    if protocols.is_empty() {
+        return format!("<pre>{}</pre>", html_escape::encode_text(result));
+    }
+
+    let protocol_context = ProtocolContext {
+        server_name: settings.get_server_display_name(server),
+        protocols,
+        raw: result.to_string(),
+    };
+
+    match templates::render_protocol(&protocol_context) {
+        Ok(rendered) => rendered,
+        Err(e) => format!("<p>Template error: {}</p>", e),
+    }
+}
@@ -4,6 +4,7 @@ use axum::{
     Router,
 };
 use clap::Parser;
+use serde::Deserialize;
 use std::net::SocketAddr;
 use tower::ServiceBuilder;
 use tower_http::{
@@ -31,6 +32,8 @@ mod settings;
 mod handlers;
 mod templates;
 mod summary_parser;
+mod route_parser;
+mod protocol_parser;
 mod proxy_client;
 mod bgpmap;
 mod whois;
@@ -38,15 +41,31 @@ mod api;
 mod telegram;
 mod static_files;
 mod csp;
-
-use settings::Settings;
-
-#[derive(Parser)]
+mod streaming;
+mod fanout;
+mod asn;
+mod net_mode;
+mod config_file;
+mod signature;
+mod bird_command;
+mod exporter;
+mod dns;
+mod traceroute;
+
+use settings::{ServerEntry, Settings};
+
+#[derive(Parser, Deserialize)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Configuration file (YAML or TOML)
+    #[arg(long)]
+    #[serde(skip)]
+    config: Option<String>,
+
     /// Server name prefixes, separated by comma
     #[arg(long, value_delimiter = ',')]
-    servers: Vec<String>,
+    #[serde(default, deserialize_with = "config_file::comma_separated")]
+    servers: Vec<ServerEntry>,
 
     /// Server name domain suffixes
     #[arg(long, default_value = "")]
@@ -68,6 +87,14 @@ struct Args {
     #[arg(long, default_value = "asn.cymru.com")]
     dns_interface: String,
 
+    /// Time to cache ASN information for bgpmap, in seconds
+    #[arg(long, default_value = "3600")]
+    asn_cache_ttl: u64,
+
+    /// Maximum number of concurrent ASN lookups
+    #[arg(long, default_value = "8")]
+    asn_max_concurrent: usize,
+
     /// The infos displayed in bgpmap, separated by comma
     #[arg(long, default_value = "asn,as-name,ASName,descr")]
     bgpmap_info: String,
@@ -92,12 +119,13 @@ struct Args {
     #[arg(long, default_value = "all")]
     navbar_all_url: String,
 
-    /// Apply network-specific changes for some networks
+    /// Apply network-specific changes for some networks (dn42, dn42_generic, dn42_shorten, shorten)
     #[arg(long, default_value = "")]
     net_specific_mode: String,
 
     /// Protocol types to show in summary tables (comma separated list)
     #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
     protocol_filter: Option<Vec<String>>,
 
     /// Protocol names to hide in summary tables (RE2 syntax)
@@ -108,6 +136,10 @@ struct Args {
     #[arg(long, default_value = "120")]
     timeout: u64,
 
+    /// Maximum number of servers queried at the same time
+    #[arg(long, default_value = "10")]
+    max_concurrent: usize,
+
     /// Telegram bot name
     #[arg(long, default_value = "")]
     telegram_bot_name: String,
@@ -119,6 +151,22 @@ struct Args {
     /// Authentication token for proxy requests
     #[arg(long)]
     auth_token: Option<String>,
+
+    /// How proxy requests carry the token: bearer, or hmac to sign them
+    #[arg(long, default_value = "bearer")]
+    auth_scheme: String,
+
+    /// Client certificate (PEM) presented to proxies that require mTLS
+    #[arg(long)]
+    proxy_client_cert: Option<String>,
+
+    /// Private key (PEM) of the proxy client certificate
+    #[arg(long)]
+    proxy_client_key: Option<String>,
+
+    /// Interval between BGP session polls for /metrics, in seconds (0 disables /metrics)
+    #[arg(long, default_value = "0")]
+    metrics_interval: u64,
 }
 
 /// Create Unix socket listener on Unix systems
@@ -174,69 +222,90 @@ async fn build_router() -> Router {
         .route("/summary/", get(handlers::redirect_to_summary))
         
         // Bird protocol queries
-        .route("/summary/:servers", get(handlers::bird_summary))
-        .route("/summary/:servers/", get(handlers::bird_summary))
-        .route("/detail/:servers/:protocol", get(handlers::bird_detail))
-        .route("/detail/:servers/:protocol/", get(handlers::bird_detail))
-        .route("/route/:servers/:route", get(handlers::bird_route))
-        .route("/route/:servers/:route/", get(handlers::bird_route))
-        .route("/route_all/:servers/:route", get(handlers::bird_route_all))
-        .route("/route_all/:servers/:route/", get(handlers::bird_route_all))
-        .route("/route_where/:servers/:prefix", get(handlers::bird_route_where))
-        .route("/route_where/:servers/:prefix/", get(handlers::bird_route_where))
-        .route("/route_where_all/:servers/:prefix", get(handlers::bird_route_where_all))
-        .route("/route_where_all/:servers/:prefix/", get(handlers::bird_route_where_all))
-        .route("/route_bgpmap/:servers/:route", get(handlers::bird_route_bgpmap))
-        .route("/route_bgpmap/:servers/:route/", get(handlers::bird_route_bgpmap))
-        .route("/route_where_bgpmap/:servers/:prefix", get(handlers::bird_route_where_bgpmap))
-        .route("/route_where_bgpmap/:servers/:prefix/", get(handlers::bird_route_where_bgpmap))
-        .route("/route_from_protocol/:servers/:protocol", get(handlers::bird_route_from_protocol))
-        .route("/route_from_protocol/:servers/:protocol/", get(handlers::bird_route_from_protocol))
-        .route("/route_from_protocol_all/:servers/:protocol", get(handlers::bird_route_from_protocol_all))
-        .route("/route_from_protocol_all/:servers/:protocol/", get(handlers::bird_route_from_protocol_all))
-        .route("/route_from_protocol_primary/:servers/:protocol", get(handlers::bird_route_from_protocol_primary))
-        .route("/route_from_protocol_primary/:servers/:protocol/", get(handlers::bird_route_from_protocol_primary))
-        .route("/route_from_protocol_all_primary/:servers/:protocol", get(handlers::bird_route_from_protocol_all_primary))
-        .route("/route_from_protocol_all_primary/:servers/:protocol/", get(handlers::bird_route_from_protocol_all_primary))
-        .route("/route_filtered_from_protocol/:servers/:protocol", get(handlers::bird_route_filtered_from_protocol))
-        .route("/route_filtered_from_protocol/:servers/:protocol/", get(handlers::bird_route_filtered_from_protocol))
-        .route("/route_filtered_from_protocol_all/:servers/:protocol", get(handlers::bird_route_filtered_from_protocol_all))
-        .route("/route_filtered_from_protocol_all/:servers/:protocol/", get(handlers::bird_route_filtered_from_protocol_all))
-        .route("/route_from_origin/:servers/:asn", get(handlers::bird_route_from_origin))
-        .route("/route_from_origin/:servers/:asn/", get(handlers::bird_route_from_origin))
-        .route("/route_from_origin_all/:servers/:asn", get(handlers::bird_route_from_origin_all))
-        .route("/route_from_origin_all/:servers/:asn/", get(handlers::bird_route_from_origin_all))
-        .route("/route_from_origin_primary/:servers/:asn", get(handlers::bird_route_from_origin_primary))
-        .route("/route_from_origin_primary/:servers/:asn/", get(handlers::bird_route_from_origin_primary))
-        .route("/route_from_origin_all_primary/:servers/:asn", get(handlers::bird_route_from_origin_all_primary))
-        .route("/route_from_origin_all_primary/:servers/:asn/", get(handlers::bird_route_from_origin_all_primary))
-        .route("/route_generic/:servers/:command", get(handlers::bird_route_generic))
-        .route("/route_generic/:servers/:command/", get(handlers::bird_route_generic))
-        .route("/generic/:servers/:command", get(handlers::bird_generic))
-        .route("/generic/:servers/:command/", get(handlers::bird_generic))
+        .route("/summary/{servers}", get(handlers::bird_summary))
+        .route("/summary/{servers}/", get(handlers::bird_summary))
+        .route("/detail/{servers}/{protocol}", get(handlers::bird_detail))
+        .route("/detail/{servers}/{protocol}/", get(handlers::bird_detail))
+        .route("/route/{servers}/{route}", get(handlers::bird_route))
+        .route("/route/{servers}/{route}/", get(handlers::bird_route))
+        .route("/route_all/{servers}/{route}", get(handlers::bird_route_all))
+        .route("/route_all/{servers}/{route}/", get(handlers::bird_route_all))
+        .route("/route_where/{servers}/{prefix}", get(handlers::bird_route_where))
+        .route("/route_where/{servers}/{prefix}/", get(handlers::bird_route_where))
+        .route("/route_where_all/{servers}/{prefix}", get(handlers::bird_route_where_all))
+        .route("/route_where_all/{servers}/{prefix}/", get(handlers::bird_route_where_all))
+        .route("/route_bgpmap/{servers}/{route}", get(handlers::bird_route_bgpmap))
+        .route("/route_bgpmap/{servers}/{route}/", get(handlers::bird_route_bgpmap))
+        .route("/route_where_bgpmap/{servers}/{prefix}", get(handlers::bird_route_where_bgpmap))
+        .route("/route_where_bgpmap/{servers}/{prefix}/", get(handlers::bird_route_where_bgpmap))
+        .route("/route_from_protocol/{servers}/{protocol}", get(handlers::bird_route_from_protocol))
+        .route("/route_from_protocol/{servers}/{protocol}/", get(handlers::bird_route_from_protocol))
+        .route("/route_from_protocol_all/{servers}/{protocol}", get(handlers::bird_route_from_protocol_all))
+        .route("/route_from_protocol_all/{servers}/{protocol}/", get(handlers::bird_route_from_protocol_all))
+        .route("/route_from_protocol_primary/{servers}/{protocol}", get(handlers::bird_route_from_protocol_primary))
+        .route("/route_from_protocol_primary/{servers}/{protocol}/", get(handlers::bird_route_from_protocol_primary))
+        .route("/route_from_protocol_all_primary/{servers}/{protocol}", get(handlers::bird_route_from_protocol_all_primary))
+        .route("/route_from_protocol_all_primary/{servers}/{protocol}/", get(handlers::bird_route_from_protocol_all_primary))
+        .route("/route_filtered_from_protocol/{servers}/{protocol}", get(handlers::bird_route_filtered_from_protocol))
+        .route("/route_filtered_from_protocol/{servers}/{protocol}/", get(handlers::bird_route_filtered_from_protocol))
+        .route("/route_filtered_from_protocol_all/{servers}/{protocol}", get(handlers::bird_route_filtered_from_protocol_all))
+        .route("/route_filtered_from_protocol_all/{servers}/{protocol}/", get(handlers::bird_route_filtered_from_protocol_all))
+        .route("/route_from_origin/{servers}/{asn}", get(handlers::bird_route_from_origin))
+        .route("/route_from_origin/{servers}/{asn}/", get(handlers::bird_route_from_origin))
+        .route("/route_from_origin_all/{servers}/{asn}", get(handlers::bird_route_from_origin_all))
+        .route("/route_from_origin_all/{servers}/{asn}/", get(handlers::bird_route_from_origin_all))
+        .route("/route_from_origin_primary/{servers}/{asn}", get(handlers::bird_route_from_origin_primary))
+        .route("/route_from_origin_primary/{servers}/{asn}/", get(handlers::bird_route_from_origin_primary))
+        .route("/route_from_origin_all_primary/{servers}/{asn}", get(handlers::bird_route_from_origin_all_primary))
+        .route("/route_from_origin_all_primary/{servers}/{asn}/", get(handlers::bird_route_from_origin_all_primary))
+        .route("/route_generic/{servers}/{command}", get(handlers::bird_route_generic))
+        .route("/route_generic/{servers}/{command}/", get(handlers::bird_route_generic))
+        .route("/generic/{servers}/{command}", get(handlers::bird_generic))
+        .route("/generic/{servers}/{command}/", get(handlers::bird_generic))
         
         // Traceroute
-        .route("/traceroute/:servers/:target", get(handlers::traceroute))
-        .route("/traceroute/:servers/:target/", get(handlers::traceroute))
+        .route("/traceroute/{servers}/{target}", get(handlers::traceroute))
+        .route("/traceroute/{servers}/{target}/", get(handlers::traceroute))
+
+        // Ping
+        .route("/ping/{servers}/{target}", get(handlers::ping))
+        .route("/ping/{servers}/{target}/", get(handlers::ping))
+
+        // DNS
+        .route("/dns/{servers}/{target}", get(handlers::dns))
+        .route("/dns/{servers}/{target}/", get(handlers::dns))
         
         // Whois
-        .route("/whois/:target", get(handlers::whois))
-        .route("/whois/:target/", get(handlers::whois))
+        .route("/whois/{target}", get(handlers::whois))
+        .route("/whois/{target}/", get(handlers::whois))
         
         // API endpoints
-        .route("/api/bird/:servers/:command", get(api::bird_api))
-        .route("/api/bird/:servers/:command/", get(api::bird_api))
-        .route("/api/traceroute/:servers/:target", get(api::traceroute_api))
-        .route("/api/traceroute/:servers/:target/", get(api::traceroute_api))
-        .route("/api/whois/:target", get(api::whois_api))
-        .route("/api/whois/:target/", get(api::whois_api))
+        .route("/api/bird/{servers}/{command}", get(api::bird_api))
+        .route("/api/bird/{servers}/{command}/", get(api::bird_api))
+        .route("/api/summary/{servers}", get(api::summary_api))
+        .route("/api/summary/{servers}/", get(api::summary_api))
+        .route("/api/routes/{servers}/{command}", get(api::routes_api))
+        .route("/api/routes/{servers}/{command}/", get(api::routes_api))
+        .route("/api/detail/{servers}/{protocol}", get(api::detail_api))
+        .route("/api/detail/{servers}/{protocol}/", get(api::detail_api))
+        .route("/api/traceroute/{servers}/{target}", get(api::traceroute_api))
+        .route("/api/traceroute/{servers}/{target}/", get(api::traceroute_api))
+        .route("/api/ping/{servers}/{target}", get(api::ping_api))
+        .route("/api/ping/{servers}/{target}/", get(api::ping_api))
+        .route("/api/dns/{servers}/{target}", get(api::dns_api))
+        .route("/api/dns/{servers}/{target}/", get(api::dns_api))
+        .route("/api/whois/{target}", get(api::whois_api))
+        .route("/api/whois/{target}/", get(api::whois_api))
         
+        // BGP session metrics
+        .route("/metrics", get(exporter::metrics_handler))
+
         // Telegram bot webhook (if enabled)
         .route("/telegram", get(telegram::telegram_webhook).post(telegram::telegram_webhook))
-        .route("/telegram/*servers", get(telegram::telegram_webhook).post(telegram::telegram_webhook))
+        .route("/telegram/{*servers}", get(telegram::telegram_webhook).post(telegram::telegram_webhook))
         
         // Static assets
-        .route("/static/*path", get(static_files::serve_static))
+        .route("/static/{*path}", get(static_files::serve_static))
         
         .layer(
             ServiceBuilder::new()
@@ -250,18 +319,25 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "bird_lg_rs=info".into()),
+                .unwrap_or_else(|_| "bird_lg_rs=info,bird_lg_common=info".into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let args = Args::parse();
+    let loader = config_file::Loader::from_env::<Args>();
     
     // Initialize settings
-    Settings::init(args).await?;
+    Settings::init(loader.load()?).await?;
 
     // Initialize templates
     templates::init()?;
+    asn::init()?;
+
+    // Reload settings on SIGHUP or when the configuration file changes
+    config_file::watch(loader, |loader| Settings::reload(loader.load()?));
+
+    // Poll servers for /metrics in the background
+    exporter::spawn_poller();
 
     let settings = Settings::global();
     info!("Listening on {}...", settings.listen);
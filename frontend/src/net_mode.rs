@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Result};
+
+/// First ASN of the dn42 private range; short ASNs below 10000 are offsets into it
+const DN42_ASN_BASE: u64 = 4242420000;
+
+/// Whois keys hidden by the shorten filter, unless too little would be left
+const SHORTEN_HIDDEN_KEYS: &[&str] = &[
+    "remarks", "admin-c", "tech-c", "abuse-c", "notify", "changed", "created", "last-modified", "source", "status",
+];
+
+/// Network-specific behaviour selected with `net_specific_mode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NetSpecificMode {
+    #[default]
+    None,
+    /// dn42 short ASNs and the dn42 whois filter
+    Dn42,
+    /// dn42 short ASNs with unfiltered whois output
+    Dn42Generic,
+    /// dn42 short ASNs with the shortened whois output
+    Dn42Shorten,
+    /// Shortened whois output
+    Shorten,
+}
+
+impl NetSpecificMode {
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode.trim() {
+            "" => Ok(Self::None),
+            "dn42" => Ok(Self::Dn42),
+            "dn42_generic" => Ok(Self::Dn42Generic),
+            "dn42_shorten" => Ok(Self::Dn42Shorten),
+            "shorten" => Ok(Self::Shorten),
+            other => Err(anyhow!(
+                "Invalid net_specific_mode '{}': expected dn42, dn42_generic, dn42_shorten or shorten",
+                other
+            )),
+        }
+    }
+
+    fn is_dn42(self) -> bool {
+        matches!(self, Self::Dn42 | Self::Dn42Generic | Self::Dn42Shorten)
+    }
+
+    fn expand_asn(self, asn: u64) -> u64 {
+        if self.is_dn42() && asn < 10000 {
+            asn + DN42_ASN_BASE
+        } else {
+            asn
+        }
+    }
+
+    /// Whois query for a target; in dn42 modes bare numbers become ASNs, so
+    /// `3914` is looked up as `AS4242423914`
+    pub fn whois_target(self, target: &str) -> String {
+        match target.trim().parse::<u64>() {
+            Ok(asn) if self.is_dn42() => format!("AS{}", self.expand_asn(asn)),
+            _ => target.to_string(),
+        }
+    }
+
+    /// ASN for a `bgp_path` condition, accepting an `AS` prefix and dn42
+    /// short ASNs; `None` if it is not a 32-bit ASN
+    pub fn origin_asn(self, asn: &str) -> Option<u32> {
+        let trimmed = asn.trim();
+        let number = trimmed
+            .strip_prefix("AS")
+            .or_else(|| trimmed.strip_prefix("as"))
+            .unwrap_or(trimmed);
+
+        let asn = number.parse::<u64>().ok()?;
+        u32::try_from(self.expand_asn(asn)).ok()
+    }
+
+    /// Filter whois output for display
+    pub fn filter_whois(self, result: &str) -> String {
+        match self {
+            Self::Dn42 => dn42_whois_filter(result),
+            Self::Dn42Shorten | Self::Shorten => shorten_whois_filter(result),
+            Self::None | Self::Dn42Generic => result.to_string(),
+        }
+    }
+}
+
+/// Keep only the queried object, dropping the related objects the dn42
+/// registry appends and all comment lines
+fn dn42_whois_filter(result: &str) -> String {
+    let mut lines = Vec::new();
+    for line in result.lines() {
+        if line.starts_with("% Information related to") && !lines.is_empty() {
+            break;
+        }
+        if line.starts_with('%') || line.trim().is_empty() {
+            continue;
+        }
+        lines.push(line.trim_end());
+    }
+    lines.join("\n")
+}
+
+/// Keep `key: value` lines only, hiding contact and bookkeeping fields when
+/// at least five other lines remain
+fn shorten_whois_filter(result: &str) -> String {
+    let lines: Vec<&str> = result
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('%') && !line.starts_with('#'))
+        .filter(|line| line.contains(':'))
+        .collect();
+
+    let short: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|line| {
+            let key = line.split(':').next().unwrap_or("").trim();
+            !key.starts_with("mnt-") && !SHORTEN_HIDDEN_KEYS.contains(&key)
+        })
+        .collect();
+
+    if short.len() < 5 {
+        lines.join("\n")
+    } else {
+        short.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHOIS_DN42: &str = include_str!("../tests/fixtures/whois_dn42_aut_num.txt");
+    const WHOIS_RIPE: &str = include_str!("../tests/fixtures/whois_ripe_inetnum.txt");
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(NetSpecificMode::parse("").unwrap(), NetSpecificMode::None);
+        assert_eq!(NetSpecificMode::parse("dn42").unwrap(), NetSpecificMode::Dn42);
+        assert_eq!(NetSpecificMode::parse("dn42_generic").unwrap(), NetSpecificMode::Dn42Generic);
+        assert_eq!(NetSpecificMode::parse("dn42_shorten").unwrap(), NetSpecificMode::Dn42Shorten);
+        assert_eq!(NetSpecificMode::parse("shorten").unwrap(), NetSpecificMode::Shorten);
+        assert!(NetSpecificMode::parse("dn43").is_err());
+    }
+
+    #[test]
+    fn test_whois_target() {
+        let dn42 = NetSpecificMode::Dn42;
+        assert_eq!(dn42.whois_target("3914"), "AS4242423914");
+        assert_eq!(dn42.whois_target("4242423914"), "AS4242423914");
+        assert_eq!(dn42.whois_target("AS4242423914"), "AS4242423914");
+        assert_eq!(dn42.whois_target("172.20.0.53"), "172.20.0.53");
+        assert_eq!(NetSpecificMode::Dn42Shorten.whois_target("3914"), "AS4242423914");
+
+        // Outside dn42, numbers are passed through untouched
+        assert_eq!(NetSpecificMode::None.whois_target("3914"), "3914");
+        assert_eq!(NetSpecificMode::Shorten.whois_target("3914"), "3914");
+    }
+
+    #[test]
+    fn test_origin_asn() {
+        let dn42 = NetSpecificMode::Dn42Generic;
+        assert_eq!(dn42.origin_asn("3914"), Some(4242423914));
+        assert_eq!(dn42.origin_asn("AS3914"), Some(4242423914));
+        assert_eq!(dn42.origin_asn("4242423914"), Some(4242423914));
+        assert_eq!(dn42.origin_asn("64512"), Some(64512));
+
+        let none = NetSpecificMode::None;
+        assert_eq!(none.origin_asn("3914"), Some(3914));
+        assert_eq!(none.origin_asn("as13335"), Some(13335));
+        assert_eq!(none.origin_asn("not-an-asn"), None);
+        assert_eq!(none.origin_asn("4294967296"), None);
+        assert_eq!(none.origin_asn("13335 all"), None);
+    }
+
+    #[test]
+    fn test_dn42_filter_keeps_queried_object() {
+        let result = NetSpecificMode::Dn42.filter_whois(WHOIS_DN42);
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines.first().copied(), Some("aut-num:            AS4242423914"));
+        assert_eq!(lines.last().copied(), Some("source:             DN42"));
+        assert_eq!(lines.len(), 9);
+        assert!(!result.contains("as-block:"));
+        assert!(!result.contains('%'));
+    }
+
+    #[test]
+    fn test_dn42_filter_with_leading_header() {
+        // RIPE-style output opens with an "Information related to" header
+        let result = NetSpecificMode::Dn42.filter_whois(WHOIS_RIPE);
+        assert!(result.starts_with("inetnum:"));
+        assert!(!result.contains("route:"));
+    }
+
+    #[test]
+    fn test_shorten_filter() {
+        let result = NetSpecificMode::Shorten.filter_whois(WHOIS_RIPE);
+        assert!(result.starts_with("inetnum:        193.0.0.0 - 193.0.7.255"));
+        assert!(result.contains("origin:         AS3333"));
+        assert!(!result.contains('%'));
+        assert!(!result.contains("mnt-by:"));
+        assert!(!result.contains("last-modified:"));
+        assert!(!result.contains("remarks:"));
+
+        // Too little left after hiding fields: keep them
+        let short = "aut-num: AS64512\nmnt-by: EXAMPLE-MNT\nsource: TEST\n";
+        assert_eq!(NetSpecificMode::Dn42Shorten.filter_whois(short), "aut-num: AS64512\nmnt-by: EXAMPLE-MNT\nsource: TEST");
+    }
+
+    #[test]
+    fn test_generic_modes_pass_whois_through() {
+        assert_eq!(NetSpecificMode::None.filter_whois(WHOIS_DN42), WHOIS_DN42);
+        assert_eq!(NetSpecificMode::Dn42Generic.filter_whois(WHOIS_DN42), WHOIS_DN42);
+    }
+}
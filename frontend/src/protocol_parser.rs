@@ -0,0 +1,387 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// A protocol from BIRD 2.x `show protocols all` output
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ProtocolDetail {
+    pub name: String,
+    pub proto: String,
+    pub table: String,
+    pub state: String,
+    pub since: String,
+    pub info: String,
+    pub description: Option<String>,
+    /// Present for BGP protocols only
+    pub bgp: Option<BgpSession>,
+    pub last_error: Option<String>,
+    pub channels: Vec<Channel>,
+    /// Every other `Key: value` line of the protocol
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BgpSession {
+    pub state: String,
+    pub neighbor_address: Option<String>,
+    pub neighbor_as: Option<u32>,
+    pub local_as: Option<u32>,
+    pub neighbor_id: Option<String>,
+    pub session: Option<String>,
+    pub source_address: Option<String>,
+    pub hold_timer: Option<Timer>,
+    pub keepalive_timer: Option<Timer>,
+    pub connect_delay: Option<Timer>,
+}
+
+/// A running timer, shown by BIRD as `remaining/interval`
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Timer {
+    pub remaining: f64,
+    pub interval: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub state: Option<String>,
+    pub table: Option<String>,
+    pub preference: Option<u32>,
+    pub input_filter: Option<String>,
+    pub output_filter: Option<String>,
+    pub routes: Option<RouteCounts>,
+    pub limits: Vec<RouteLimit>,
+    pub stats: Vec<RouteChangeStats>,
+    /// Every other `Key: value` line of the channel
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct RouteCounts {
+    pub imported: u64,
+    pub filtered: u64,
+    pub exported: u64,
+    pub preferred: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct RouteLimit {
+    /// receive, import or export
+    pub kind: String,
+    pub limit: u64,
+    pub action: Option<String>,
+}
+
+/// One row of the `Route change stats` table; `---` cells are `None`
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct RouteChangeStats {
+    /// Such as `Import updates` or `Export withdraws`
+    pub name: String,
+    pub received: Option<u64>,
+    pub rejected: Option<u64>,
+    pub filtered: Option<u64>,
+    pub ignored: Option<u64>,
+    pub accepted: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Protocol,
+    Bgp,
+    Channel,
+}
+
+fn protocol_header_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?P<name>\S+)\s+(?P<proto>\S+)\s+(?P<table>\S+)\s+(?P<state>\S+)\s+(?P<since>[0-9\-\. :]+)(?P<info>.*)$")
+            .expect("Invalid regex pattern")
+    })
+}
+
+fn parse_timer(value: &str) -> Option<Timer> {
+    let (remaining, interval) = value.split_once('/')?;
+    Some(Timer {
+        remaining: remaining.trim().parse().ok()?,
+        interval: interval.trim().parse().ok()?,
+    })
+}
+
+/// Parse `812 imported, 3 filtered, 1204 exported, 790 preferred`
+fn parse_route_counts(value: &str) -> RouteCounts {
+    let mut counts = RouteCounts::default();
+    for part in value.split(',') {
+        let mut words = part.split_whitespace();
+        let (Some(count), Some(kind)) = (words.next().and_then(|n| n.parse().ok()), words.next()) else {
+            continue;
+        };
+        match kind {
+            "imported" => counts.imported = count,
+            "filtered" => counts.filtered = count,
+            "exported" => counts.exported = count,
+            "preferred" => counts.preferred = count,
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// Parse a `Import updates:  45210  0  12  108  45090` row
+fn parse_change_stats(name: &str, value: &str) -> Option<RouteChangeStats> {
+    let cells: Vec<Option<u64>> = value.split_whitespace().map(|cell| cell.parse().ok()).collect();
+    let [received, rejected, filtered, ignored, accepted] = cells[..] else {
+        return None;
+    };
+    Some(RouteChangeStats {
+        name: name.to_string(),
+        received,
+        rejected,
+        filtered,
+        ignored,
+        accepted,
+    })
+}
+
+fn apply_protocol_attribute(protocol: &mut ProtocolDetail, key: &str, value: &str) -> Section {
+    match key {
+        "Description" => protocol.description = Some(value.to_string()),
+        "BGP state" => {
+            protocol.bgp = Some(BgpSession {
+                state: value.to_string(),
+                ..Default::default()
+            });
+            return Section::Bgp;
+        }
+        "Last error" => protocol.last_error = Some(value.to_string()),
+        _ => {
+            protocol.attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+    Section::Protocol
+}
+
+fn apply_bgp_attribute(protocol: &mut ProtocolDetail, key: &str, value: &str) {
+    let Some(bgp) = protocol.bgp.as_mut() else {
+        return;
+    };
+    match key {
+        "Neighbor address" => bgp.neighbor_address = Some(value.to_string()),
+        "Neighbor AS" => bgp.neighbor_as = value.parse().ok(),
+        "Local AS" => bgp.local_as = value.parse().ok(),
+        "Neighbor ID" => bgp.neighbor_id = Some(value.to_string()),
+        "Session" => bgp.session = Some(value.to_string()),
+        "Source address" => bgp.source_address = Some(value.to_string()),
+        "Hold timer" => bgp.hold_timer = parse_timer(value),
+        "Keepalive timer" => bgp.keepalive_timer = parse_timer(value),
+        "Connect delay" => bgp.connect_delay = parse_timer(value),
+        "Last error" => protocol.last_error = Some(value.to_string()),
+        _ => {
+            protocol.attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+fn apply_channel_attribute(channel: &mut Channel, indent: usize, key: &str, value: &str) {
+    // Deeper lines qualify the previous one: a limit's action or a stats row
+    if indent > 4 {
+        if key == "Action" {
+            if let Some(limit) = channel.limits.last_mut() {
+                limit.action = Some(value.to_string());
+            }
+        } else if let Some(stats) = parse_change_stats(key, value) {
+            channel.stats.push(stats);
+        }
+        return;
+    }
+
+    match key {
+        "State" => channel.state = Some(value.to_string()),
+        "Table" => channel.table = Some(value.to_string()),
+        "Preference" => channel.preference = value.parse().ok(),
+        "Input filter" => channel.input_filter = Some(value.to_string()),
+        "Output filter" => channel.output_filter = Some(value.to_string()),
+        "Routes" => channel.routes = Some(parse_route_counts(value)),
+        "Route change stats" => {}
+        "Receive limit" | "Import limit" | "Export limit" => {
+            if let Ok(limit) = value.parse() {
+                channel.limits.push(RouteLimit {
+                    kind: key.trim_end_matches(" limit").to_lowercase(),
+                    limit,
+                    action: None,
+                });
+            }
+        }
+        _ => {
+            channel.attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Parse `show protocols all` output into protocols, in output order
+pub fn parse_protocols(data: &str) -> Vec<ProtocolDetail> {
+    let mut protocols: Vec<ProtocolDetail> = Vec::new();
+    let mut section = Section::Protocol;
+
+    for line in data.lines() {
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+
+        // Unindented lines are the table header or a protocol's summary row
+        if indent == 0 {
+            if let Some(captures) = protocol_header_re().captures(text).filter(|_| !text.starts_with("Name ")) {
+                protocols.push(ProtocolDetail {
+                    name: captures["name"].to_string(),
+                    proto: captures["proto"].to_string(),
+                    table: captures["table"].to_string(),
+                    state: captures["state"].to_string(),
+                    since: captures["since"].trim().to_string(),
+                    info: captures["info"].trim().to_string(),
+                    ..Default::default()
+                });
+                section = Section::Protocol;
+            }
+            continue;
+        }
+
+        let Some(protocol) = protocols.last_mut() else {
+            continue;
+        };
+
+        if indent <= 2 {
+            if let Some(name) = text.strip_prefix("Channel ") {
+                protocol.channels.push(Channel {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                section = Section::Channel;
+                continue;
+            }
+            section = Section::Protocol;
+        }
+
+        // Capability listings and other headings carry no value
+        let Some((key, value)) = text.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        match section {
+            Section::Protocol => section = apply_protocol_attribute(protocol, key, value),
+            // Capability details sit deeper than the session fields
+            Section::Bgp if indent > 4 => {}
+            Section::Bgp => apply_bgp_attribute(protocol, key, value),
+            Section::Channel => {
+                if let Some(channel) = protocol.channels.last_mut() {
+                    apply_channel_attribute(channel, indent, key, value);
+                }
+            }
+        }
+    }
+
+    protocols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROTOCOLS_ALL_BGP: &str = include_str!("../tests/fixtures/show_protocols_all_bgp.txt");
+    const PROTOCOLS_ALL_DOWN: &str = include_str!("../tests/fixtures/show_protocols_all_down.txt");
+
+    #[test]
+    fn test_parse_established_session() {
+        let protocols = parse_protocols(PROTOCOLS_ALL_BGP);
+        assert_eq!(protocols.len(), 1);
+
+        let protocol = &protocols[0];
+        assert_eq!(protocol.name, "dn42_kioubit");
+        assert_eq!(protocol.proto, "BGP");
+        assert_eq!(protocol.table, "---");
+        assert_eq!(protocol.state, "up");
+        assert_eq!(protocol.since, "2024-03-02 11:45:21");
+        assert_eq!(protocol.info, "Established");
+        assert_eq!(protocol.description.as_deref(), Some("Kioubit Network"));
+        assert_eq!(protocol.last_error, None);
+
+        let bgp = protocol.bgp.as_ref().unwrap();
+        assert_eq!(bgp.state, "Established");
+        assert_eq!(bgp.neighbor_address.as_deref(), Some("fe80::ade0%dn42_kioubit"));
+        assert_eq!(bgp.neighbor_as, Some(4242423914));
+        assert_eq!(bgp.local_as, Some(4242421234));
+        assert_eq!(bgp.neighbor_id.as_deref(), Some("172.20.53.97"));
+        assert_eq!(bgp.session.as_deref(), Some("external AS4"));
+        assert_eq!(bgp.source_address.as_deref(), Some("fe80::1234"));
+        assert_eq!(bgp.hold_timer, Some(Timer { remaining: 196.532, interval: 240 }));
+        assert_eq!(bgp.keepalive_timer, Some(Timer { remaining: 41.204, interval: 80 }));
+
+        // Capability details must not leak into the attributes
+        assert!(!protocol.attributes.contains_key("AF announced"));
+
+        assert_eq!(protocol.channels.len(), 2);
+        let ipv4 = &protocol.channels[0];
+        assert_eq!(ipv4.name, "ipv4");
+        assert_eq!(ipv4.state.as_deref(), Some("UP"));
+        assert_eq!(ipv4.table.as_deref(), Some("master4"));
+        assert_eq!(ipv4.preference, Some(100));
+        assert_eq!(ipv4.input_filter.as_deref(), Some("(unnamed)"));
+        assert_eq!(ipv4.routes, Some(RouteCounts { imported: 812, filtered: 3, exported: 1204, preferred: 790 }));
+        assert_eq!(ipv4.limits, vec![RouteLimit {
+            kind: "import".to_string(),
+            limit: 10000,
+            action: Some("block".to_string()),
+        }]);
+        assert_eq!(ipv4.stats.len(), 4);
+        assert_eq!(ipv4.stats[1], RouteChangeStats {
+            name: "Import withdraws".to_string(),
+            received: Some(2201),
+            rejected: Some(0),
+            filtered: None,
+            ignored: Some(4),
+            accepted: Some(2197),
+        });
+        assert_eq!(ipv4.attributes.get("BGP Next hop").map(String::as_str), Some("172.20.0.1 fe80::1234"));
+        assert_eq!(protocol.channels[1].table.as_deref(), Some("master6"));
+    }
+
+    #[test]
+    fn test_parse_down_session_and_other_protocols() {
+        let protocols = parse_protocols(PROTOCOLS_ALL_DOWN);
+        assert_eq!(protocols.len(), 2);
+
+        let broken = &protocols[0];
+        assert_eq!(broken.state, "start");
+        assert_eq!(broken.info, "Active        Socket: Connection refused");
+        assert_eq!(broken.last_error.as_deref(), Some("Socket: Connection refused"));
+        let bgp = broken.bgp.as_ref().unwrap();
+        assert_eq!(bgp.state, "Active");
+        assert_eq!(bgp.hold_timer, None);
+        assert_eq!(bgp.connect_delay, Some(Timer { remaining: 3.144, interval: 5 }));
+
+        let ipv4 = &broken.channels[0];
+        assert_eq!(ipv4.routes, None);
+        assert_eq!(ipv4.limits.len(), 2);
+        assert_eq!(ipv4.limits[0].kind, "receive");
+        assert_eq!(ipv4.limits[0].action.as_deref(), Some("disable"));
+        assert_eq!(ipv4.limits[1].kind, "export");
+        assert_eq!(ipv4.limits[1].limit, 2000);
+
+        let device = &protocols[1];
+        assert_eq!(device.name, "device1");
+        assert_eq!(device.proto, "Device");
+        assert_eq!(device.bgp, None);
+        assert_eq!(device.info, "");
+        assert_eq!(device.channels[0].routes, Some(RouteCounts::default()));
+        assert_eq!(device.channels[0].preference, Some(240));
+    }
+
+    #[test]
+    fn test_parse_garbage() {
+        assert!(parse_protocols("").is_empty());
+        assert!(parse_protocols("syntax error, unexpected CF_SYM_UNDEFINED").is_empty());
+        assert!(parse_protocols("  Channel ipv4\n    State: UP").is_empty());
+    }
+}
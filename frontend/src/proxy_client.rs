@@ -1,7 +1,170 @@
-use anyhow::{anyhow, Result};
-use reqwest::{Client, header::{HeaderMap, AUTHORIZATION}};
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{Certificate, Client, ClientBuilder, Identity, Response, StatusCode, header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE}};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::Ipv6Addr;
 use std::time::Duration;
-use crate::settings::Settings;
+use url::Url;
+use crate::dns::DnsReply;
+use crate::traceroute::{self, EventParser, TracerouteEvent, TracerouteReport};
+use crate::settings::{ServerOptions, Settings};
+use crate::signature::{self, AuthScheme};
+use crate::streaming::LineChunker;
+
+/// Error returned by a proxy, with the machine-readable kind when the proxy reports one
+#[derive(Debug)]
+pub struct ProxyError {
+    pub status: StatusCode,
+    pub kind: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "HTTP error: {}", self.status)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+#[derive(Deserialize)]
+struct ProxyErrorBody {
+    error: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Build a `ProxyError` from a non-success response, accepting both the JSON
+/// error body of the bird endpoint and plain-text bodies
+async fn error_from_response(response: Response) -> anyhow::Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+
+    let error = match serde_json::from_str::<ProxyErrorBody>(&body) {
+        Ok(parsed) => ProxyError {
+            status,
+            kind: Some(parsed.error),
+            message: parsed.message,
+        },
+        Err(_) => ProxyError {
+            status,
+            kind: None,
+            message: body.trim().to_string(),
+        },
+    };
+    error.into()
+}
+
+/// Machine-readable error kind of a failed proxy request, if any
+pub fn error_kind(error: &anyhow::Error) -> Option<&str> {
+    error.downcast_ref::<ProxyError>().and_then(|e| e.kind.as_deref())
+}
+
+/// Base URL and HTTP client of one server's proxy
+#[derive(Debug, Clone)]
+pub struct ProxyEndpoint {
+    base_url: Url,
+    client: Client,
+}
+
+impl ProxyEndpoint {
+    /// Build the proxy endpoint of every server. Servers without their own CA
+    /// bundle or Unix socket share one client.
+    pub fn for_servers(
+        servers: &HashMap<String, ServerOptions>,
+        proxy_port: u16,
+        identity: Option<Identity>,
+    ) -> Result<HashMap<String, ProxyEndpoint>> {
+        let client_builder = || match &identity {
+            Some(identity) => Client::builder().identity(identity.clone()),
+            None => Client::builder(),
+        };
+        let shared = client_builder().build()?;
+        servers
+            .iter()
+            .map(|(server, options)| {
+                let endpoint = Self::new(server, options, proxy_port, &shared, client_builder())
+                    .with_context(|| format!("Invalid proxy settings for server '{}'", server))?;
+                Ok((server.clone(), endpoint))
+            })
+            .collect()
+    }
+
+    fn new(server: &str, options: &ServerOptions, proxy_port: u16, shared: &Client, mut builder: ClientBuilder) -> Result<Self> {
+        let base = match (&options.url, &options.socket) {
+            (Some(url), _) => url.clone(),
+            // Over a Unix socket only the path of the URL matters
+            (None, Some(_)) => "http://localhost/".to_string(),
+            (None, None) if server.parse::<Ipv6Addr>().is_ok() => format!("http://[{}]:{}/", server, proxy_port),
+            (None, None) => format!("http://{}:{}/", server, proxy_port),
+        };
+
+        let mut base_url = Url::parse(&base).with_context(|| format!("Invalid proxy URL '{}'", base))?;
+        if !matches!(base_url.scheme(), "http" | "https") {
+            bail!("Unsupported proxy URL scheme '{}'", base_url.scheme());
+        }
+        // Keep the path prefix when joining endpoint names
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+
+        if options.ca_file.is_none() && options.socket.is_none() {
+            return Ok(ProxyEndpoint { base_url, client: shared.clone() });
+        }
+
+        if let Some(ca_file) = &options.ca_file {
+            if base_url.scheme() != "https" {
+                bail!("ca_file needs an https proxy URL");
+            }
+            let pem = std::fs::read(ca_file).with_context(|| format!("Failed to read CA bundle {}", ca_file))?;
+            let certs = Certificate::from_pem_bundle(&pem).with_context(|| format!("Invalid CA bundle {}", ca_file))?;
+            if certs.is_empty() {
+                bail!("No certificates found in CA bundle {}", ca_file);
+            }
+            builder = builder.tls_certs_only(certs);
+        }
+        if let Some(socket) = &options.socket {
+            builder = unix_socket(builder, socket)?;
+        }
+
+        Ok(ProxyEndpoint { base_url, client: builder.build()? })
+    }
+
+    fn url(&self, endpoint: &str) -> Result<Url> {
+        Ok(self.base_url.join(endpoint)?)
+    }
+}
+
+#[cfg(unix)]
+fn unix_socket(builder: ClientBuilder, socket: &str) -> Result<ClientBuilder> {
+    Ok(builder.unix_socket(socket))
+}
+
+#[cfg(not(unix))]
+fn unix_socket(_builder: ClientBuilder, _socket: &str) -> Result<ClientBuilder> {
+    bail!("Unix socket proxies are not supported on this platform")
+}
+
+/// Client certificate for proxies requiring mTLS, from PEM certificate and key files
+pub fn client_identity(cert: Option<&str>, key: Option<&str>) -> Result<Option<Identity>> {
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => bail!("proxy_client_cert and proxy_client_key must be set together"),
+    };
+
+    let mut pem = std::fs::read(cert).with_context(|| format!("Failed to read {}", cert))?;
+    pem.push(b'\n');
+    pem.extend(std::fs::read(key).with_context(|| format!("Failed to read {}", key))?);
+    let identity = Identity::from_pem(&pem).with_context(|| format!("Invalid client certificate {} or key {}", cert, key))?;
+    Ok(Some(identity))
+}
 
 /// Validate that all requested servers are in the configured server list
 pub fn validate_servers(servers: &[String]) -> Result<()> {
@@ -16,62 +179,230 @@ pub fn validate_servers(servers: &[String]) -> Result<()> {
     Ok(())
 }
 
-pub async fn bird_query(server: &str, command: &str) -> Result<String> {
+/// Send a query to a proxy endpoint, returning the response once its status is known
+async fn proxy_request(server: &str, endpoint: &str, query: &str) -> Result<Response> {
+    proxy_request_with(server, endpoint, &[("q", query)]).await
+}
+
+/// Send a request with query parameters to a proxy endpoint
+async fn proxy_request_with(server: &str, endpoint: &str, params: &[(&str, &str)]) -> Result<Response> {
     let settings = Settings::global();
-    let client = Client::new();
+    let proxy = settings.proxies.get(server).ok_or_else(|| anyhow!("request failed: invalid server"))?;
+    let options = settings.server_options.get(server);
     
-    let url = format!("http://{}:{}/bird", server, settings.proxy_port);
-    
-    let mut request = client
-        .get(&url)
-        .query(&[("q", command)])
+    let mut url = proxy.url(endpoint)?;
+    url.query_pairs_mut().extend_pairs(params);
+    // Signatures cover the path the proxy routes on, even behind a path prefix
+    let signed_path = format!("/{}", endpoint);
+    let signed_query = url.query().unwrap_or("").to_string();
+    let signed_host = url.host_str().unwrap_or("").to_string();
+
+    let mut request = proxy.client
+        .get(url)
         .timeout(Duration::from_secs(settings.timeout));
 
-    // Add authorization header if auth is enabled
-    if settings.auth_enabled {
-        if let Some(token) = &settings.auth_token {
-            let mut headers = HeaderMap::new();
-            let header_value = format!("Bearer {}", token).parse().map_err(|e| anyhow!("Invalid auth token: {}", e))?;
-            headers.insert(AUTHORIZATION, header_value);
-            request = request.headers(headers);
-        }
+    // Authenticate with the server's own token, or the shared one if auth is enabled
+    let token = match options.and_then(|options| options.token.as_ref()) {
+        Some(token) => Some(token),
+        None if settings.auth_enabled => settings.auth_token.as_ref(),
+        None => None,
+    };
+    if let Some(token) = token {
+        let headers = match settings.auth_scheme {
+            AuthScheme::Bearer => {
+                let mut headers = HeaderMap::new();
+                let header_value = format!("Bearer {}", token).parse().map_err(|e| anyhow!("Invalid auth token: {}", e))?;
+                headers.insert(AUTHORIZATION, header_value);
+                headers
+            }
+            AuthScheme::Hmac => signature::signed_headers(token, "GET", &signed_host, &signed_path, &signed_query),
+        };
+        request = request.headers(headers);
     }
     
     let response = request.send().await?;
     
     if response.status().is_success() {
-        Ok(response.text().await?)
+        Ok(response)
     } else {
-        Err(anyhow!("HTTP error: {}", response.status()))
+        Err(error_from_response(response).await)
+    }
+}
+
+/// Streaming reply from a proxy, yielding text split on line boundaries
+pub struct ReplyStream {
+    response: Response,
+    chunker: LineChunker,
+    finished: bool,
+}
+
+impl ReplyStream {
+    /// Next piece of text, or `None` once the reply is complete
+    pub async fn next_text(&mut self) -> Result<Option<String>> {
+        while !self.finished {
+            match self.response.chunk().await? {
+                Some(bytes) => {
+                    if let Some(text) = self.chunker.push(&bytes) {
+                        return Ok(Some(text));
+                    }
+                }
+                None => {
+                    self.finished = true;
+                    return Ok(self.chunker.finish());
+                }
+            }
+        }
+        Ok(None)
     }
 }
 
+pub async fn bird_query(server: &str, command: &str) -> Result<String> {
+    Ok(proxy_request(server, "bird", command).await?.text().await?)
+}
+
+pub async fn bird_query_stream(server: &str, command: &str) -> Result<ReplyStream> {
+    Ok(ReplyStream {
+        response: proxy_request(server, "bird", command).await?,
+        chunker: LineChunker::default(),
+        finished: false,
+    })
+}
+
 pub async fn traceroute_query(server: &str, target: &str) -> Result<String> {
-    let settings = Settings::global();
-    let client = Client::new();
-    
-    let url = format!("http://{}:{}/traceroute", server, settings.proxy_port);
-    
-    let mut request = client
-        .get(&url)
-        .query(&[("q", target)])
-        .timeout(Duration::from_secs(settings.timeout));
+    Ok(proxy_request(server, "traceroute", target).await?.text().await?)
+}
 
-    // Add authorization header if auth is enabled
-    if settings.auth_enabled {
-        if let Some(token) = &settings.auth_token {
-            let mut headers = HeaderMap::new();
-            let header_value = format!("Bearer {}", token).parse().map_err(|e| anyhow!("Invalid auth token: {}", e))?;
-            headers.insert(AUTHORIZATION, header_value);
-            request = request.headers(headers);
+/// Traceroute with parsed hops, or only the text from proxies without JSON support
+pub async fn traceroute_report(server: &str, target: &str) -> Result<TracerouteReport> {
+    let body = proxy_request_with(server, "traceroute", &[("q", target), ("format", "json")]).await?.text().await?;
+    Ok(traceroute::parse_reply(target, &body))
+}
+
+pub async fn ping_query(server: &str, target: &str) -> Result<String> {
+    Ok(proxy_request(server, "ping", target).await?.text().await?)
+}
+
+/// Streaming traceroute from a proxy, yielding its events as they arrive
+pub struct TracerouteStream {
+    reply: ReplyStream,
+    parser: EventParser,
+    pending: VecDeque<TracerouteEvent>,
+}
+
+impl TracerouteStream {
+    /// Next event, or `None` once the traceroute has ended
+    pub async fn next_event(&mut self) -> Result<Option<TracerouteEvent>> {
+        while self.pending.is_empty() {
+            match self.reply.next_text().await? {
+                Some(text) => self.pending.extend(self.parser.push(&text)),
+                None => return Ok(self.parser.finish()),
+            }
         }
+        Ok(self.pending.pop_front())
     }
-    
-    let response = request.send().await?;
-    
-    if response.status().is_success() {
-        Ok(response.text().await?)
-    } else {
-        Err(anyhow!("HTTP error: {}", response.status()))
+}
+
+/// Stream a traceroute as server-sent events, or read the text of proxies
+/// that do not stream
+pub async fn traceroute_stream(server: &str, target: &str) -> Result<TracerouteStream> {
+    let response = proxy_request_with(server, "traceroute", &[("q", target), ("format", "stream")]).await?;
+    let streamed = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+
+    Ok(TracerouteStream {
+        reply: ReplyStream {
+            response,
+            chunker: LineChunker::default(),
+            finished: false,
+        },
+        parser: EventParser::new(target, streamed),
+        pending: VecDeque::new(),
+    })
+}
+
+/// Resolve `target` with the proxy's resolver, for one record type or all that apply
+pub async fn dns_query(server: &str, target: &str, record_type: Option<&str>) -> Result<DnsReply> {
+    let mut params = vec![("q", target)];
+    if let Some(record_type) = record_type {
+        params.push(("type", record_type));
     }
-} 
\ No newline at end of file
+    Ok(proxy_request_with(server, "dns", &params).await?.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA_BUNDLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proxy_ca.pem");
+
+    fn endpoint(server: &str, options: ServerOptions) -> Result<ProxyEndpoint> {
+        ProxyEndpoint::new(server, &options, 8000, &Client::new(), Client::builder())
+    }
+
+    fn url(server: &str, options: ServerOptions, name: &str) -> String {
+        endpoint(server, options).unwrap().url(name).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_default_endpoint() {
+        assert_eq!(url("node1.example.com", ServerOptions::default(), "bird"), "http://node1.example.com:8000/bird");
+        assert_eq!(url("192.0.2.1", ServerOptions::default(), "traceroute"), "http://192.0.2.1:8000/traceroute");
+        assert_eq!(url("2001:db8::1", ServerOptions::default(), "bird"), "http://[2001:db8::1]:8000/bird");
+    }
+
+    #[test]
+    fn test_base_url_with_path_prefix() {
+        let options = |url: &str| ServerOptions { url: Some(url.to_string()), ..Default::default() };
+
+        assert_eq!(url("node1", options("https://lg.example.com:8443/node1"), "bird"), "https://lg.example.com:8443/node1/bird");
+        assert_eq!(url("node1", options("https://lg.example.com/node1/"), "bird"), "https://lg.example.com/node1/bird");
+        assert_eq!(url("node1", options("http://10.0.0.1:9000"), "bird"), "http://10.0.0.1:9000/bird");
+
+        assert!(endpoint("node1", options("ftp://lg.example.com/")).is_err());
+        assert!(endpoint("node1", options("not a url")).is_err());
+    }
+
+    #[test]
+    fn test_unix_socket_endpoint() {
+        let socket = ServerOptions { socket: Some("/run/bird-lgproxy.sock".to_string()), ..Default::default() };
+        assert_eq!(url("node1", socket, "bird"), "http://localhost/bird");
+
+        let prefixed = ServerOptions {
+            url: Some("http://localhost/lg".to_string()),
+            socket: Some("/run/bird-lgproxy.sock".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(url("node1", prefixed, "bird"), "http://localhost/lg/bird");
+    }
+
+    #[test]
+    fn test_ca_bundle() {
+        let https = ServerOptions {
+            url: Some("https://node1.example.com:8443".to_string()),
+            ca_file: Some(CA_BUNDLE.to_string()),
+            ..Default::default()
+        };
+        assert!(endpoint("node1", https.clone()).is_ok());
+
+        let plain_http = ServerOptions { url: Some("http://node1.example.com".to_string()), ..https.clone() };
+        assert!(endpoint("node1", plain_http).is_err());
+
+        let missing = ServerOptions { ca_file: Some("/nonexistent/ca.pem".to_string()), ..https.clone() };
+        assert!(endpoint("node1", missing).is_err());
+
+        let not_pem = ServerOptions { ca_file: Some(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/show_route.txt").to_string()), ..https };
+        assert!(endpoint("node1", not_pem).is_err());
+    }
+
+    #[test]
+    fn test_client_identity_needs_cert_and_key() {
+        assert!(client_identity(None, None).unwrap().is_none());
+        assert!(client_identity(Some(CA_BUNDLE), None).is_err());
+        assert!(client_identity(None, Some(CA_BUNDLE)).is_err());
+        // A certificate without its key
+        assert!(client_identity(Some(CA_BUNDLE), Some(CA_BUNDLE)).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A single route from BIRD 2.x `show route [all]` output
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct RouteEntry {
+    pub prefix: String,
+    pub table: Option<String>,
+    /// unicast, blackhole, unreachable or prohibited
+    pub route_type: String,
+    pub protocol: String,
+    pub since: String,
+    /// Address of the neighbor the route was learned from
+    pub from: Option<String>,
+    pub primary: bool,
+    pub preference: Option<u32>,
+    pub metric: Option<u32>,
+    /// Trailing bracketed info, such as `AS4242423914i` or an OSPF router ID
+    pub info: Option<String>,
+    pub nexthops: Vec<Nexthop>,
+    /// The `Type:` attribute, such as `BGP univ`
+    pub source: Option<String>,
+    pub as_path: Vec<u32>,
+    pub bgp_next_hop: Option<String>,
+    pub communities: Vec<Community>,
+    pub large_communities: Vec<LargeCommunity>,
+    pub ext_communities: Vec<String>,
+    pub local_pref: Option<u32>,
+    pub med: Option<u32>,
+    pub origin: Option<String>,
+    /// Every other `Key: value` attribute line
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct Nexthop {
+    pub gateway: Option<String>,
+    pub interface: Option<String>,
+    pub weight: Option<u32>,
+}
+
+impl fmt::Display for Nexthop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.gateway, &self.interface) {
+            (Some(gateway), Some(interface)) => write!(f, "via {} on {}", gateway, interface),
+            (Some(gateway), None) => write!(f, "via {}", gateway),
+            (None, Some(interface)) => write!(f, "dev {}", interface),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Standard BGP community, serialized as `[asn, value]`
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct Community(pub u32, pub u32);
+
+/// Large BGP community, serialized as `[asn, data1, data2]`
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct LargeCommunity(pub u32, pub u32, pub u32);
+
+fn route_header_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"^(?P<prefix>\S+)?\s+(?P<type>unicast|blackhole|unreachable|prohibited)\s+\[(?P<proto>\S+)\s+(?P<since>[^\]]*?)(?:\s+from\s+(?P<from>[^\]\s]+))?\]\s*(?P<primary>\*)?\s*(?:[A-Z][A-Z0-9]*\s+)?\((?P<pref>\d+)(?:/(?P<metric>\d+|\?))?\)(?:\s+\[(?P<info>[^\]]*)\])?",
+        )
+        .expect("Invalid regex pattern")
+    })
+}
+
+fn via_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^via\s+(?P<gateway>\S+)(?:\s+on\s+(?P<interface>\S+))?(?:.*?\bweight\s+(?P<weight>\d+))?")
+            .expect("Invalid regex pattern")
+    })
+}
+
+fn parse_numbers(value: &str) -> Vec<u32> {
+    value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect()
+}
+
+/// Split `(a, b) (c, d)` style lists into the contents of each parenthesis
+fn parenthesized(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split('(')
+        .filter_map(|part| part.split_once(')').map(|(inner, _)| inner.trim()))
+}
+
+fn apply_attribute(route: &mut RouteEntry, key: &str, value: &str) {
+    match key {
+        "Type" => route.source = Some(value.to_string()),
+        "BGP.as_path" | "bgp_path" => route.as_path = parse_numbers(value),
+        "BGP.next_hop" => route.bgp_next_hop = Some(value.to_string()),
+        "BGP.local_pref" => route.local_pref = value.parse().ok(),
+        "BGP.med" => route.med = value.parse().ok(),
+        "BGP.origin" => route.origin = Some(value.to_string()),
+        "BGP.community" => {
+            route.communities = parenthesized(value)
+                .filter_map(|c| match parse_numbers(c)[..] {
+                    [asn, data] => Some(Community(asn, data)),
+                    _ => None,
+                })
+                .collect();
+        }
+        "BGP.large_community" => {
+            route.large_communities = parenthesized(value)
+                .filter_map(|c| match parse_numbers(c)[..] {
+                    [asn, data1, data2] => Some(LargeCommunity(asn, data1, data2)),
+                    _ => None,
+                })
+                .collect();
+        }
+        "BGP.ext_community" => {
+            route.ext_communities = parenthesized(value).map(str::to_string).collect();
+        }
+        _ => {
+            route.attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Parse `show route [all]` output into route entries, in output order
+pub fn parse_routes(data: &str) -> Vec<RouteEntry> {
+    let mut routes: Vec<RouteEntry> = Vec::new();
+    let mut table: Option<String> = None;
+    let mut last_prefix = String::new();
+
+    for line in data.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("Table ").and_then(|rest| rest.trim().strip_suffix(':')) {
+            table = Some(name.to_string());
+            continue;
+        }
+
+        if let Some(captures) = route_header_re().captures(line) {
+            if let Some(prefix) = captures.name("prefix") {
+                last_prefix = prefix.as_str().to_string();
+            }
+            routes.push(RouteEntry {
+                prefix: last_prefix.clone(),
+                table: table.clone(),
+                route_type: captures["type"].to_string(),
+                protocol: captures["proto"].to_string(),
+                since: captures["since"].trim().to_string(),
+                from: captures.name("from").map(|m| m.as_str().to_string()),
+                primary: captures.name("primary").is_some(),
+                preference: captures.name("pref").and_then(|m| m.as_str().parse().ok()),
+                metric: captures.name("metric").and_then(|m| m.as_str().parse().ok()),
+                info: captures.name("info").map(|m| m.as_str().to_string()),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        // Everything else belongs to the most recent route
+        let Some(route) = routes.last_mut() else {
+            continue;
+        };
+        let detail = line.trim();
+
+        if let Some(captures) = via_re().captures(detail) {
+            route.nexthops.push(Nexthop {
+                gateway: Some(captures["gateway"].to_string()),
+                interface: captures.name("interface").map(|m| m.as_str().to_string()),
+                weight: captures.name("weight").and_then(|m| m.as_str().parse().ok()),
+            });
+        } else if let Some(interface) = detail.strip_prefix("dev ") {
+            route.nexthops.push(Nexthop {
+                gateway: None,
+                interface: interface.split_whitespace().next().map(str::to_string),
+                weight: None,
+            });
+        } else if let Some((key, value)) = detail.split_once(':') {
+            apply_attribute(route, key.trim(), value.trim());
+        }
+    }
+
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE_ALL_BGP: &str = include_str!("../tests/fixtures/show_route_all_bgp.txt");
+    const ROUTE_ALL_MULTIPATH: &str = include_str!("../tests/fixtures/show_route_all_multipath.txt");
+    const ROUTE: &str = include_str!("../tests/fixtures/show_route.txt");
+
+    #[test]
+    fn test_parse_bgp_routes() {
+        let routes = parse_routes(ROUTE_ALL_BGP);
+        assert_eq!(routes.len(), 3);
+
+        let primary = &routes[0];
+        assert_eq!(primary.prefix, "172.20.0.53/32");
+        assert_eq!(primary.table.as_deref(), Some("master4"));
+        assert_eq!(primary.route_type, "unicast");
+        assert_eq!(primary.protocol, "ibgp_sjc2");
+        assert_eq!(primary.since, "2023-04-29");
+        assert_eq!(primary.from.as_deref(), Some("fd86:bad:11b7:22::1"));
+        assert!(primary.primary);
+        assert_eq!(primary.preference, Some(100));
+        assert_eq!(primary.metric, Some(38));
+        assert_eq!(primary.info.as_deref(), Some("AS4242423914i"));
+        assert_eq!(primary.nexthops, vec![Nexthop {
+            gateway: Some("169.254.108.122".to_string()),
+            interface: Some("igp-sjc2".to_string()),
+            weight: None,
+        }]);
+        assert_eq!(primary.source.as_deref(), Some("BGP univ"));
+        assert_eq!(primary.origin.as_deref(), Some("IGP"));
+        assert_eq!(primary.as_path, vec![4242423914]);
+        assert_eq!(primary.bgp_next_hop.as_deref(), Some("172.20.229.122"));
+        assert_eq!(primary.med, Some(50));
+        assert_eq!(primary.local_pref, Some(100));
+        assert_eq!(primary.communities, vec![Community(64511, 1), Community(64511, 24), Community(64511, 34)]);
+        assert_eq!(primary.large_communities[0], LargeCommunity(4242421080, 101, 44));
+        assert_eq!(primary.large_communities.len(), 3);
+
+        // Second route for the same prefix has no prefix column
+        let backup = &routes[1];
+        assert_eq!(backup.prefix, "172.20.0.53/32");
+        assert_eq!(backup.protocol, "miaotony_2688");
+        assert!(!backup.primary);
+        assert_eq!(backup.metric, None);
+        assert_eq!(backup.as_path, vec![4242422688, 4242423914]);
+        assert_eq!(backup.nexthops[0].to_string(), "via 172.23.6.6 on dn42las-miaoton");
+        assert_eq!(backup.as_path.last(), Some(&4242423914));
+    }
+
+    #[test]
+    fn test_parse_ipv6_route_attributes() {
+        let routes = parse_routes(ROUTE_ALL_BGP);
+        let route = &routes[2];
+
+        assert_eq!(route.prefix, "fd42:4242:2601::/48");
+        assert_eq!(route.table.as_deref(), Some("master6"));
+        assert_eq!(route.since, "2024-02-11 12:03:45");
+        assert_eq!(route.from.as_deref(), Some("fe80::ade0%dn42-kioubit"));
+        assert_eq!(route.origin.as_deref(), Some("Incomplete"));
+        // AS sets are flattened into the path
+        assert_eq!(route.as_path, vec![4242423914, 4242422601, 4242422602]);
+        assert_eq!(route.bgp_next_hop.as_deref(), Some("fd42:d42:d42:80::1 fe80::ade0"));
+        assert_eq!(route.ext_communities, vec!["rt, 64511, 1".to_string(), "ro, 64511, 2".to_string()]);
+        assert_eq!(route.attributes.get("BGP.atomic_aggr").map(String::as_str), Some(""));
+        assert_eq!(
+            route.attributes.get("BGP.aggregator").map(String::as_str),
+            Some("172.20.0.1 AS4242422601")
+        );
+    }
+
+    #[test]
+    fn test_parse_multipath_and_special_routes() {
+        let routes = parse_routes(ROUTE_ALL_MULTIPATH);
+        assert_eq!(routes.len(), 3);
+
+        let ospf = &routes[0];
+        assert_eq!(ospf.protocol, "ospf1");
+        assert_eq!(ospf.preference, Some(150));
+        assert_eq!(ospf.metric, Some(20));
+        assert_eq!(ospf.info.as_deref(), Some("10.0.0.1"));
+        assert_eq!(ospf.nexthops.len(), 2);
+        assert_eq!(ospf.nexthops[1].weight, Some(2));
+        assert_eq!(ospf.attributes.get("OSPF.metric1").map(String::as_str), Some("20"));
+        assert!(ospf.as_path.is_empty());
+
+        let direct = &routes[1];
+        assert_eq!(direct.nexthops[0].to_string(), "dev eth0");
+
+        let blackhole = &routes[2];
+        assert_eq!(blackhole.route_type, "blackhole");
+        assert_eq!(blackhole.since, "2024-01-01 10:00:00");
+        assert!(blackhole.nexthops.is_empty());
+    }
+
+    #[test]
+    fn test_parse_brief_output() {
+        let routes = parse_routes(ROUTE);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[1].prefix, "172.20.0.53/32");
+        assert_eq!(routes[1].nexthops[0].gateway.as_deref(), Some("172.23.6.6"));
+        assert_eq!(routes[2].prefix, "10.0.0.0/8");
+        assert_eq!(routes[2].route_type, "unreachable");
+        assert!(routes[2].source.is_none());
+    }
+
+    #[test]
+    fn test_parse_garbage() {
+        assert!(parse_routes("").is_empty());
+        assert!(parse_routes(r#"<script>alert("evil!")</script>"#).is_empty());
+        assert!(parse_routes("Network not found").is_empty());
+    }
+}
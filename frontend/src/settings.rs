@@ -1,66 +1,178 @@
 use crate::Args;
-use anyhow::Result;
-use std::sync::OnceLock;
-use tracing::info;
+use crate::net_mode::NetSpecificMode;
+use crate::proxy_client::{self, ProxyEndpoint};
+use crate::signature::AuthScheme;
+use crate::summary_parser::ProtocolFilter;
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+/// One entry of the server list: either the `Display<host>` shorthand used by
+/// `--servers`, or a structured entry from the configuration file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ServerEntry {
+    Spec(String),
+    Detailed(ServerConfig),
+}
+
+impl FromStr for ServerEntry {
+    type Err = Infallible;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        Ok(ServerEntry::Spec(spec.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Server hostname, completed with `domain` like plain server names
+    pub name: String,
+    /// Name shown in the navigation bar and URLs
+    #[serde(default)]
+    pub display: Option<String>,
+    /// Base URL of the proxy, instead of `http://<name>:<proxy_port>`
+    #[serde(default)]
+    pub url: Option<String>,
+    /// PEM bundle of CA certificates trusted for an `https` URL, instead of the system roots
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// Unix socket of a co-located proxy
+    #[serde(default)]
+    pub socket: Option<String>,
+    /// Token for this proxy, instead of `auth_token`
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Groups this server can be selected by, like a display name
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Per-server proxy options from structured server entries
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    pub url: Option<String>,
+    pub ca_file: Option<String>,
+    pub socket: Option<String>,
+    pub token: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl ServerEntry {
+    /// Server hostname, display name and proxy options of this entry
+    fn split(&self) -> (String, String, ServerOptions) {
+        match self {
+            ServerEntry::Spec(spec) => {
+                if let Some(angle_pos) = spec.find('<') {
+                    // Display name format: "Display<actual>"
+                    let display_name = spec[..angle_pos].to_string();
+                    let actual = spec[angle_pos + 1..].trim_end_matches('>').to_string();
+                    info!("Found <> format: display_name='{}', actual='{}'", display_name, actual);
+                    (actual, display_name, ServerOptions::default())
+                } else {
+                    // Plain server name - store the original as display name
+                    info!("Plain server name: '{}'", spec);
+                    (spec.clone(), spec.clone(), ServerOptions::default())
+                }
+            }
+            ServerEntry::Detailed(server) => {
+                let display_name = server.display.clone().unwrap_or_else(|| server.name.clone());
+                let options = ServerOptions {
+                    url: server.url.clone(),
+                    ca_file: server.ca_file.clone(),
+                    socket: server.socket.clone(),
+                    token: server.token.clone(),
+                    tags: server.tags.clone(),
+                };
+                (server.name.clone(), display_name, options)
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub servers: Vec<String>,
     pub servers_display: Vec<String>,
+    /// Options of servers configured with structured entries, by hostname
+    pub server_options: HashMap<String, ServerOptions>,
+    /// Proxy of each server, by hostname
+    pub proxies: HashMap<String, ProxyEndpoint>,
     #[allow(dead_code)]
     pub domain: String,
+    #[allow(dead_code)]
     pub proxy_port: u16,
     pub whois_server: String,
     pub listen: String,
-    #[allow(dead_code)]
     pub dns_interface: String,
-    #[allow(dead_code)]
-    pub net_specific_mode: String,
+    pub asn_cache_ttl: u64,
+    pub asn_max_concurrent: usize,
+    pub net_specific_mode: NetSpecificMode,
     pub title_brand: String,
     pub navbar_brand: String,
     pub navbar_brand_url: String,
     pub navbar_all_server: String,
     #[allow(dead_code)]
     pub navbar_all_url: String,
-    #[allow(dead_code)]
     pub bgpmap_info: String,
     #[allow(dead_code)]
     pub telegram_bot_name: String,
-    #[allow(dead_code)]
-    pub protocol_filter: Vec<String>,
-    #[allow(dead_code)]
-    pub name_filter: String,
+    pub protocol_filter: ProtocolFilter,
     pub timeout: u64,
+    pub max_concurrent: usize,
     pub auth_enabled: bool,
     pub auth_token: Option<String>,
+    pub auth_scheme: AuthScheme,
+    pub metrics_interval: u64,
 }
 
-static SETTINGS: OnceLock<Settings> = OnceLock::new();
+static SETTINGS: OnceLock<ArcSwap<Settings>> = OnceLock::new();
 
 impl Settings {
     pub async fn init(args: Args) -> Result<()> {
+        let settings = Self::from_args(args)?;
+        info!("Settings initialized: {:?}", settings);
+
+        SETTINGS.set(ArcSwap::from_pointee(settings)).map_err(|_| anyhow::anyhow!("Settings already initialized"))?;
+        Ok(())
+    }
+
+    /// Replace the running settings. Invalid `args` leave them untouched.
+    pub fn reload(args: Args) -> Result<()> {
+        let settings = Self::from_args(args)?;
+        let handle = SETTINGS.get().context("Settings not initialized")?;
+
+        let restart_required = handle.load().restart_required(&settings);
+        if !restart_required.is_empty() {
+            warn!("Changes to {} only apply after a restart", restart_required.join(", "));
+        }
+
+        info!("Settings reloaded: {:?}", settings);
+        handle.store(Arc::new(settings));
+        Ok(())
+    }
+
+    fn from_args(args: Args) -> Result<Settings> {
         // Parse servers with display names
         let mut servers = Vec::new();
         let mut servers_display = Vec::new();
+        let mut options = Vec::new();
 
         info!("Initializing settings with args.servers: {:?}", args.servers);
         info!("Domain: '{}'", args.domain);
 
-        for server_spec in &args.servers {
-            info!("Processing server_spec: '{}'", server_spec);
-            if let Some(angle_pos) = server_spec.find('<') {
-                // Display name format: "Display<actual>"
-                let display_name = server_spec[..angle_pos].to_string();
-                let actual = server_spec[angle_pos + 1..server_spec.len() - 1].to_string();
-                info!("Found <> format: display_name='{}', actual='{}'", display_name, actual);
-                servers_display.push(display_name);
-                servers.push(actual);
-            } else {
-                // Plain server name - store the original as display name
-                info!("Plain server name: '{}'", server_spec);
-                servers_display.push(server_spec.clone());
-                servers.push(server_spec.clone());
-            }
+        for entry in &args.servers {
+            info!("Processing server entry: {:?}", entry);
+            let (server, display_name, server_options) = entry.split();
+            servers.push(server);
+            servers_display.push(display_name);
+            options.push(server_options);
         }
 
         info!("Before domain processing - servers: {:?}", servers);
@@ -70,7 +182,7 @@ impl Settings {
         if !args.domain.is_empty() {
             for i in 0..servers.len() {
                 let original = servers[i].clone();
-                if !servers[i].contains('.') && !servers[i].parse::<std::net::IpAddr>().is_ok() {
+                if !servers[i].contains('.') && servers[i].parse::<std::net::IpAddr>().is_err() {
                     servers[i] = format!("{}.{}", servers[i], args.domain);
                     info!("Added domain to servers[{}]: '{}' -> '{}'", i, original, servers[i]);
                 } else {
@@ -88,15 +200,23 @@ impl Settings {
         info!("After domain processing - servers: {:?}", servers);
         info!("After domain processing - servers_display: {:?}", servers_display);
 
+        let server_options: HashMap<String, ServerOptions> = servers.iter().cloned().zip(options).collect();
+        let identity = proxy_client::client_identity(args.proxy_client_cert.as_deref(), args.proxy_client_key.as_deref())?;
+        let proxies = ProxyEndpoint::for_servers(&server_options, args.proxy_port, identity)?;
+
         let settings = Settings {
             servers,
             servers_display,
+            server_options,
+            proxies,
             domain: args.domain,
             proxy_port: args.proxy_port,
             whois_server: args.whois,
             listen: args.listen,
             dns_interface: args.dns_interface,
-            net_specific_mode: args.net_specific_mode,
+            asn_cache_ttl: args.asn_cache_ttl,
+            asn_max_concurrent: args.asn_max_concurrent,
+            net_specific_mode: NetSpecificMode::parse(&args.net_specific_mode)?,
             title_brand: args.title_brand,
             navbar_brand: args.navbar_brand,
             navbar_brand_url: args.navbar_brand_url,
@@ -104,21 +224,38 @@ impl Settings {
             navbar_all_url: args.navbar_all_url,
             bgpmap_info: args.bgpmap_info,
             telegram_bot_name: args.telegram_bot_name,
-            protocol_filter: args.protocol_filter.unwrap_or_default(),
-            name_filter: args.name_filter,
+            protocol_filter: ProtocolFilter::new(&args.protocol_filter.unwrap_or_default(), &args.name_filter)?,
             timeout: args.timeout,
+            max_concurrent: args.max_concurrent,
             auth_enabled: args.auth_enabled,
             auth_token: args.auth_token,
+            auth_scheme: AuthScheme::parse(&args.auth_scheme)?,
+            metrics_interval: args.metrics_interval,
         };
 
-        info!("Settings initialized: {:?}", settings);
+        Ok(settings)
+    }
 
-        SETTINGS.set(settings).map_err(|_| anyhow::anyhow!("Settings already initialized"))?;
-        Ok(())
+    /// Settings only read at startup that differ in `new`
+    fn restart_required(&self, new: &Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.listen != new.listen {
+            changed.push("listen");
+        }
+        if self.dns_interface != new.dns_interface {
+            changed.push("dns_interface");
+        }
+        if self.asn_cache_ttl != new.asn_cache_ttl || self.asn_max_concurrent != new.asn_max_concurrent {
+            changed.push("asn_cache_ttl/asn_max_concurrent");
+        }
+        if self.bgpmap_info != new.bgpmap_info {
+            changed.push("bgpmap_info");
+        }
+        changed
     }
 
-    pub fn global() -> &'static Settings {
-        SETTINGS.get().expect("Settings not initialized")
+    pub fn global() -> Arc<Settings> {
+        SETTINGS.get().expect("Settings not initialized").load_full()
     }
 
     pub fn get_server_display_name(&self, server: &str) -> String {
@@ -149,21 +286,34 @@ impl Settings {
     }
 
     pub fn resolve_servers_from_display_names(&self, display_names: &str) -> Vec<String> {
-        display_names
-            .split('+')
-            .filter_map(|display_name| {
-                // First try to find by display name
-                if let Some(server) = self.get_server_from_display_name(display_name) {
-                    Some(server)
-                } else {
-                    // If not found by display name, check if it's already a server name
-                    if self.servers.contains(&display_name.to_string()) {
-                        Some(display_name.to_string())
-                    } else {
-                        None
-                    }
+        let mut resolved = Vec::new();
+
+        for display_name in display_names.split('+') {
+            // First try to find by display name, then check if it's already a server name
+            let matched = if let Some(server) = self.get_server_from_display_name(display_name) {
+                vec![server]
+            } else if self.servers.contains(&display_name.to_string()) {
+                vec![display_name.to_string()]
+            } else {
+                // Finally, select every server carrying the tag
+                self.servers
+                    .iter()
+                    .filter(|server| {
+                        self.server_options
+                            .get(*server)
+                            .is_some_and(|options| options.tags.iter().any(|tag| tag == display_name))
+                    })
+                    .cloned()
+                    .collect()
+            };
+
+            for server in matched {
+                if !resolved.contains(&server) {
+                    resolved.push(server);
                 }
-            })
-            .collect()
+            }
+        }
+
+        resolved
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,59 @@
+use bird_lg_common::signature::{SignedRequest, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use hmac::Mac;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use bird_lg_common::signature::AuthScheme;
+
+fn sign(secret: &str, request: &SignedRequest) -> String {
+    hex::encode(request.mac(secret).finalize().into_bytes())
+}
+
+/// Headers signing a request to `host` for `path` and its raw `query` with `secret`
+pub fn signed_headers(secret: &str, method: &str, host: &str, path: &str, query: &str) -> HeaderMap {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+        .to_string();
+    let nonce = hex::encode(rand::random::<[u8; 16]>());
+    let request = SignedRequest { method, host, path, query, timestamp: &timestamp, nonce: &nonce };
+    let signature = sign(secret, &request);
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in [(TIMESTAMP_HEADER, timestamp), (NONCE_HEADER, nonce), (SIGNATURE_HEADER, signature)] {
+        // Digits and hex only, always valid header values
+        headers.insert(name, HeaderValue::from_str(&value).expect("valid header value"));
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_matches_proxy() {
+        // Same vector as the proxy's signature tests
+        let request = SignedRequest {
+            method: "GET",
+            host: "lg1.example:8000",
+            path: "/bird",
+            query: "q=show+protocols",
+            timestamp: "1700000000",
+            nonce: "0123456789abcdef0123456789abcdef",
+        };
+        assert_eq!(sign("node-secret", &request), "976eba941d942d54e83bcc00ac0f6ef58d92a7554f3cb913bb9f96cc7e029c9a");
+    }
+
+    #[test]
+    fn test_signed_headers_use_fresh_nonces() {
+        let first = signed_headers("node-secret", "GET", "lg1.example", "/bird", "q=show+protocols");
+        let second = signed_headers("node-secret", "GET", "lg1.example", "/bird", "q=show+protocols");
+
+        assert_eq!(first[NONCE_HEADER].len(), 32);
+        assert_ne!(first[NONCE_HEADER], second[NONCE_HEADER]);
+        assert_ne!(first[SIGNATURE_HEADER], second[SIGNATURE_HEADER]);
+        assert!(first[TIMESTAMP_HEADER].to_str().unwrap().parse::<u64>().is_ok());
+    }
+}
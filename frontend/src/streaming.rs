@@ -0,0 +1,54 @@
+use axum::body::Body;
+use futures_util::stream;
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+
+/// Marker rendered in place of content when a template is split for streaming
+pub const CONTENT_MARKER: &str = "<!--bird-lg-stream-content-->";
+
+/// Sending half of a streamed response body
+pub type BodySender = mpsc::Sender<String>;
+
+/// Create a response body fed through the returned sender. Sends fail once
+/// the client has gone away, which tells the producer to stop.
+pub fn channel_body() -> (BodySender, Body) {
+    let (tx, rx) = mpsc::channel::<String>(16);
+    let body_stream = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (Ok::<_, Infallible>(chunk), rx))
+    });
+    (tx, Body::from_stream(body_stream))
+}
+
+/// Split rendered HTML around `CONTENT_MARKER`
+pub fn split_at_marker(rendered: &str) -> (String, String) {
+    match rendered.split_once(CONTENT_MARKER) {
+        Some((head, tail)) => (head.to_string(), tail.to_string()),
+        None => (rendered.to_string(), String::new()),
+    }
+}
+
+/// Re-chunks a byte stream on line boundaries, so every chunk can be decoded
+/// and escaped on its own without splitting a UTF-8 sequence
+#[derive(Default)]
+pub struct LineChunker {
+    pending: Vec<u8>,
+}
+
+impl LineChunker {
+    /// Add bytes, returning all complete lines received so far
+    pub fn push(&mut self, bytes: &[u8]) -> Option<String> {
+        self.pending.extend_from_slice(bytes);
+        let end = self.pending.iter().rposition(|&b| b == b'\n')? + 1;
+        let complete: Vec<u8> = self.pending.drain(..end).collect();
+        Some(String::from_utf8_lossy(&complete).to_string())
+    }
+
+    /// Flush a trailing partial line at end of stream
+    pub fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.pending);
+        Some(String::from_utf8_lossy(&rest).to_string())
+    }
+}
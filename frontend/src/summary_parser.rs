@@ -3,6 +3,56 @@ use regex::Regex;
 use std::collections::HashMap;
 use crate::templates::{SummaryContext, SummaryRowData};
 
+/// Hides protocols from summaries by type (`protocol_filter`) and name (`name_filter`)
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolFilter {
+    protocols: Vec<String>,
+    name: Option<Regex>,
+}
+
+impl ProtocolFilter {
+    pub fn new(protocols: &[String], name_filter: &str) -> Result<Self> {
+        let name = if name_filter.is_empty() {
+            None
+        } else {
+            Some(Regex::new(name_filter).map_err(|e| anyhow!("Invalid name filter: {}", e))?)
+        };
+
+        Ok(Self {
+            protocols: protocols
+                .iter()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            name,
+        })
+    }
+
+    /// Whether a protocol should be listed; protocol types match case-insensitively
+    pub fn is_shown(&self, name: &str, proto: &str) -> bool {
+        if !self.protocols.is_empty() && !self.protocols.iter().any(|p| p.eq_ignore_ascii_case(proto)) {
+            return false;
+        }
+        !self.name.as_ref().is_some_and(|re| re.is_match(name))
+    }
+
+    /// Drop hidden protocols from raw `show protocols` output, keeping the header
+    pub fn filter_output(&self, data: &str) -> String {
+        data.lines()
+            .enumerate()
+            .filter(|(i, line)| {
+                let mut columns = line.split_whitespace();
+                match (columns.next(), columns.next()) {
+                    (Some(name), Some(proto)) if *i > 0 => self.is_shown(name, proto),
+                    _ => true,
+                }
+            })
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
 // Protocol state to CSS class mapping
 fn get_state_map() -> HashMap<&'static str, &'static str> {
     let mut map = HashMap::new();
@@ -13,7 +63,7 @@ fn get_state_map() -> HashMap<&'static str, &'static str> {
     map
 }
 
-pub fn parse_summary(data: &str, server_name: String) -> Result<SummaryContext> {
+pub fn parse_summary(data: &str, server_name: String, filter: &ProtocolFilter) -> Result<SummaryContext> {
     let lines: Vec<&str> = data.trim().split('\n').collect();
     
     if lines.len() <= 1 {
@@ -48,6 +98,10 @@ pub fn parse_summary(data: &str, server_name: String) -> Result<SummaryContext>
             let since = captures.get(5).map(|m| m.as_str()).unwrap_or("").trim().to_string();
             let info = captures.get(6).map(|m| m.as_str()).unwrap_or("").trim().to_string();
 
+            if !filter.is_shown(&name, &proto) {
+                continue;
+            }
+
             let mapped_state = if info.contains("Passive") {
                 "info".to_string()
             } else {
@@ -74,4 +128,63 @@ pub fn parse_summary(data: &str, server_name: String) -> Result<SummaryContext>
         headers,
         rows,
     })
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUMMARY: &str = "Name       Proto      Table      State  Since         Info
+device1    Device     ---        up     2024-01-01
+kernel1    Kernel     master4    up     2024-01-01
+static1    Static     master4    up     2024-01-01
+ibgp_sjc2  BGP        ---        up     2024-01-01    Established
+dn42_kioubit BGP      ---        up     2024-01-01    Established
+dn42_broken BGP       ---        start  2024-01-01    Active        Socket: Connection refused
+ospf_igp   OSPF       master6    up     2024-01-01    Running";
+
+    fn names(filter: &ProtocolFilter) -> Vec<String> {
+        parse_summary(SUMMARY, "node".to_string(), filter)
+            .unwrap()
+            .rows
+            .into_iter()
+            .map(|row| row.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_no_filter_shows_everything() {
+        assert_eq!(names(&ProtocolFilter::default()).len(), 7);
+    }
+
+    #[test]
+    fn test_protocol_filter() {
+        let filter = ProtocolFilter::new(&["bgp".to_string(), " OSPF ".to_string()], "").unwrap();
+        assert_eq!(names(&filter), vec!["dn42_broken", "dn42_kioubit", "ibgp_sjc2", "ospf_igp"]);
+    }
+
+    #[test]
+    fn test_name_filter() {
+        let filter = ProtocolFilter::new(&[], "^ibgp_|_broken$").unwrap();
+        assert_eq!(names(&filter), vec!["device1", "dn42_kioubit", "kernel1", "ospf_igp", "static1"]);
+
+        let filter = ProtocolFilter::new(&["BGP".to_string()], "^ibgp_").unwrap();
+        assert_eq!(names(&filter), vec!["dn42_broken", "dn42_kioubit"]);
+    }
+
+    #[test]
+    fn test_invalid_name_filter() {
+        assert!(ProtocolFilter::new(&[], "(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_filter_raw_output() {
+        let filter = ProtocolFilter::new(&["BGP".to_string()], "^ibgp_").unwrap();
+        let output = filter.filter_output(SUMMARY);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Name"));
+        assert!(lines[1].starts_with("dn42_kioubit"));
+        assert!(lines[2].starts_with("dn42_broken"));
+    }
+}
@@ -4,9 +4,9 @@ use axum::{
     response::IntoResponse,
 };
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
+use crate::bird_command::{BirdQuery, RouteOptions, RouteTarget};
 use crate::settings::Settings;
-use crate::{proxy_client, whois};
+use crate::{fanout, proxy_client, route_parser, whois};
 
 #[derive(Deserialize)]
 struct TgChat {
@@ -38,11 +38,11 @@ fn telegram_is_command(message: &str, command: &str) -> bool {
     let settings = Settings::global();
     let bot_name = &settings.telegram_bot_name;
     
-    if !bot_name.is_empty() {
-        if message.starts_with(&format!("/{}@{} ", command, bot_name)) ||
-           message == format!("/{}@{}", command, bot_name) {
-            return true;
-        }
+    if !bot_name.is_empty()
+        && (message.starts_with(&format!("/{}@{} ", command, bot_name))
+            || message == format!("/{}@{}", command, bot_name))
+    {
+        return true;
     }
     
     message.starts_with(&format!("/{} ", command)) || message == format!("/{}", command)
@@ -69,18 +69,19 @@ async fn telegram_batch_request_format(
     }
     
     let mut result = String::new();
+    let responses = fanout::query_all(servers, |server| async move {
+        match endpoint {
+            "traceroute" => proxy_client::traceroute_query(&server, command).await,
+            "bird" => proxy_client::bird_query(&server, command).await,
+            _ => Err(anyhow::anyhow!("Unknown endpoint: {}", endpoint)),
+        }
+    }).await;
     
-    for server in servers {
+    for (server, response) in servers.iter().zip(responses) {
         if servers.len() > 1 {
             result.push_str(&format!("{}\n", server));
         }
         
-        let response = match endpoint {
-            "traceroute" => proxy_client::traceroute_query(server, command).await,
-            "bird" => proxy_client::bird_query(server, command).await,
-            _ => Err(anyhow::anyhow!("Unknown endpoint: {}", endpoint)),
-        };
-        
         match response {
             Ok(res) => {
                 result.push_str(&post_process(&res));
@@ -95,76 +96,29 @@ async fn telegram_batch_request_format(
     result
 }
 
-fn extract_as_path(result: &str) -> String {
-    for line in result.lines() {
-        if line.contains("BGP.as_path: ") || line.contains("bgp_path: ") {
-            if let Some(path) = line.split(':').nth(1) {
-                return path.trim().to_string();
-            }
-        }
-    }
-    String::new()
-}
-
-async fn process_whois_command(target: &str) -> Result<String> {
+fn summary_post_process(result: &str) -> String {
     let settings = Settings::global();
-    let mut target = target.to_string();
-    
-    // Handle dn42 specific ASN formatting
-    if settings.net_specific_mode == "dn42" || settings.net_specific_mode == "dn42_generic" {
-        if let Ok(target_number) = target.parse::<u64>() {
-            if target_number < 10000 {
-                target = format!("AS{}", target_number + 4242420000);
-            } else {
-                target = format!("AS{}", target);
-            }
-        }
-    }
-    
-    let temp_result = whois::query(&target).await?;
-    
-    // Apply network-specific filters
-    let result = match settings.net_specific_mode.as_str() {
-        "dn42" => dn42_whois_filter(&temp_result),
-        "dn42_shorten" | "shorten" => shorten_whois_filter(&temp_result),
-        _ => temp_result,
-    };
-    
-    Ok(result)
+    settings.protocol_filter.filter_output(result.trim())
 }
 
-// Simplified whois filters (you may want to implement these based on Go version)
-fn dn42_whois_filter(result: &str) -> String {
-    // Simplified implementation - filter relevant dn42 information
-    result.lines()
-        .filter(|line| {
-            line.contains("aut-num:") ||
-            line.contains("as-name:") ||
-            line.contains("descr:") ||
-            line.contains("admin-c:") ||
-            line.contains("tech-c:") ||
-            line.contains("mnt-by:")
+fn extract_as_path(result: &str) -> String {
+    route_parser::parse_routes(result)
+        .iter()
+        .find(|route| !route.as_path.is_empty())
+        .map(|route| {
+            route.as_path
+                .iter()
+                .map(|asn| asn.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
         })
-        .collect::<Vec<_>>()
-        .join("\n")
-}
-
-fn shorten_whois_filter(result: &str) -> String {
-    // Simplified implementation - show only essential information
-    result.lines()
-        .take(20) // Limit to first 20 lines
-        .collect::<Vec<_>>()
-        .join("\n")
+        .unwrap_or_default()
 }
 
 pub async fn telegram_webhook(request: Request) -> impl IntoResponse {
     // Extract the path to get servers list
     let path = request.uri().path().to_string();
-    let servers_path = if path.starts_with("/telegram/") {
-        &path[10..] // Remove "/telegram/" prefix
-    } else {
-        ""
-    };
+    let servers_path = path.strip_prefix("/telegram/").unwrap_or("");
     
     // Parse JSON body with size limit (100KB)
     const MAX_BODY_SIZE: usize = 100 * 1024;
@@ -221,21 +175,34 @@ pub async fn telegram_webhook(request: Request) -> impl IntoResponse {
         telegram_batch_request_format(&servers, "traceroute", &target, telegram_default_post_process).await
         
     } else if telegram_is_command(&text, "route") {
-        let command = format!("show route for {} primary", target);
-        telegram_batch_request_format(&servers, "bird", &command, telegram_default_post_process).await
+        match RouteTarget::parse(&target) {
+            Ok(target) => {
+                let command = BirdQuery::RouteFor(target, RouteOptions::PRIMARY).to_string();
+                telegram_batch_request_format(&servers, "bird", &command, telegram_default_post_process).await
+            }
+            Err(e) => format!("Error: {}", e),
+        }
         
     } else if telegram_is_command(&text, "path") {
-        let command = format!("show route for {} all primary", target);
-        telegram_batch_request_format(&servers, "bird", &command, |result| extract_as_path(result)).await
+        match RouteTarget::parse(&target) {
+            Ok(target) => {
+                let command = BirdQuery::RouteFor(target, RouteOptions::ALL_PRIMARY).to_string();
+                telegram_batch_request_format(&servers, "bird", &command, extract_as_path).await
+            }
+            Err(e) => format!("Error: {}", e),
+        }
+        
+    } else if telegram_is_command(&text, "summary") {
+        telegram_batch_request_format(&servers, "bird", &BirdQuery::Summary.to_string(), summary_post_process).await
         
     } else if telegram_is_command(&text, "whois") {
-        match process_whois_command(&target).await {
+        match whois::lookup(&target).await {
             Ok(result) => result,
             Err(e) => format!("Error: {}", e),
         }
         
     } else if telegram_is_command(&text, "help") {
-        "/path <IP>\n/route <IP>\n/trace <IP>\n/whois <Target>".to_string()
+        "/path <IP>\n/route <IP>\n/summary\n/trace <IP>\n/whois <Target>".to_string()
         
     } else {
         return StatusCode::OK.into_response();
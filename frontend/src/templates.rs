@@ -1,6 +1,9 @@
 use anyhow::{Result, anyhow};
 use serde::Serialize;
 use std::sync::OnceLock;
+use crate::dns::DnsComparison;
+use crate::protocol_parser::ProtocolDetail;
+use crate::traceroute::TracerouteReport;
 use tera::{Context, Tera};
 use rust_embed::RustEmbed;
 
@@ -68,6 +71,28 @@ pub struct SummaryRowData {
     pub info: String,
 }
 
+#[derive(Serialize)]
+pub struct ProtocolContext {
+    pub server_name: String,
+    pub protocols: Vec<ProtocolDetail>,
+    pub raw: String,
+}
+
+#[derive(Serialize)]
+pub struct DnsContext {
+    pub target: String,
+    /// Whether more than one server answered, so answers can be compared
+    pub compared: bool,
+    pub comparisons: Vec<DnsComparison>,
+}
+
+#[derive(Serialize)]
+pub struct TracerouteStreamContext {
+    pub target: String,
+    /// Display names, in the order of the `traceroute-<index>` sections
+    pub servers: Vec<String>,
+}
+
 pub fn init() -> Result<()> {
     let mut tera = Tera::default();
     
@@ -116,4 +141,28 @@ pub fn render_summary(context: &SummaryContext) -> Result<String> {
     let tera = get_templates();
     let rendered = tera.render("summary.html", &Context::from_serialize(context)?)?;
     Ok(rendered)
-} 
\ No newline at end of file
+}
+
+pub fn render_protocol(context: &ProtocolContext) -> Result<String> {
+    let tera = get_templates();
+    let rendered = tera.render("protocol.html", &Context::from_serialize(context)?)?;
+    Ok(rendered)
+}
+
+pub fn render_dns(context: &DnsContext) -> Result<String> {
+    let tera = get_templates();
+    let rendered = tera.render("dns.html", &Context::from_serialize(context)?)?;
+    Ok(rendered)
+}
+
+pub fn render_traceroute(report: &TracerouteReport) -> Result<String> {
+    let tera = get_templates();
+    let rendered = tera.render("traceroute.html", &Context::from_serialize(report)?)?;
+    Ok(rendered)
+}
+
+pub fn render_traceroute_stream(context: &TracerouteStreamContext) -> Result<String> {
+    let tera = get_templates();
+    let rendered = tera.render("traceroute_stream.html", &Context::from_serialize(context)?)?;
+    Ok(rendered)
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+/// One hop of a proxy's traceroute report
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Hop {
+    pub hop: u32,
+    pub address: Option<String>,
+    pub hostname: Option<String>,
+    /// Other addresses that answered for the same hop
+    #[serde(default)]
+    pub alternates: Vec<String>,
+    pub asn: Option<u32>,
+    pub sent: u32,
+    /// Share of probes without an answer, in percent
+    pub loss: f64,
+    #[serde(default)]
+    pub rtts: Vec<f64>,
+    pub avg: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+}
+
+/// A proxy's `/traceroute?format=json` reply
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct TracerouteReport {
+    pub target: String,
+    pub tool: String,
+    pub hops: Vec<Hop>,
+    /// The run as text, as shown without `format=json`
+    pub text: String,
+}
+
+/// Read a traceroute reply. Proxies without JSON support answer with text,
+/// which is kept as a report without hops.
+pub fn parse_reply(target: &str, body: &str) -> TracerouteReport {
+    serde_json::from_str(body).unwrap_or_else(|_| TracerouteReport {
+        target: target.to_string(),
+        tool: String::new(),
+        hops: Vec::new(),
+        text: body.to_string(),
+    })
+}
+
+/// Progress of a streamed traceroute
+#[derive(Debug, Clone, PartialEq)]
+pub enum TracerouteEvent {
+    Line(String),
+    Report(TracerouteReport),
+    Error(String),
+}
+
+/// Reads the server-sent events of `format=stream` replies. Proxies without
+/// streaming answer with text, whose lines are read the same way.
+pub struct EventParser {
+    target: String,
+    streamed: bool,
+    event: String,
+    data: Vec<String>,
+    /// Text replies, kept for the report at the end
+    text: String,
+    finished: bool,
+}
+
+impl EventParser {
+    pub fn new(target: &str, streamed: bool) -> Self {
+        Self {
+            target: target.to_string(),
+            streamed,
+            event: String::new(),
+            data: Vec::new(),
+            text: String::new(),
+            finished: false,
+        }
+    }
+
+    /// Events completed by a chunk of whole lines
+    pub fn push(&mut self, text: &str) -> Vec<TracerouteEvent> {
+        if !self.streamed {
+            self.text.push_str(text);
+            return text.lines().map(|line| TracerouteEvent::Line(line.to_string())).collect();
+        }
+
+        let mut events = Vec::new();
+        for line in text.lines() {
+            if line.is_empty() {
+                events.extend(self.dispatch());
+            } else if let Some(event) = line.strip_prefix("event:") {
+                self.event = event.trim_start().to_string();
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+            // Comments, such as keep-alives, and other fields are ignored
+        }
+        events
+    }
+
+    /// The last event, once the reply has ended
+    pub fn finish(&mut self) -> Option<TracerouteEvent> {
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        if self.streamed {
+            Some(TracerouteEvent::Error("traceroute ended without a result".to_string()))
+        } else {
+            Some(TracerouteEvent::Report(parse_reply(&self.target, &self.text)))
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<TracerouteEvent> {
+        let event = std::mem::take(&mut self.event);
+        let data = std::mem::take(&mut self.data).join("\n");
+        match event.as_str() {
+            "line" => Some(TracerouteEvent::Line(data)),
+            "report" | "error" if self.finished => None,
+            "report" => {
+                self.finished = true;
+                Some(match serde_json::from_str(&data) {
+                    Ok(report) => TracerouteEvent::Report(report),
+                    Err(e) => TracerouteEvent::Error(format!("invalid traceroute report: {}", e)),
+                })
+            }
+            "error" => {
+                self.finished = true;
+                Some(TracerouteEvent::Error(data))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        let body = r#"{"target":"1.1.1.1","tool":"traceroute","text":" 1  _gateway (192.168.1.1)  0.421 ms","hops":[
+            {"hop":1,"address":"192.168.1.1","hostname":"_gateway","asn":null,"sent":1,"loss":0.0,"rtts":[0.421],"avg":0.421,"best":0.421,"worst":0.421},
+            {"hop":2,"address":null,"hostname":null,"asn":null,"sent":1,"loss":100.0,"rtts":[],"avg":null,"best":null,"worst":null}
+        ]}"#;
+        let report = parse_reply("1.1.1.1", body);
+        assert_eq!(report.tool, "traceroute");
+        assert_eq!(report.hops.len(), 2);
+        assert_eq!(report.hops[0].hostname.as_deref(), Some("_gateway"));
+        assert_eq!(report.hops[1].avg, None);
+
+        // Older proxies ignore format=json
+        let text = "traceroute to 1.1.1.1 (1.1.1.1), 30 hops max\n 1  _gateway (192.168.1.1)  0.421 ms";
+        let report = parse_reply("1.1.1.1", text);
+        assert!(report.hops.is_empty());
+        assert_eq!(report.text, text);
+        assert_eq!(report.target, "1.1.1.1");
+    }
+
+    #[test]
+    fn test_event_parser() {
+        let mut parser = EventParser::new("192.0.2.1", true);
+        assert_eq!(
+            parser.push("event: line\ndata: traceroute to 192.0.2.1\n\n: keep-alive\n\nevent: line\n"),
+            vec![TracerouteEvent::Line("traceroute to 192.0.2.1".to_string())]
+        );
+        assert_eq!(
+            parser.push("data:  1  192.0.2.1  0.5 ms\n\n"),
+            vec![TracerouteEvent::Line(" 1  192.0.2.1  0.5 ms".to_string())]
+        );
+
+        let events = parser.push("event: report\ndata: {\"target\":\"192.0.2.1\",\"tool\":\"traceroute\",\"hops\":[],\"text\":\"\"}\n\n");
+        assert!(matches!(&events[..], [TracerouteEvent::Report(report)] if report.tool == "traceroute"));
+        assert_eq!(parser.finish(), None);
+
+        let mut parser = EventParser::new("192.0.2.1", true);
+        assert_eq!(parser.push("event: error\ndata: Traceroute not supported on this node\n\n").len(), 1);
+        assert_eq!(parser.finish(), None);
+
+        // A reply cut short
+        let mut parser = EventParser::new("192.0.2.1", true);
+        assert!(parser.push("event: line\ndata: traceroute to 192.0.2.1\n").is_empty());
+        assert!(matches!(parser.finish(), Some(TracerouteEvent::Error(_))));
+    }
+
+    #[test]
+    fn test_event_parser_text() {
+        let mut parser = EventParser::new("192.0.2.1", false);
+        assert_eq!(parser.push("traceroute to 192.0.2.1\n 1  192.0.2.1  0.5 ms\n").len(), 2);
+        match parser.finish() {
+            Some(TracerouteEvent::Report(report)) => {
+                assert!(report.hops.is_empty());
+                assert_eq!(report.text, "traceroute to 192.0.2.1\n 1  192.0.2.1  0.5 ms\n");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -43,11 +43,17 @@ fn add_default_whois_port(server: &str) -> String {
     format!("{}:43", server)
 }
 
-pub async fn query(target: &str) -> Result<String> {
+// Looks up a target the way the configured net_specific_mode expects:
+// normalizes the query, then filters the reply for display.
+pub async fn lookup(target: &str) -> Result<String> {
     let settings = Settings::global();
-    
+    let mode = settings.net_specific_mode;
+    let result = query_server(&settings.whois_server, &mode.whois_target(target)).await?;
+    Ok(mode.filter_whois(&result))
+}
+
+pub async fn query_server(whois_server: &str, target: &str) -> Result<String> {
     // Validate and prepare whois server address
-    let whois_server = &settings.whois_server;
     let server_addr = add_default_whois_port(whois_server);
     
     // Connect to whois server with timeout
@@ -1,132 +1,532 @@
-use anyhow::{anyhow, Result};
-use std::io::{BufReader, Write};
+use bytes::Bytes;
+use futures_util::{stream, Stream};
+use std::convert::Infallible;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use crate::bird_reply::{BirdError, ReplyLine};
+use crate::metrics::Metrics;
 use crate::settings::Settings;
-use tracing::debug;
+use tracing::{debug, warn};
 
 #[cfg(unix)]
-use std::os::unix::net::UnixStream;
+type BirdStream = tokio::net::UnixStream;
 
 #[cfg(not(unix))]
-use std::net::TcpStream;
+type BirdStream = tokio::net::TcpStream;
+
+const MAX_LINE_SIZE: usize = 64 * 1024;
+const CHUNK_SIZE: usize = 16 * 1024;
+
+static BIRD_POOL: OnceLock<BirdPool> = OnceLock::new();
+
+/// Connect to BIRD socket - Unix socket on Unix systems, TCP fallback on others
+#[cfg(unix)]
+async fn connect_to_bird(socket_path: &str) -> Result<BirdStream, BirdError> {
+    BirdStream::connect(socket_path)
+        .await
+        .map_err(|e| BirdError::Unavailable(format!("Failed to connect to BIRD Unix socket: {}", e)))
+}
 
 #[cfg(not(unix))]
-type UnixStream = TcpStream;
+async fn connect_to_bird(socket_path: &str) -> Result<BirdStream, BirdError> {
+    // On non-Unix systems, treat the socket_path as host:port for TCP connection
+    let addr = if socket_path.contains(':') {
+        socket_path.to_string()
+    } else {
+        format!("127.0.0.1:{}", socket_path)
+    };
 
-const MAX_LINE_SIZE: usize = 1024;
+    BirdStream::connect(&addr)
+        .await
+        .map_err(|e| BirdError::Unavailable(format!("Failed to connect to BIRD TCP socket: {}", e)))
+}
 
-/// Check if a byte is numeric
-fn is_numeric(b: u8) -> bool {
-    b >= b'0' && b <= b'9'
+/// A single restricted connection to the BIRD control socket
+struct BirdConnection {
+    stream: BufReader<BirdStream>,
+    last_used: Instant,
 }
 
-/// Read a line from bird socket, removing preceding status number
-/// Returns if there are more lines
-fn bird_read_line(reader: &mut BufReader<UnixStream>, output: &mut Vec<u8>) -> Result<bool> {
-    let mut line = Vec::new();
-    
-    // Read line byte by byte up to MAX_LINE_SIZE
-    for _ in 0..MAX_LINE_SIZE {
-        let mut byte = [0u8; 1];
-        match std::io::Read::read_exact(reader, &mut byte) {
-            Ok(_) => {
-                line.push(byte[0]);
-                if byte[0] == b'\n' {
-                    break;
+impl BirdConnection {
+    /// Connect, consume the greeting and perform the `restrict` handshake
+    async fn open(socket_path: &str) -> Result<Self, BirdError> {
+        let stream = connect_to_bird(socket_path)
+            .await
+            .inspect_err(|_| Metrics::global().bird_connect_error())?;
+        let mut conn = Self {
+            stream: BufReader::new(stream),
+            last_used: Instant::now(),
+        };
+
+        // Read initial greeting
+        let mut greeting = Vec::new();
+        conn.read_line(&mut greeting).await?;
+
+        // Send restrict command and verify the confirmation
+        conn.write_line("restrict").await?;
+        let mut restrict_output = Vec::new();
+        conn.read_line(&mut restrict_output).await?;
+
+        let restrict_response = String::from_utf8_lossy(&restrict_output);
+        if !restrict_response.contains("Access restricted") {
+            return Err(BirdError::Unavailable("Could not verify that bird access was restricted".to_string()));
+        }
+
+        debug!("Opened new restricted BIRD connection");
+        Ok(conn)
+    }
+
+    /// Read a line from bird socket, removing preceding status number
+    /// Returns if there are more lines, or the error BIRD replied with
+    async fn read_line(&mut self, output: &mut Vec<u8>) -> Result<bool, BirdError> {
+        let mut line = Vec::new();
+        let read = (&mut self.stream)
+            .take(MAX_LINE_SIZE as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+
+        if read == 0 {
+            return Err(BirdError::Unavailable("BIRD closed the control socket".to_string()));
+        }
+        if !line.ends_with(b"\n") {
+            if read >= MAX_LINE_SIZE {
+                return Err(BirdError::Unavailable(format!("BIRD reply line exceeds {} bytes", MAX_LINE_SIZE)));
+            }
+            return Err(BirdError::Unavailable("BIRD closed the control socket mid-line".to_string()));
+        }
+
+        debug!("Bird raw line: {:?}", String::from_utf8_lossy(&line));
+
+        let reply = ReplyLine::parse(&line);
+        match reply.code {
+            Some(code) if code >= 8000 => Err(BirdError::from_reply(code, reply.text_lossy())),
+            Some(_) => {
+                // Coded lines with an empty text (such as the closing "0000 ") carry no output
+                if !reply.text.is_empty() {
+                    output.extend_from_slice(reply.text);
+                    output.push(b'\n');
                 }
+                Ok(!reply.is_final())
             }
-            Err(e) => {
-                output.extend_from_slice(e.to_string().as_bytes());
-                return Ok(false);
+            None => {
+                output.extend_from_slice(reply.text);
+                output.push(b'\n');
+                Ok(true)
             }
         }
     }
 
-    debug!("Bird raw line: {:?}", String::from_utf8_lossy(&line));
+    /// Write a command to bird socket
+    async fn write_line(&mut self, command: &str) -> Result<(), BirdError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{}\n", command).as_bytes()).await?;
+        stream.flush().await?;
+        Ok(())
+    }
 
-    // Remove preceding status number if present
-    if line.len() > 4 
-        && is_numeric(line[0]) 
-        && is_numeric(line[1]) 
-        && is_numeric(line[2]) 
-        && is_numeric(line[3]) 
-    {
-        // There is a status number at beginning, remove first 5 bytes (4 digits + space)
-        if line.len() > 6 {
-            output.extend_from_slice(&line[5..]);
+    /// Cheap liveness check for an idle connection, without a round trip to BIRD.
+    /// A healthy idle connection has nothing buffered and nothing to read;
+    /// EOF or unexpected data means it can no longer be used.
+    fn is_healthy(&self, idle_timeout: Duration) -> bool {
+        if self.last_used.elapsed() > idle_timeout || !self.stream.buffer().is_empty() {
+            return false;
         }
-        // Return true if status is not 0, 8, or 9 (meaning more lines follow)
-        Ok(line[0] != b'0' && line[0] != b'8' && line[0] != b'9')
-    } else {
-        // No status number, output the line (skip first byte which might be a space)
-        if line.len() > 1 {
-            output.extend_from_slice(&line[1..]);
+
+        let mut probe = [0u8; 1];
+        matches!(
+            self.stream.get_ref().try_read(&mut probe),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock
+        )
+    }
+}
+
+/// Bounded pool of restricted BIRD control socket connections
+pub struct BirdPool {
+    socket_path: String,
+    idle: Mutex<Vec<BirdConnection>>,
+    permits: Semaphore,
+    query_timeout: Duration,
+    idle_timeout: Duration,
+}
+
+/// A connection checked out of the pool. It is handed back on drop only after
+/// a complete reply has been read, otherwise it is closed.
+struct PooledConnection<'a> {
+    pool: &'a BirdPool,
+    conn: Option<BirdConnection>,
+    reusable: bool,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            if self.reusable {
+                self.pool.lock_idle().push(conn);
+            }
         }
-        Ok(true)
     }
 }
 
-/// Write a command to bird socket
-fn bird_write_line(stream: &mut UnixStream, command: &str) -> Result<()> {
-    stream.write_all(format!("{}\n", command).as_bytes())?;
-    stream.flush()?;
-    Ok(())
+impl BirdPool {
+    pub fn new(socket_path: String, size: usize, query_timeout: Duration, idle_timeout: Duration) -> Self {
+        Self {
+            socket_path,
+            idle: Mutex::new(Vec::with_capacity(size)),
+            permits: Semaphore::new(size.max(1)),
+            query_timeout,
+            idle_timeout,
+        }
+    }
+
+    /// The idle connections. A panic elsewhere must not poison the pool,
+    /// since connections are returned from `Drop`.
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<BirdConnection>> {
+        self.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Take a healthy idle connection, discarding stale ones along the way
+    fn take_idle(&self) -> Option<BirdConnection> {
+        let mut idle = self.lock_idle();
+        while let Some(conn) = idle.pop() {
+            if conn.is_healthy(self.idle_timeout) {
+                return Some(conn);
+            }
+            debug!("Discarding stale BIRD connection");
+        }
+        None
+    }
+
+    async fn checkout(&self) -> Result<(PooledConnection<'_>, bool), BirdError> {
+        let permit = self.permits.acquire().await
+            .map_err(|e| BirdError::Unavailable(format!("Failed to acquire BIRD connection: {}", e)))?;
+
+        let (conn, reused) = match self.take_idle() {
+            Some(conn) => (conn, true),
+            None => (BirdConnection::open(&self.socket_path).await?, false),
+        };
+
+        let pooled = PooledConnection {
+            pool: self,
+            conn: Some(conn),
+            reusable: false,
+            _permit: permit,
+        };
+        Ok((pooled, reused))
+    }
+
+    async fn start(&self, query: &str, limits: OutputLimits) -> Result<BirdReply<'_>, BirdError> {
+        let (pooled, reused) = self.checkout().await?;
+        let mut reply = BirdReply::new(pooled, self.query_timeout, limits);
+
+        match reply.begin(query).await {
+            Err(e) if reused && !e.is_reply() => {
+                // BIRD may have restarted since the connection was last used; retry once on a fresh one
+                debug!("Pooled BIRD connection failed ({}), reconnecting", e);
+                reply.pooled.conn = Some(BirdConnection::open(&self.socket_path).await?);
+                reply.begin(query).await?;
+            }
+            result => result?,
+        }
+
+        Ok(reply)
+    }
+
+    /// Execute a query on a pooled connection. Connecting and reading the first
+    /// chunk are bounded by the query timeout, so reply errors are known before
+    /// any output is returned; later reads are bounded individually.
+    pub async fn query(&self, query: &str, limits: OutputLimits) -> Result<BirdReply<'_>, BirdError> {
+        tokio::time::timeout(self.query_timeout, self.start(query, limits))
+            .await
+            .map_err(|_| BirdError::Timeout(self.query_timeout))?
+    }
 }
 
-/// Connect to BIRD socket - Unix socket on Unix systems, TCP fallback on others
-#[cfg(unix)]
-fn connect_to_bird(socket_path: &str) -> Result<UnixStream> {
-    UnixStream::connect(socket_path)
-        .map_err(|e| anyhow!("Failed to connect to BIRD Unix socket: {}", e))
+/// Optional caps on the amount of output streamed for a single query
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputLimits {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
 }
 
-#[cfg(not(unix))]
-fn connect_to_bird(socket_path: &str) -> Result<UnixStream> {
-    // On non-Unix systems, treat the socket_path as host:port for TCP connection
-    let addr = if socket_path.contains(':') {
-        socket_path.to_string()
-    } else {
-        format!("127.0.0.1:{}", socket_path)
-    };
-    
-    TcpStream::connect(&addr)
-        .map_err(|e| anyhow!("Failed to connect to BIRD TCP socket: {}", e))
+impl OutputLimits {
+    fn exceeded(&self, lines: usize, bytes: usize) -> Option<String> {
+        match (self.max_lines, self.max_bytes) {
+            (Some(max), _) if lines > max => Some(format!("{} lines", max)),
+            (_, Some(max)) if bytes > max => Some(format!("{} bytes", max)),
+            _ => None,
+        }
+    }
+}
+
+/// A reply being read from a pooled connection, chunk by chunk
+pub struct BirdReply<'a> {
+    pooled: PooledConnection<'a>,
+    first: Option<Vec<u8>>,
+    read_timeout: Duration,
+    limits: OutputLimits,
+    lines: usize,
+    bytes: usize,
+    done: bool,
+}
+
+impl<'a> BirdReply<'a> {
+    fn new(pooled: PooledConnection<'a>, read_timeout: Duration, limits: OutputLimits) -> Self {
+        Self {
+            pooled,
+            first: None,
+            read_timeout,
+            limits,
+            lines: 0,
+            bytes: 0,
+            done: false,
+        }
+    }
+
+    /// Send the query and read the first chunk, surfacing reply errors early
+    async fn begin(&mut self, query: &str) -> Result<(), BirdError> {
+        self.done = false;
+        self.lines = 0;
+        self.bytes = 0;
+
+        let conn = self.pooled.conn.as_mut().expect("Checked out connection is present");
+        if let Err(e) = conn.write_line(query).await {
+            self.done = true;
+            return Err(e);
+        }
+
+        self.first = self.read_chunk().await?;
+        Ok(())
+    }
+
+    /// Read lines up to CHUNK_SIZE bytes, or until no more data is buffered
+    async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, BirdError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let conn = self.pooled.conn.as_mut().expect("Checked out connection is present");
+        let mut chunk = Vec::new();
+
+        loop {
+            let mut line = Vec::new();
+            let more = match tokio::time::timeout(self.read_timeout, conn.read_line(&mut line)).await {
+                Ok(Ok(more)) => more,
+                Ok(Err(e)) => {
+                    // A reply error still leaves the connection at a clean reply boundary
+                    self.done = true;
+                    self.pooled.reusable = e.is_reply();
+                    return Err(e);
+                }
+                Err(_) => {
+                    self.done = true;
+                    return Err(BirdError::Timeout(self.read_timeout));
+                }
+            };
+
+            if !line.is_empty() {
+                self.lines += 1;
+                self.bytes += line.len();
+                if let Some(limit) = self.limits.exceeded(self.lines, self.bytes) {
+                    // The rest of the reply is left unread, so the connection is closed
+                    self.done = true;
+                    chunk.extend_from_slice(format!("\n[Output truncated: exceeded {}]\n", limit).as_bytes());
+                    return Ok(Some(chunk));
+                }
+                chunk.extend_from_slice(&line);
+            }
+
+            if !more {
+                self.done = true;
+                self.pooled.reusable = true;
+                conn.last_used = Instant::now();
+                return Ok(if chunk.is_empty() { None } else { Some(chunk) });
+            }
+
+            if chunk.len() >= CHUNK_SIZE || conn.stream.buffer().is_empty() {
+                return Ok(Some(chunk));
+            }
+        }
+    }
+
+    /// Next chunk of output, or `None` once the reply is complete
+    pub async fn next_chunk(&mut self) -> Result<Option<Bytes>, BirdError> {
+        if let Some(first) = self.first.take() {
+            return Ok(Some(first.into()));
+        }
+        Ok(self.read_chunk().await?.map(Bytes::from))
+    }
+
+    /// Turn the reply into a response body stream. Errors after output has
+    /// started can no longer change the status, so they end the body with a notice.
+    pub fn into_body_stream(self) -> impl Stream<Item = Result<Bytes, Infallible>> + 'a {
+        stream::unfold(self, |mut reply| async move {
+            match reply.next_chunk().await {
+                Ok(Some(chunk)) => Some((Ok(chunk), reply)),
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Bird reply aborted: {}", e);
+                    Some((Ok(Bytes::from(format!("\n[{}]\n", e))), reply))
+                }
+            }
+        })
+    }
 }
 
-/// Execute a BIRD command and return the output
-pub async fn execute_bird_command(query: &str) -> Result<String> {
+/// Initialize the global BIRD connection pool
+pub fn init() {
     let settings = Settings::global();
-    
-    // Connect to BIRD socket
-    let mut stream = connect_to_bird(&settings.bird_socket)?;
-    let mut reader = BufReader::new(stream.try_clone().map_err(|e| anyhow!("Failed to clone stream: {}", e))?);
-    
-    // Read initial greeting
-    let mut temp_output = Vec::new();
-    bird_read_line(&mut reader, &mut temp_output)?;
-    
-    // Send restrict command
-    bird_write_line(&mut stream, "restrict")?;
-    
-    // Read restriction confirmation
-    let mut restrict_output = Vec::new();
-    bird_read_line(&mut reader, &mut restrict_output)?;
-    
-    let restrict_response = String::from_utf8_lossy(&restrict_output);
-    if !restrict_response.contains("Access restricted") {
-        return Err(anyhow!("Could not verify that bird access was restricted"));
-    }
-    
-    // Send the actual query
-    bird_write_line(&mut stream, query)?;
-    
-    // Read the response
-    let mut output = Vec::new();
-    while bird_read_line(&mut reader, &mut output)? {
-        // Continue reading until no more lines
-    }
-    
-    let result = String::from_utf8_lossy(&output).to_string();
-    debug!("Bird command '{}' output: {}", query, result);
-    
-    Ok(result)
-} 
\ No newline at end of file
+
+    let pool = BirdPool::new(
+        settings.bird_socket.clone(),
+        settings.bird_pool_size,
+        Duration::from_secs(settings.bird_timeout),
+        Duration::from_secs(settings.bird_idle_timeout),
+    );
+    if BIRD_POOL.set(pool).is_err() {
+        panic!("BIRD pool already initialized");
+    }
+}
+
+/// Execute a BIRD command and return its reply for streaming
+pub async fn execute_bird_command(query: &str) -> Result<BirdReply<'static>, BirdError> {
+    let settings = Settings::global();
+    let pool = BIRD_POOL
+        .get()
+        .ok_or_else(|| BirdError::Unavailable("BIRD pool not initialized".to_string()))?;
+
+    let limits = OutputLimits {
+        max_lines: settings.bird_max_lines,
+        max_bytes: settings.bird_max_bytes,
+    };
+
+    debug!("Executing bird command '{}'", query);
+    pool.query(query, limits).await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::UnixListener;
+
+    async fn collect(mut reply: BirdReply<'_>) -> Result<String, BirdError> {
+        let mut output = Vec::new();
+        while let Some(chunk) = reply.next_chunk().await? {
+            output.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    async fn query(pool: &BirdPool, query: &str) -> Result<String, BirdError> {
+        collect(pool.query(query, OutputLimits::default()).await?).await
+    }
+
+    /// Minimal fake BIRD daemon: greets, accepts `restrict` and answers every
+    /// other command with a one-line route table
+    async fn spawn_fake_bird(name: &str) -> (String, Arc<AtomicUsize>) {
+        let path = std::env::temp_dir().join(format!("bird-lg-test-{}-{}.ctl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).expect("Failed to bind fake bird socket");
+        let connects = Arc::new(AtomicUsize::new(0));
+
+        let counter = connects.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    stream.get_mut().write_all(b"0001 BIRD 2.15 ready.\n").await.ok();
+                    let mut line = String::new();
+                    while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let reply: &[u8] = if line.trim() == "restrict" {
+                            b"0016 Access restricted\n"
+                        } else if line.trim() == "show route for 192.0.2.1" {
+                            b"8001 Network not found\n"
+                        } else if line.starts_with("show route") {
+                            b"1007-Table master4:\n1007-10.0.0.0/8 unreachable [static1 2024-01-01] * (200)\n0000 \n"
+                        } else {
+                            b"9001 syntax error, unexpected CF_SYM_UNDEFINED\n"
+                        };
+                        stream.get_mut().write_all(reply).await.ok();
+                        line.clear();
+                    }
+                });
+            }
+        });
+
+        (path.to_string_lossy().to_string(), connects)
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_restricted_connection() {
+        let (path, connects) = spawn_fake_bird("reuse").await;
+        let pool = BirdPool::new(path, 2, Duration::from_secs(5), Duration::from_secs(60));
+
+        for _ in 0..3 {
+            let output = query(&pool, "show route").await.expect("Query failed");
+            assert_eq!(output, "Table master4:\n10.0.0.0/8 unreachable [static1 2024-01-01] * (200)\n");
+        }
+
+        assert_eq!(connects.load(Ordering::SeqCst), 1, "Sequential queries should share one connection");
+    }
+
+    #[tokio::test]
+    async fn test_pool_bounds_concurrent_connections() {
+        let (path, connects) = spawn_fake_bird("bounded").await;
+        let pool = Arc::new(BirdPool::new(path, 2, Duration::from_secs(5), Duration::from_secs(60)));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move { query(&pool, "show route").await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.expect("Task panicked").expect("Query failed");
+        }
+
+        assert!(connects.load(Ordering::SeqCst) <= 2, "Pool must not exceed its size");
+    }
+
+    #[tokio::test]
+    async fn test_reply_errors_keep_connection() {
+        let (path, connects) = spawn_fake_bird("errors").await;
+        let pool = BirdPool::new(path, 1, Duration::from_secs(5), Duration::from_secs(60));
+
+        let error = query(&pool, "show route for 192.0.2.1").await.expect_err("Expected route_not_found");
+        assert_eq!(error.kind(), "route_not_found");
+        assert_eq!(error.to_string(), "Network not found");
+
+        let error = query(&pool, "show nonsense").await.expect_err("Expected syntax_error");
+        assert_eq!(error.kind(), "syntax_error");
+        assert_eq!(error.code(), Some(9001));
+
+        query(&pool, "show route").await.expect("Query failed");
+        assert_eq!(connects.load(Ordering::SeqCst), 1, "Reply errors should not drop the connection");
+    }
+
+    #[tokio::test]
+    async fn test_output_limit_truncates() {
+        let (path, connects) = spawn_fake_bird("limits").await;
+        let pool = BirdPool::new(path, 1, Duration::from_secs(5), Duration::from_secs(60));
+        let limits = OutputLimits { max_lines: Some(1), max_bytes: None };
+
+        let reply = pool.query("show route", limits).await.expect("Query failed");
+        let output = collect(reply).await.expect("Query failed");
+        assert_eq!(output, "Table master4:\n\n[Output truncated: exceeded 1 lines]\n");
+
+        // A reply that fits exactly within the limit is not truncated
+        let limits = OutputLimits { max_lines: Some(2), max_bytes: None };
+        let reply = pool.query("show route", limits).await.expect("Query failed");
+        let output = collect(reply).await.expect("Query failed");
+        assert!(!output.contains("truncated"), "Unexpected truncation: {}", output);
+
+        // The truncated connection had unread output and must not be reused
+        query(&pool, "show route").await.expect("Query failed");
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,561 @@
+use ipnet::IpNet;
+use std::fmt;
+use std::net::IpAddr;
+
+/// A BIRD query from the subset of the CLI grammar the proxy lets through.
+/// Rendering it gives the canonical command sent to BIRD, so nothing but
+/// validated arguments reaches the control socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BirdCommand {
+    /// `show protocols [all] [<name> | "<pattern>"]`
+    ShowProtocols { all: bool, name: Option<ProtocolMatch> },
+    /// `show route [options]`
+    ShowRoute(RouteQuery),
+    /// One of the configured extra commands, with plain word arguments
+    Extra(Vec<String>),
+}
+
+/// Protocol selected by `show protocols`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolMatch {
+    Name(Symbol),
+    /// Quoted shell-style pattern, like `"bgp_*"`
+    Pattern(String),
+}
+
+/// A BIRD symbol: protocol or table name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol(String);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteQuery {
+    pub target: Option<RouteTarget>,
+    pub table: Option<Symbol>,
+    pub protocol: Option<Symbol>,
+    pub condition: Option<Condition>,
+    pub filtered: bool,
+    pub all: bool,
+    pub primary: bool,
+    pub count: bool,
+}
+
+/// Network or address a route query is limited to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteTarget {
+    /// `for <ip | prefix>`: the best matching route
+    For(Address),
+    /// `<prefix>`: exactly this network
+    Net(IpNet),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    Ip(IpAddr),
+    Net(IpNet),
+}
+
+/// The `where` conditions accepted in route queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// `net ~ [ <pattern>, ... ]`
+    NetIn(Vec<NetPattern>),
+    /// `bgp_path.last = <asn>`
+    OriginAs(u32),
+    /// `bgp_path.first = <asn>`
+    NeighborAs(u32),
+}
+
+/// Prefix pattern of a BIRD prefix set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetPattern {
+    pub net: IpNet,
+    pub lengths: PrefixLengths,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixLengths {
+    Exact,
+    /// `+`: the prefix and all more specifics
+    OrLonger,
+    /// `-`: the prefix and all less specifics
+    OrShorter,
+    /// `{low,high}`
+    Range(u8, u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, String>;
+
+fn tokenize(input: &str) -> ParseResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' | ']' | ',' | '=' | '~' => {
+                chars.next();
+                tokens.push(Token::Symbol(c));
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) if c.is_ascii_alphanumeric() || matches!(c, '_' | '*' | '?') => text.push(c),
+                        Some(c) => return Err(format!("character '{}' is not allowed in a quoted pattern", c)),
+                        None => return Err("unterminated quoted pattern".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            c if is_word_char(c) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if is_word_char(c) {
+                        word.push(c);
+                        chars.next();
+                    } else if c == '{' {
+                        // Prefix length range, the only place braces and commas may appear
+                        for c in chars.by_ref() {
+                            word.push(c);
+                            if c == '}' {
+                                break;
+                            }
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => return Err(format!("character '{}' is not allowed", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '/' | '+' | '-')
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn word(&mut self, what: &str) -> ParseResult<String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            Some(other) => Err(format!("expected {}, found '{}'", what, other)),
+            None => Err(format!("expected {}", what)),
+        }
+    }
+
+    fn symbol(&mut self, symbol: char) -> ParseResult<()> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            Some(other) => Err(format!("expected '{}', found '{}'", symbol, other)),
+            None => Err(format!("expected '{}'", symbol)),
+        }
+    }
+
+    /// Next token if it is `keyword`, matched case-insensitively
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Symbol {
+    pub fn parse(name: &str) -> ParseResult<Self> {
+        let mut chars = name.chars();
+        let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        if valid_start && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Ok(Symbol(name.to_string()))
+        } else {
+            Err(format!("'{}' is not a valid name", name))
+        }
+    }
+}
+
+impl Address {
+    fn parse(text: &str) -> ParseResult<Self> {
+        if let Ok(ip) = text.parse::<IpAddr>() {
+            Ok(Address::Ip(ip))
+        } else {
+            parse_net(text).map(Address::Net)
+        }
+    }
+}
+
+fn parse_net(text: &str) -> ParseResult<IpNet> {
+    text.parse::<IpNet>().map_err(|_| format!("'{}' is not a valid prefix", text))
+}
+
+fn parse_asn(text: &str) -> ParseResult<u32> {
+    text.parse::<u32>().map_err(|_| format!("'{}' is not a valid ASN", text))
+}
+
+impl NetPattern {
+    fn parse(text: &str) -> ParseResult<Self> {
+        let (net, lengths) = if let Some(net) = text.strip_suffix('+') {
+            (net, PrefixLengths::OrLonger)
+        } else if let Some(net) = text.strip_suffix('-') {
+            (net, PrefixLengths::OrShorter)
+        } else if let Some((net, range)) = text.strip_suffix('}').and_then(|text| text.split_once('{')) {
+            let (low, high) = range.split_once(',').ok_or_else(|| format!("invalid length range in '{}'", text))?;
+            let parse = |length: &str| length.trim().parse::<u8>().map_err(|_| format!("invalid length range in '{}'", text));
+            (net, PrefixLengths::Range(parse(low)?, parse(high)?))
+        } else {
+            (text, PrefixLengths::Exact)
+        };
+
+        let net = parse_net(net)?;
+        if let PrefixLengths::Range(low, high) = lengths {
+            if low > high || high > net.max_prefix_len() {
+                return Err(format!("invalid length range in '{}'", text));
+            }
+        }
+        Ok(NetPattern { net, lengths })
+    }
+}
+
+impl BirdCommand {
+    /// Parse `input`, accepting `show protocols`, `show route` and the
+    /// `extra` commands (like `show status`)
+    pub fn parse(input: &str, extra: &[String]) -> ParseResult<Self> {
+        let mut parser = Parser { tokens: tokenize(input)?, pos: 0 };
+
+        if parser.keyword("show") {
+            if parser.keyword("protocols") {
+                return parse_protocols(&mut parser);
+            }
+            if parser.keyword("route") {
+                return parse_route(&mut parser).map(BirdCommand::ShowRoute);
+            }
+        }
+
+        parse_extra(&parser.tokens, extra)
+    }
+}
+
+fn parse_protocols(parser: &mut Parser) -> ParseResult<BirdCommand> {
+    let all = parser.keyword("all");
+    let name = match parser.next() {
+        None => None,
+        Some(Token::Word(name)) => Some(ProtocolMatch::Name(Symbol::parse(&name)?)),
+        Some(Token::Quoted(pattern)) => Some(ProtocolMatch::Pattern(pattern)),
+        Some(other) => return Err(format!("unexpected '{}' in show protocols", other)),
+    };
+    if let Some(extra) = parser.next() {
+        return Err(format!("unexpected '{}' in show protocols", extra));
+    }
+    Ok(BirdCommand::ShowProtocols { all, name })
+}
+
+fn parse_route(parser: &mut Parser) -> ParseResult<RouteQuery> {
+    let mut query = RouteQuery::default();
+
+    fn set<T>(slot: &mut Option<T>, value: T, name: &str) -> ParseResult<()> {
+        if slot.is_some() {
+            return Err(format!("'{}' given more than once", name));
+        }
+        *slot = Some(value);
+        Ok(())
+    }
+
+    while let Some(token) = parser.next() {
+        let Token::Word(word) = token else {
+            return Err(format!("unexpected '{}' in show route", token));
+        };
+        match word.to_ascii_lowercase().as_str() {
+            "all" => query.all = true,
+            "primary" => query.primary = true,
+            "filtered" => query.filtered = true,
+            "count" => query.count = true,
+            "for" => {
+                let address = Address::parse(&parser.word("an address or prefix after 'for'")?)?;
+                set(&mut query.target, RouteTarget::For(address), "for")?;
+            }
+            "table" => {
+                let table = Symbol::parse(&parser.word("a table name")?)?;
+                set(&mut query.table, table, "table")?;
+            }
+            "protocol" => {
+                let protocol = Symbol::parse(&parser.word("a protocol name")?)?;
+                set(&mut query.protocol, protocol, "protocol")?;
+            }
+            "where" => {
+                let condition = parse_condition(parser)?;
+                set(&mut query.condition, condition, "where")?;
+            }
+            _ if word.starts_with(|c: char| c.is_ascii_hexdigit() || c == ':') && word.contains('/') => {
+                set(&mut query.target, RouteTarget::Net(parse_net(&word)?), "prefix")?;
+            }
+            _ => return Err(format!("'{}' is not allowed in show route", word)),
+        }
+    }
+
+    Ok(query)
+}
+
+fn parse_condition(parser: &mut Parser) -> ParseResult<Condition> {
+    let subject = parser.word("a condition after 'where'")?;
+    match subject.to_ascii_lowercase().as_str() {
+        "net" => {
+            parser.symbol('~')?;
+            parser.symbol('[')?;
+            let mut patterns = vec![NetPattern::parse(&parser.word("a prefix")?)?];
+            loop {
+                match parser.next() {
+                    Some(Token::Symbol(']')) => break,
+                    Some(Token::Symbol(',')) => patterns.push(NetPattern::parse(&parser.word("a prefix")?)?),
+                    Some(other) => return Err(format!("expected ',' or ']', found '{}'", other)),
+                    None => return Err("expected ']'".to_string()),
+                }
+            }
+            Ok(Condition::NetIn(patterns))
+        }
+        "bgp_path.last" => {
+            parser.symbol('=')?;
+            Ok(Condition::OriginAs(parse_asn(&parser.word("an ASN")?)?))
+        }
+        "bgp_path.first" => {
+            parser.symbol('=')?;
+            Ok(Condition::NeighborAs(parse_asn(&parser.word("an ASN")?)?))
+        }
+        _ => Err(format!("'where {}' is not allowed", subject)),
+    }
+}
+
+/// Match one of the extra commands, followed by plain words only: names,
+/// addresses and keywords, no filters or quoted text
+fn parse_extra(tokens: &[Token], extra: &[String]) -> ParseResult<BirdCommand> {
+    let words: Option<Vec<&str>> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) if !word.contains('{') => Some(word.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    if let Some(words) = words {
+        for command in extra {
+            let keywords: Vec<&str> = command.split_whitespace().collect();
+            let matches = words.len() >= keywords.len()
+                && keywords.iter().zip(&words).all(|(keyword, word)| keyword.eq_ignore_ascii_case(word));
+            if !keywords.is_empty() && matches {
+                return Ok(BirdCommand::Extra(words.iter().map(|word| word.to_string()).collect()));
+            }
+        }
+    }
+
+    let mut allowed = vec!["show protocols", "show route"];
+    allowed.extend(extra.iter().map(String::as_str));
+    Err(format!("only {} commands are allowed", allowed.join(", ")))
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Ip(ip) => write!(f, "{}", ip),
+            Address::Net(net) => write!(f, "{}", net),
+        }
+    }
+}
+
+impl fmt::Display for NetPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lengths {
+            PrefixLengths::Exact => write!(f, "{}", self.net),
+            PrefixLengths::OrLonger => write!(f, "{}+", self.net),
+            PrefixLengths::OrShorter => write!(f, "{}-", self.net),
+            PrefixLengths::Range(low, high) => write!(f, "{}{{{},{}}}", self.net, low, high),
+        }
+    }
+}
+
+impl fmt::Display for BirdCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BirdCommand::ShowProtocols { all, name } => {
+                write!(f, "show protocols")?;
+                if *all {
+                    write!(f, " all")?;
+                }
+                match name {
+                    Some(ProtocolMatch::Name(name)) => write!(f, " {}", name),
+                    Some(ProtocolMatch::Pattern(pattern)) => write!(f, " \"{}\"", pattern),
+                    None => Ok(()),
+                }
+            }
+            BirdCommand::ShowRoute(query) => {
+                write!(f, "show route")?;
+                match &query.target {
+                    Some(RouteTarget::For(address)) => write!(f, " for {}", address)?,
+                    Some(RouteTarget::Net(net)) => write!(f, " {}", net)?,
+                    None => {}
+                }
+                if let Some(table) = &query.table {
+                    write!(f, " table {}", table)?;
+                }
+                match &query.condition {
+                    Some(Condition::NetIn(patterns)) => {
+                        let patterns: Vec<String> = patterns.iter().map(ToString::to_string).collect();
+                        write!(f, " where net ~ [ {} ]", patterns.join(", "))?;
+                    }
+                    Some(Condition::OriginAs(asn)) => write!(f, " where bgp_path.last = {}", asn)?,
+                    Some(Condition::NeighborAs(asn)) => write!(f, " where bgp_path.first = {}", asn)?,
+                    None => {}
+                }
+                if let Some(protocol) = &query.protocol {
+                    write!(f, " protocol {}", protocol)?;
+                }
+                for (set, flag) in [(query.filtered, "filtered"), (query.all, "all"), (query.primary, "primary"), (query.count, "count")] {
+                    if set {
+                        write!(f, " {}", flag)?;
+                    }
+                }
+                Ok(())
+            }
+            BirdCommand::Extra(words) => write!(f, "{}", words.join(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extra() -> Vec<String> {
+        vec!["show status".to_string(), "show ospf".to_string()]
+    }
+
+    /// Canonical form of an accepted command
+    fn canonical(input: &str) -> String {
+        BirdCommand::parse(input, &extra()).unwrap().to_string()
+    }
+
+    fn rejected(input: &str) -> bool {
+        BirdCommand::parse(input, &extra()).is_err()
+    }
+
+    #[test]
+    fn test_show_protocols() {
+        assert_eq!(canonical("show protocols"), "show protocols");
+        assert_eq!(canonical("SHOW  Protocols all bgp_1"), "show protocols all bgp_1");
+        assert_eq!(canonical("show protocols \"dn42_*\""), "show protocols \"dn42_*\"");
+        assert_eq!(
+            BirdCommand::parse("show protocols all ibgp", &[]).unwrap(),
+            BirdCommand::ShowProtocols { all: true, name: Some(ProtocolMatch::Name(Symbol("ibgp".to_string()))) }
+        );
+
+        assert!(rejected("show protocols all bgp1 bgp2"));
+        assert!(rejected("show protocols 1bgp"));
+        assert!(rejected("show protocols \"a;b\""));
+        assert!(rejected("show protocols all bgp1; configure"));
+    }
+
+    #[test]
+    fn test_frontend_route_queries() {
+        // Every form the frontend builds
+        assert_eq!(canonical("show route for 192.0.2.1"), "show route for 192.0.2.1");
+        assert_eq!(canonical("show route for 2001:db8::/32 all"), "show route for 2001:db8::/32 all");
+        assert_eq!(canonical("show route where net ~ [ 10.0.0.0/8 ]"), "show route where net ~ [ 10.0.0.0/8 ]");
+        assert_eq!(canonical("show route where net ~ [ 10.0.0.0/8+ ] all"), "show route where net ~ [ 10.0.0.0/8+ ] all");
+        assert_eq!(canonical("show route protocol bgp1 all primary"), "show route protocol bgp1 all primary");
+        assert_eq!(canonical("show route filtered protocol bgp1 all"), "show route protocol bgp1 filtered all");
+        assert_eq!(canonical("show route where bgp_path.last = 4242423914 all primary"), "show route where bgp_path.last = 4242423914 all primary");
+    }
+
+    #[test]
+    fn test_typed_route_arguments() {
+        let BirdCommand::ShowRoute(query) = BirdCommand::parse("show route where net~[10.0.0.0/8{16,24},fd00::/8-] table master4", &[]).unwrap() else {
+            panic!("expected a route query");
+        };
+        assert_eq!(query.table, Some(Symbol("master4".to_string())));
+        assert_eq!(
+            query.condition,
+            Some(Condition::NetIn(vec![
+                NetPattern { net: "10.0.0.0/8".parse().unwrap(), lengths: PrefixLengths::Range(16, 24) },
+                NetPattern { net: "fd00::/8".parse().unwrap(), lengths: PrefixLengths::OrShorter },
+            ]))
+        );
+
+        assert_eq!(canonical("show route 192.0.2.0/24 count"), "show route 192.0.2.0/24 count");
+        assert_eq!(canonical("show route where bgp_path.first = 64512"), "show route where bgp_path.first = 64512");
+    }
+
+    #[test]
+    fn test_rejects_route_queries_outside_grammar() {
+        assert!(rejected("show route export bgp1"));
+        assert!(rejected("show route filter { accept; }"));
+        assert!(rejected("show route where net ~ [ 10.0.0.0/8 ] && bgp_path.len > 3"));
+        assert!(rejected("show route where ifname = \"eth0\""));
+        assert!(rejected("show route where bgp_path.last = AS64512"));
+        assert!(rejected("show route where bgp_path.last = 99999999999"));
+        assert!(rejected("show route for example.com"));
+        assert!(rejected("show route for 192.0.2.1 for 192.0.2.2"));
+        assert!(rejected("show route where net ~ [ 10.0.0.0/8{24,16} ]"));
+        assert!(rejected("show route where net ~ [ 10.0.0.0/8{16,40} ]"));
+        assert!(rejected("show route where net ~ [ 10.0.0.0/33 ]"));
+        assert!(rejected("show route where net ~ [ 10.0.0.0/8"));
+        assert!(rejected("show route protocol bgp-1"));
+    }
+
+    #[test]
+    fn test_extra_commands() {
+        assert_eq!(canonical("show status"), "show status");
+        assert_eq!(canonical("show ospf neighbors ospf1"), "show ospf neighbors ospf1");
+        assert_eq!(canonical("SHOW OSPF state"), "SHOW OSPF state");
+
+        assert!(rejected("show ospf \"x\""));
+        assert!(rejected("show memory"));
+        assert!(rejected("configure soft"));
+        assert!(rejected(""));
+        assert!(BirdCommand::parse("show status", &[]).is_err());
+    }
+}
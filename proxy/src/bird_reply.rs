@@ -0,0 +1,211 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::fmt;
+use std::time::Duration;
+
+/// A single line of a BIRD control socket reply
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplyLine<'a> {
+    /// 4-digit reply code, absent on continuation lines starting with a space
+    pub code: Option<u16>,
+    /// Line text without the code, separator and trailing newline
+    pub text: &'a [u8],
+}
+
+impl<'a> ReplyLine<'a> {
+    pub fn parse(line: &'a [u8]) -> Self {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+
+        // "DDDD-text" or "DDDD text": a coded line
+        if line.len() >= 4 && line[..4].iter().all(u8::is_ascii_digit) && matches!(line.get(4), None | Some(b' ') | Some(b'-')) {
+            let code = line[..4].iter().fold(0u16, |acc, d| acc * 10 + u16::from(d - b'0'));
+            return Self {
+                code: Some(code),
+                text: line.get(5..).unwrap_or_default(),
+            };
+        }
+
+        // " text": continuation of the previous coded line
+        Self {
+            code: None,
+            text: line.strip_prefix(b" ").unwrap_or(line),
+        }
+    }
+
+    /// Codes 0xxx (done), 8xxx (runtime error) and 9xxx (parse error) end a reply
+    pub fn is_final(&self) -> bool {
+        matches!(self.code, Some(code) if !(1000..8000).contains(&code))
+    }
+
+    pub fn text_lossy(&self) -> String {
+        String::from_utf8_lossy(self.text).trim().to_string()
+    }
+}
+
+/// Errors reported by BIRD itself or raised while talking to it
+#[derive(Debug)]
+pub enum BirdError {
+    /// 9xxx: BIRD could not parse the command
+    Syntax { code: u16, message: String },
+    /// 8001: the requested network is not in the table
+    RouteNotFound(String),
+    /// 8003: no protocol matched the given name
+    NoSuchProtocol(String),
+    /// 8007: the command is not permitted on a restricted connection
+    AccessDenied(String),
+    /// 8000: the reply exceeded BIRD's own size limit
+    ReplyTooLong(String),
+    /// Any other 8xxx runtime error
+    Runtime { code: u16, message: String },
+    /// The query was rejected by the proxy before reaching BIRD
+    NotAllowed(String),
+    /// The query was empty or malformed
+    InvalidQuery(String),
+    /// The control socket could not be reached or broke mid-reply
+    Unavailable(String),
+    /// The query did not complete within the configured timeout
+    Timeout(Duration),
+}
+
+impl BirdError {
+    /// Map a final 8xxx/9xxx reply line to an error
+    pub fn from_reply(code: u16, message: String) -> Self {
+        match code {
+            8000 => Self::ReplyTooLong(message),
+            8001 => Self::RouteNotFound(message),
+            8003 => Self::NoSuchProtocol(message),
+            8007 => Self::AccessDenied(message),
+            9000.. => Self::Syntax { code, message },
+            _ => Self::Runtime { code, message },
+        }
+    }
+
+    /// BIRD reply code, if the error came from BIRD
+    pub fn code(&self) -> Option<u16> {
+        match self {
+            Self::Syntax { code, .. } | Self::Runtime { code, .. } => Some(*code),
+            Self::RouteNotFound(_) => Some(8001),
+            Self::NoSuchProtocol(_) => Some(8003),
+            Self::AccessDenied(_) => Some(8007),
+            Self::ReplyTooLong(_) => Some(8000),
+            _ => None,
+        }
+    }
+
+    /// Machine-readable error identifier
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Syntax { .. } => "syntax_error",
+            Self::RouteNotFound(_) => "route_not_found",
+            Self::NoSuchProtocol(_) => "no_such_protocol",
+            Self::AccessDenied(_) => "access_denied",
+            Self::ReplyTooLong(_) => "reply_too_long",
+            Self::Runtime { .. } => "runtime_error",
+            Self::NotAllowed(_) => "query_not_allowed",
+            Self::InvalidQuery(_) => "invalid_query",
+            Self::Unavailable(_) => "bird_unavailable",
+            Self::Timeout(_) => "timeout",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Syntax { .. } | Self::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            Self::RouteNotFound(_) | Self::NoSuchProtocol(_) => StatusCode::NOT_FOUND,
+            Self::AccessDenied(_) | Self::NotAllowed(_) => StatusCode::FORBIDDEN,
+            Self::ReplyTooLong(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Runtime { .. } => StatusCode::BAD_GATEWAY,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    /// Whether the connection is still in a clean state after this error
+    pub fn is_reply(&self) -> bool {
+        self.code().is_some()
+    }
+}
+
+impl fmt::Display for BirdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { message, .. }
+            | Self::Runtime { message, .. }
+            | Self::RouteNotFound(message)
+            | Self::NoSuchProtocol(message)
+            | Self::AccessDenied(message)
+            | Self::ReplyTooLong(message)
+            | Self::NotAllowed(message)
+            | Self::InvalidQuery(message)
+            | Self::Unavailable(message) => write!(f, "{}", message),
+            Self::Timeout(timeout) => write!(f, "BIRD query timed out after {}s", timeout.as_secs()),
+        }
+    }
+}
+
+impl std::error::Error for BirdError {}
+
+impl From<std::io::Error> for BirdError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Unavailable(format!("BIRD socket error: {}", e))
+    }
+}
+
+impl IntoResponse for BirdError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "error": self.kind(),
+            "code": self.code(),
+            "message": self.to_string(),
+        });
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply_lines() {
+        let line = ReplyLine::parse(b"1007-10.0.0.0/8 unreachable [static1 2024-01-01] * (200)\n");
+        assert_eq!(line.code, Some(1007));
+        assert_eq!(line.text, b"10.0.0.0/8 unreachable [static1 2024-01-01] * (200)");
+        assert!(!line.is_final());
+
+        let line = ReplyLine::parse(b" \tvia 192.0.2.1 on eth0\n");
+        assert_eq!(line.code, None);
+        assert_eq!(line.text, b"\tvia 192.0.2.1 on eth0");
+
+        let line = ReplyLine::parse(b"0000 \n");
+        assert_eq!(line.code, Some(0));
+        assert!(line.is_final());
+
+        let line = ReplyLine::parse(b"9001 syntax error, unexpected CF_SYM_UNDEFINED\n");
+        assert!(line.is_final());
+        assert_eq!(line.text_lossy(), "syntax error, unexpected CF_SYM_UNDEFINED");
+    }
+
+    #[test]
+    fn test_error_mapping() {
+        let cases = [
+            (9001, "syntax_error", StatusCode::BAD_REQUEST),
+            (8001, "route_not_found", StatusCode::NOT_FOUND),
+            (8003, "no_such_protocol", StatusCode::NOT_FOUND),
+            (8007, "access_denied", StatusCode::FORBIDDEN),
+            (8000, "reply_too_long", StatusCode::PAYLOAD_TOO_LARGE),
+            (8005, "runtime_error", StatusCode::BAD_GATEWAY),
+        ];
+
+        for (code, kind, status) in cases {
+            let error = BirdError::from_reply(code, "message".to_string());
+            assert_eq!(error.kind(), kind);
+            assert_eq!(error.status(), status);
+            assert_eq!(error.code(), Some(code));
+            assert!(error.is_reply());
+        }
+    }
+}
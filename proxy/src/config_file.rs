@@ -0,0 +1,80 @@
+pub use bird_lg_common::config_file::{comma_separated_opt, watch, Loader};
+
+/// Environment variables kept from bird-lg-go, and the keys they set
+pub const ENV_ALIASES: &[(&str, &str)] = &[
+    ("ALLOWED_IPS", "allowed"),
+    ("BIRD_SOCKET", "bird"),
+    ("BIRDLG_PROXY_PORT", "listen"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use crate::Args;
+    use std::io::Write;
+
+    fn load_with(argv: &[&str], vars: &[(&str, &str)]) -> Result<Args> {
+        Loader::from_args::<Args>(argv, vars)?.with_env_aliases(ENV_ALIASES).load()
+    }
+
+    #[test]
+    fn test_file_env_and_cli_precedence() {
+        let path = std::env::temp_dir().join(format!("bird-lgproxy-rs-{}.yaml", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"allowed: [10.0.0.0/8, 192.0.2.1]\nbird: /run/bird/file.ctl\nbird_timeout: 10\nbird_restrict_cmds: false\n")
+            .unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let args = load_with(&["bird-lgproxy-rs", "--config", &path], &[]).unwrap();
+        assert_eq!(args.allowed, Some(vec!["10.0.0.0/8".to_string(), "192.0.2.1".to_string()]));
+        assert_eq!(args.bird, "/run/bird/file.ctl");
+        assert_eq!(args.bird_timeout, 10);
+        assert!(!args.bird_restrict_cmds);
+        assert_eq!(args.bird_pool_size, 4);
+
+        let vars = [("BIRD_SOCKET", "/run/bird/env.ctl"), ("BIRDLG_BIRD_TIMEOUT", "20")];
+        let args = load_with(&["bird-lgproxy-rs", "--config", &path, "--bird-timeout", "5"], &vars).unwrap();
+        assert_eq!(args.bird, "/run/bird/env.ctl");
+        assert_eq!(args.bird_timeout, 5);
+    }
+
+    #[test]
+    fn test_bird_lg_go_env_names() {
+        let vars = [("ALLOWED_IPS", "127.0.0.1,::1"), ("BIRDLG_PROXY_PORT", "9000"), ("BIRDLG_AUTH_TOKEN", "1e5")];
+        let args = load_with(&["bird-lgproxy-rs"], &vars).unwrap();
+        assert_eq!(args.allowed, Some(vec!["127.0.0.1".to_string(), "::1".to_string()]));
+        assert_eq!(args.listen, "9000");
+        assert_eq!(args.auth_token.as_deref(), Some("1e5"));
+    }
+
+    #[test]
+    fn test_token_table() {
+        let path = std::env::temp_dir().join(format!("bird-lgproxy-rs-{}-tokens.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+[[tokens]]
+name = "monitoring"
+token = "mon-secret"
+endpoints = ["/bird"]
+commands = ["show protocols"]
+allowed = ["192.0.2.0/24"]
+expires = "2027-01-01"
+
+[[tokens]]
+name = "frontend"
+token = "fe-secret"
+"#,
+        )
+        .unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let args = load_with(&["bird-lgproxy-rs", "--config", &path], &[]).unwrap();
+        assert_eq!(args.tokens.len(), 2);
+        assert_eq!(args.tokens[0].name, "monitoring");
+        assert_eq!(args.tokens[0].commands, Some(vec!["show protocols".to_string()]));
+        assert_eq!(args.tokens[0].expires.as_deref(), Some("2027-01-01"));
+        assert!(args.tokens[1].endpoints.is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use futures_util::future::join_all;
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{Name, RecordType};
+use hickory_resolver::TokioAsyncResolver;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::warn;
+
+static DNS_RESOLVER: OnceLock<TokioAsyncResolver> = OnceLock::new();
+
+/// Record types that can be looked up
+const SUPPORTED_TYPES: [RecordType; 6] = [
+    RecordType::A,
+    RecordType::AAAA,
+    RecordType::PTR,
+    RecordType::MX,
+    RecordType::TXT,
+    RecordType::NS,
+];
+
+/// Record types looked up for a name when no type is given
+const NAME_TYPES: [RecordType; 5] = [RecordType::A, RecordType::AAAA, RecordType::MX, RecordType::TXT, RecordType::NS];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DnsTarget {
+    Name(Name),
+    /// Looked up by its reverse name
+    Address(IpAddr),
+}
+
+/// One record of an answer, which may be a CNAME leading to the records asked for
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// Records of one type, or why there are none
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsAnswer {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub records: Vec<DnsRecord>,
+    /// `NXDOMAIN`, `NODATA`, or another failure
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsReply {
+    pub query: String,
+    pub answers: Vec<DnsAnswer>,
+}
+
+/// Whether `name` is a host name of letters, digits, `-` and `_`, with an
+/// optional trailing dot
+pub fn is_host_name(name: &str) -> bool {
+    let labels = name.strip_suffix('.').unwrap_or(name);
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    !labels.is_empty() && labels.len() <= 253 && labels.split('.').all(valid_label)
+}
+
+/// Parse an address or a host name
+fn parse_target(query: &str) -> Result<DnsTarget, String> {
+    let query = query.trim();
+    if let Ok(ip) = query.parse::<IpAddr>() {
+        return Ok(DnsTarget::Address(ip));
+    }
+
+    if !is_host_name(query) {
+        return Err(format!("Invalid name '{}': expected a host name or an IP address", query));
+    }
+    let labels = query.strip_suffix('.').unwrap_or(query);
+
+    // Fully qualified, so search domains are not tried
+    Name::from_ascii(format!("{}.", labels))
+        .map(DnsTarget::Name)
+        .map_err(|e| format!("Invalid name '{}': {}", query, e))
+}
+
+/// Record types to look up: the requested one, or every type that applies to the target
+fn record_types(target: &DnsTarget, requested: Option<&str>) -> Result<Vec<RecordType>, String> {
+    let Some(requested) = requested.map(str::trim).filter(|requested| !requested.is_empty()) else {
+        return Ok(match target {
+            DnsTarget::Name(_) => NAME_TYPES.to_vec(),
+            DnsTarget::Address(_) => vec![RecordType::PTR],
+        });
+    };
+
+    let record_type = RecordType::from_str(&requested.to_ascii_uppercase())
+        .ok()
+        .filter(|record_type| SUPPORTED_TYPES.contains(record_type))
+        .ok_or_else(|| format!("Unsupported record type '{}': expected A, AAAA, PTR, MX, TXT or NS", requested))?;
+
+    if matches!(target, DnsTarget::Address(_)) && record_type != RecordType::PTR {
+        return Err("Addresses can only be looked up with PTR".to_string());
+    }
+    Ok(vec![record_type])
+}
+
+pub fn describe_error(error: &ResolveError) -> String {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code: ResponseCode::NXDomain, .. } => "NXDOMAIN".to_string(),
+        ResolveErrorKind::NoRecordsFound { .. } => "NODATA".to_string(),
+        ResolveErrorKind::Timeout => "timed out".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+async fn lookup(resolver: &TokioAsyncResolver, target: &DnsTarget, record_type: RecordType) -> DnsAnswer {
+    let name = match target {
+        DnsTarget::Name(name) => name.clone(),
+        DnsTarget::Address(ip) => Name::from(*ip),
+    };
+
+    let (records, error) = match resolver.lookup(name, record_type).await {
+        Ok(lookup) => {
+            let records = lookup
+                .record_iter()
+                .filter_map(|record| {
+                    Some(DnsRecord {
+                        name: record.name().to_string(),
+                        record_type: record.record_type().to_string(),
+                        ttl: record.ttl(),
+                        data: record.data()?.to_string(),
+                    })
+                })
+                .collect();
+            (records, None)
+        }
+        Err(e) => (Vec::new(), Some(describe_error(&e))),
+    };
+
+    DnsAnswer {
+        record_type: record_type.to_string(),
+        records,
+        error,
+    }
+}
+
+/// Resolve `query` with the local resolver, for the requested record type or
+/// every type that applies. Invalid queries are rejected with a message.
+pub async fn resolve(query: &str, requested: Option<&str>) -> Result<DnsReply, String> {
+    let target = parse_target(query)?;
+    let record_types = record_types(&target, requested)?;
+    let resolver = resolver()?;
+
+    let answers = join_all(record_types.into_iter().map(|record_type| lookup(resolver, &target, record_type))).await;
+    Ok(DnsReply {
+        query: query.trim().to_string(),
+        answers,
+    })
+}
+
+/// The resolver used for lookups and traceroute targets
+pub fn resolver() -> Result<&'static TokioAsyncResolver, String> {
+    DNS_RESOLVER.get().ok_or_else(|| "DNS resolver not initialized".to_string())
+}
+
+/// Create the resolver from the system configuration
+pub fn init() {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+        warn!("Failed to read system DNS configuration, using defaults: {}", e);
+        TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+    });
+    DNS_RESOLVER.set(resolver).expect("DNS resolver already initialized");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> DnsTarget {
+        DnsTarget::Name(Name::from_ascii(name).unwrap())
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("example.com"), Ok(name("example.com.")));
+        assert_eq!(parse_target(" Example.COM. "), Ok(name("Example.COM.")));
+        assert_eq!(parse_target("_dmarc.example.com"), Ok(name("_dmarc.example.com.")));
+        assert_eq!(parse_target("2001:db8::1"), Ok(DnsTarget::Address("2001:db8::1".parse().unwrap())));
+        assert_eq!(parse_target("192.0.2.1"), Ok(DnsTarget::Address("192.0.2.1".parse().unwrap())));
+
+        assert!(parse_target("").is_err());
+        assert!(parse_target("example..com").is_err());
+        assert!(parse_target("-rf.example.com").is_err());
+        assert!(parse_target("example.com; ls").is_err());
+        assert!(parse_target(&format!("{}.com", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn test_record_types() {
+        let host = name("example.com.");
+        let address = DnsTarget::Address("192.0.2.1".parse().unwrap());
+
+        assert_eq!(record_types(&host, None), Ok(NAME_TYPES.to_vec()));
+        assert_eq!(record_types(&host, Some("")), Ok(NAME_TYPES.to_vec()));
+        assert_eq!(record_types(&host, Some("mx")), Ok(vec![RecordType::MX]));
+        assert_eq!(record_types(&host, Some("PTR")), Ok(vec![RecordType::PTR]));
+        assert_eq!(record_types(&address, None), Ok(vec![RecordType::PTR]));
+        assert_eq!(record_types(&address, Some("ptr")), Ok(vec![RecordType::PTR]));
+
+        assert!(record_types(&host, Some("AXFR")).is_err());
+        assert!(record_types(&host, Some("bogus")).is_err());
+        assert_eq!(record_types(&address, Some("A")), Err("Addresses can only be looked up with PTR".to_string()));
+    }
+}
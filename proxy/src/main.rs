@@ -1,12 +1,18 @@
 use axum::{
+    body::Body,
     extract::Query,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
     routing::get,
     Router,
 };
 use clap::Parser;
+use futures_util::StreamExt;
 use serde::Deserialize;
+use std::convert::Infallible;
 use std::net::SocketAddr;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
@@ -30,22 +36,64 @@ use hyper::{body::Incoming, Request};
 
 mod settings;
 mod bird;
+mod bird_reply;
+mod bird_command;
 mod traceroute;
+mod traceroute_parser;
+mod ping;
+mod dns;
+mod target;
 mod middleware;
-
+mod config_file;
+mod tls;
+mod signature;
+mod tokens;
+mod metrics;
+
+use bird_command::BirdCommand;
+use bird_reply::BirdError;
+use metrics::Metrics;
 use settings::Settings;
+use target::AddressFamily;
+use traceroute::TracerouteEvent;
 
-#[derive(Parser)]
+#[derive(Parser, Deserialize)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Configuration file (YAML or TOML)
+    #[arg(long)]
+    #[serde(skip)]
+    config: Option<String>,
+
     /// IPs or networks allowed to access this proxy, separated by commas
     #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
     allowed: Option<Vec<String>>,
 
     /// Socket file for bird
     #[arg(long, default_value = "/var/run/bird/bird.ctl")]
     bird: String,
 
+    /// Maximum number of pooled connections to the bird socket
+    #[arg(long, default_value_t = 4)]
+    bird_pool_size: usize,
+
+    /// Time before a bird query times out, in seconds
+    #[arg(long, default_value_t = 30)]
+    bird_timeout: u64,
+
+    /// Time before an idle pooled bird connection is closed, in seconds
+    #[arg(long, default_value_t = 60)]
+    bird_idle_timeout: u64,
+
+    /// Truncate bird output after this many lines
+    #[arg(long)]
+    bird_max_lines: Option<usize>,
+
+    /// Truncate bird output after this many bytes
+    #[arg(long)]
+    bird_max_bytes: Option<usize>,
+
     /// Listen address (TCP port or Unix socket path)
     #[arg(long, default_value = "8000")]
     listen: String,
@@ -66,10 +114,36 @@ struct Args {
     #[arg(long, default_value_t = 10)]
     traceroute_max_concurrent: usize,
 
-    /// Restrict Bird queries to show protocols and show route commands
+    /// Ping binary file
+    #[arg(long)]
+    ping_bin: Option<String>,
+
+    /// Ping flags, supports multiple flags separated with space
+    #[arg(long)]
+    ping_flags: Option<String>,
+
+    /// Whether to display ping outputs raw
+    #[arg(long)]
+    ping_raw: bool,
+
+    /// Maximum number of concurrent ping requests
+    #[arg(long, default_value_t = 10)]
+    ping_max_concurrent: usize,
+
+    /// IPs or networks traceroute and ping may not target, separated by commas (default private and local ranges)
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
+    denied_targets: Option<Vec<String>>,
+
+    /// Restrict Bird queries to the allowed show protocols and show route syntax
     #[arg(long, default_value_t = true)]
     bird_restrict_cmds: bool,
 
+    /// Extra Bird commands allowed with bird_restrict_cmds, like "show status", separated by commas
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
+    bird_extra_cmds: Option<Vec<String>>,
+
     /// Enable token-based authentication
     #[arg(long, default_value_t = false)]
     auth_enabled: bool,
@@ -77,6 +151,45 @@ struct Args {
     /// Authentication token for API access
     #[arg(long)]
     auth_token: Option<String>,
+
+    /// Scoped API tokens, set in the configuration file
+    #[arg(skip)]
+    #[serde(default)]
+    tokens: Vec<tokens::TokenConfig>,
+
+    /// How requests carry the token: bearer, or hmac for signed requests
+    #[arg(long, default_value = "bearer")]
+    auth_scheme: String,
+
+    /// Largest accepted age of signed requests, in seconds
+    #[arg(long, default_value_t = 30)]
+    auth_max_skew: u64,
+
+    /// Host names frontends sign requests for, separated by commas (default the request's Host header)
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
+    auth_hosts: Option<Vec<String>>,
+
+    /// IPs or networks allowed to read /metrics, separated by commas (default localhost)
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default, deserialize_with = "config_file::comma_separated_opt")]
+    metrics_allowed: Option<Vec<String>>,
+
+    /// Bearer token required to read /metrics
+    #[arg(long)]
+    metrics_token: Option<String>,
+
+    /// TLS certificate chain (PEM); serves HTTPS together with --tls-key
+    #[arg(long)]
+    tls_cert: Option<String>,
+
+    /// TLS private key (PEM)
+    #[arg(long)]
+    tls_key: Option<String>,
+
+    /// CA bundle (PEM) that client certificates must be signed by; requires a client certificate
+    #[arg(long)]
+    tls_client_ca: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -87,6 +200,22 @@ struct BirdQuery {
 #[derive(Deserialize)]
 struct TracerouteQuery {
     q: String,
+    /// `text` (the default), `json`, or `stream` for server-sent events
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PingQuery {
+    q: String,
+}
+
+#[derive(Deserialize)]
+struct DnsQuery {
+    q: String,
+    /// Record type; every type that applies to the name when unset
+    #[serde(default, rename = "type")]
+    record_type: Option<String>,
 }
 
 // Default handler, returns project info
@@ -104,43 +233,128 @@ async fn bird_handler(
     Query(params): Query<BirdQuery>,
 ) -> Result<impl IntoResponse, Response> {
     if params.q.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Query parameter 'q' is required").into_response());
+        return Err(BirdError::InvalidQuery("Query parameter 'q' is required".to_string()).into_response());
     }
 
+    // Only pass on commands from the allowed grammar, in their canonical form
     let settings = Settings::global();
-    if settings.bird_restrict_cmds {
-        let query_lower = params.q.to_lowercase();
-        if !query_lower.starts_with("show protocols") && !query_lower.starts_with("show route") {
-            return Err((StatusCode::BAD_REQUEST, "Query not allowed. Only 'show protocols' and 'show route' commands are permitted.").into_response());
+    let command = if settings.bird_restrict_cmds {
+        match BirdCommand::parse(&params.q, &settings.bird_extra_cmds) {
+            Ok(command) => command.to_string(),
+            Err(reason) => {
+                return Err(BirdError::NotAllowed(format!("Query not allowed: {}", reason)).into_response());
+            }
         }
-    }
+    } else {
+        params.q
+    };
 
-    match bird::execute_bird_command(&params.q).await {
-        Ok(output) => Ok(output),
+    match bird::execute_bird_command(&command).await {
+        Ok(reply) => Ok((
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            Body::from_stream(reply.into_body_stream()),
+        )),
         Err(e) => {
-            warn!("Bird command failed: {}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())
+            warn!("Bird command failed: {} ({})", e, e.kind());
+            Metrics::global().bird_command_error(e.kind());
+            Err(e.into_response())
         }
     }
 }
 
 // Handles traceroute queries
-async fn traceroute_handler(
-    Query(params): Query<TracerouteQuery>,
-) -> Result<impl IntoResponse, Response> {
+async fn traceroute_handler(Query(params): Query<TracerouteQuery>) -> Result<Response, Response> {
+    traceroute_query(params, AddressFamily::Any).await
+}
+
+// Handles traceroute queries over IPv6
+async fn traceroute6_handler(Query(params): Query<TracerouteQuery>) -> Result<Response, Response> {
+    traceroute_query(params, AddressFamily::Ipv6).await
+}
+
+async fn traceroute_query(params: TracerouteQuery, family: AddressFamily) -> Result<Response, Response> {
+    if params.q.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Query parameter 'q' is required").into_response());
+    }
+
+    let result = match params.format.as_deref() {
+        None | Some("text") => traceroute::execute_traceroute(&params.q, family).await.map(IntoResponse::into_response),
+        Some("json") => traceroute::execute_traceroute_report(&params.q, family).await.map(|report| Json(report).into_response()),
+        Some("stream") => traceroute::stream_traceroute(&params.q, family).await.map(|events| {
+            Sse::new(events.map(traceroute_sse_event)).keep_alive(KeepAlive::default()).into_response()
+        }),
+        Some(format) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown format '{}': expected text, json or stream", format)).into_response());
+        }
+    };
+
+    result.map_err(|e| {
+        warn!("Traceroute command failed: {}", e);
+        (target::error_status(&e), e.to_string()).into_response()
+    })
+}
+
+// One streamed traceroute event: `line`, then `report` or `error`
+fn traceroute_sse_event(event: TracerouteEvent) -> Result<Event, Infallible> {
+    Ok(match event {
+        TracerouteEvent::Line(line) => Event::default().event("line").data(line),
+        TracerouteEvent::Report(report) => Event::default()
+            .event("report")
+            .json_data(report)
+            .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())),
+        TracerouteEvent::Error(e) => {
+            warn!("Traceroute command failed: {}", e);
+            Event::default().event("error").data(e)
+        }
+    })
+}
+
+// Handles ping queries
+async fn ping_handler(Query(params): Query<PingQuery>) -> Result<String, Response> {
+    ping_query(params, AddressFamily::Any).await
+}
+
+// Handles ping queries over IPv6
+async fn ping6_handler(Query(params): Query<PingQuery>) -> Result<String, Response> {
+    ping_query(params, AddressFamily::Ipv6).await
+}
+
+async fn ping_query(params: PingQuery, family: AddressFamily) -> Result<String, Response> {
     if params.q.trim().is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Query parameter 'q' is required").into_response());
     }
 
-    match traceroute::execute_traceroute(&params.q).await {
+    match ping::execute_ping(&params.q, family).await {
         Ok(output) => Ok(output),
         Err(e) => {
-            warn!("Traceroute command failed: {}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())
+            warn!("Ping command failed: {}", e);
+            Err((target::error_status(&e), e.to_string()).into_response())
         }
     }
 }
 
+// Handles DNS lookups with the local resolver
+async fn dns_handler(
+    Query(params): Query<DnsQuery>,
+) -> Result<impl IntoResponse, Response> {
+    if params.q.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Query parameter 'q' is required").into_response());
+    }
+
+    match dns::resolve(&params.q, params.record_type.as_deref()).await {
+        Ok(reply) => Ok(Json(reply)),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e).into_response()),
+    }
+}
+
+// Prometheus metrics of the proxy itself
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        Metrics::global().render(traceroute::slots()),
+    )
+}
+
 /// Create Unix socket listener on Unix systems
 #[cfg(unix)]
 async fn create_unix_listener(socket_path: &str) -> anyhow::Result<()> {
@@ -190,13 +404,22 @@ async fn build_router() -> Router {
         .route("/bird", get(bird_handler))
         .route("/bird6", get(bird_handler))
         .route("/traceroute", get(traceroute_handler))
-        .route("/traceroute6", get(traceroute_handler))
+        .route("/traceroute6", get(traceroute6_handler))
+        .route("/ping", get(ping_handler))
+        .route("/ping6", get(ping6_handler))
+        .route("/dns", get(dns_handler))
         .fallback(invalid_handler)
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
+                .layer(axum::middleware::from_fn(middleware::track_requests))
                 .layer(axum::middleware::from_fn(middleware::access_control))
         )
+        // Added after the API layers, so only its own access control applies
+        .route(
+            "/metrics",
+            get(metrics_handler).layer(axum::middleware::from_fn(middleware::metrics_access)),
+        )
 }
 
 #[tokio::main]
@@ -204,19 +427,31 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "bird_lgproxy_rs=info".into()),
+                .unwrap_or_else(|_| "bird_lgproxy_rs=info,bird_lg_common=info".into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let args = Args::parse();
+    let loader = config_file::Loader::from_env::<Args>().with_env_aliases(config_file::ENV_ALIASES);
     
     // Initialize settings
-    Settings::init(args).await?;
+    Settings::init(loader.load()?).await?;
+
+    // Initialize BIRD connection pool
+    bird::init();
 
     // Initialize traceroute
     traceroute::init().await;
 
+    // Initialize ping
+    ping::init().await;
+
+    // Initialize DNS resolver
+    dns::init();
+
+    // Reload settings on SIGHUP or when the configuration file changes
+    config_file::watch(loader, |loader| Settings::reload(loader.load()?));
+
     let settings = Settings::global();
     info!("Listening on {}...", settings.listen);
 
@@ -224,6 +459,9 @@ async fn main() -> anyhow::Result<()> {
     #[cfg(unix)]
     if settings.listen.starts_with('/') {
         // Unix socket on Unix systems
+        if settings.tls.is_some() {
+            warn!("TLS is not used on Unix sockets");
+        }
         return create_unix_listener(&settings.listen).await;
     }
     
@@ -235,6 +473,12 @@ async fn main() -> anyhow::Result<()> {
     };
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    if let Some(files) = settings.tls.clone() {
+        info!("Server started on TLS: {}", addr);
+        return tls::serve(listener, build_router().await, files).await;
+    }
+
     info!("Server started on TCP: {}", addr);
     
     let app = build_router().await
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+/// Upper bounds of the request latency histogram buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len()];
+        }
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Proxy health counters, exported on `/metrics` in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    /// Responses by endpoint and status code
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    /// Time to response headers by endpoint
+    latencies: Mutex<BTreeMap<String, Histogram>>,
+    bird_connect_errors: AtomicU64,
+    /// Failed BIRD queries by error kind
+    bird_command_errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// Requests refused for missing or invalid credentials, by reason
+    auth_rejected: Mutex<BTreeMap<&'static str, u64>>,
+    /// Requests from addresses outside `allowed`
+    forbidden_ip: AtomicU64,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Metrics {
+    pub fn global() -> &'static Metrics {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        METRICS.get_or_init(Metrics::default)
+    }
+
+    pub fn observe_request(&self, endpoint: &str, status: u16, elapsed: Duration) {
+        *lock(&self.requests).entry((endpoint.to_string(), status)).or_default() += 1;
+        lock(&self.latencies)
+            .entry(endpoint.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn bird_connect_error(&self) {
+        self.bird_connect_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bird_command_error(&self, kind: &'static str) {
+        *lock(&self.bird_command_errors).entry(kind).or_default() += 1;
+    }
+
+    pub fn auth_rejected(&self, reason: &'static str) {
+        *lock(&self.auth_rejected).entry(reason).or_default() += 1;
+    }
+
+    pub fn forbidden_ip(&self) {
+        self.forbidden_ip.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Text exposition of all metrics, with traceroute slots `(in use, capacity)`
+    /// when traceroute is initialized
+    pub fn render(&self, traceroute_slots: Option<(usize, usize)>) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "birdlg_proxy_requests_total", "counter", "Requests handled, by endpoint and status code");
+        for ((endpoint, status), count) in lock(&self.requests).iter() {
+            let _ = writeln!(out, "birdlg_proxy_requests_total{{endpoint=\"{}\",status=\"{}\"}} {}", endpoint, status, count);
+        }
+
+        header(
+            &mut out,
+            "birdlg_proxy_request_duration_seconds",
+            "histogram",
+            "Time until response headers are sent, by endpoint",
+        );
+        for (endpoint, histogram) in lock(&self.latencies).iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "birdlg_proxy_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "birdlg_proxy_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+                endpoint, histogram.count
+            );
+            let _ = writeln!(out, "birdlg_proxy_request_duration_seconds_sum{{endpoint=\"{}\"}} {}", endpoint, histogram.sum);
+            let _ = writeln!(out, "birdlg_proxy_request_duration_seconds_count{{endpoint=\"{}\"}} {}", endpoint, histogram.count);
+        }
+
+        header(&mut out, "birdlg_proxy_bird_connect_errors_total", "counter", "Failed connections to the BIRD control socket");
+        let _ = writeln!(out, "birdlg_proxy_bird_connect_errors_total {}", self.bird_connect_errors.load(Ordering::Relaxed));
+
+        header(&mut out, "birdlg_proxy_bird_command_errors_total", "counter", "Failed BIRD queries, by error kind");
+        for (kind, count) in lock(&self.bird_command_errors).iter() {
+            let _ = writeln!(out, "birdlg_proxy_bird_command_errors_total{{kind=\"{}\"}} {}", kind, count);
+        }
+
+        if let Some((in_use, capacity)) = traceroute_slots {
+            header(&mut out, "birdlg_proxy_traceroute_in_flight", "gauge", "Traceroutes currently running");
+            let _ = writeln!(out, "birdlg_proxy_traceroute_in_flight {}", in_use);
+            header(&mut out, "birdlg_proxy_traceroute_capacity", "gauge", "Traceroutes allowed to run at the same time");
+            let _ = writeln!(out, "birdlg_proxy_traceroute_capacity {}", capacity);
+        }
+
+        header(&mut out, "birdlg_proxy_auth_rejected_total", "counter", "Requests refused by token authentication, by reason");
+        for (reason, count) in lock(&self.auth_rejected).iter() {
+            let _ = writeln!(out, "birdlg_proxy_auth_rejected_total{{reason=\"{}\"}} {}", reason, count);
+        }
+
+        header(&mut out, "birdlg_proxy_forbidden_ip_total", "counter", "Requests refused because of their source address");
+        let _ = writeln!(out, "birdlg_proxy_forbidden_ip_total {}", self.forbidden_ip.load(Ordering::Relaxed));
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_histograms() {
+        let metrics = Metrics::default();
+        metrics.observe_request("/bird", 200, Duration::from_millis(20));
+        metrics.observe_request("/bird", 200, Duration::from_millis(300));
+        metrics.observe_request("/bird", 403, Duration::from_millis(1));
+        metrics.bird_connect_error();
+        metrics.bird_command_error("bird_unavailable");
+        metrics.auth_rejected("invalid_token");
+        metrics.auth_rejected("invalid_token");
+        metrics.forbidden_ip();
+
+        let text = metrics.render(Some((2, 10)));
+        assert!(text.contains("birdlg_proxy_requests_total{endpoint=\"/bird\",status=\"200\"} 2\n"));
+        assert!(text.contains("birdlg_proxy_requests_total{endpoint=\"/bird\",status=\"403\"} 1\n"));
+        assert!(text.contains("birdlg_proxy_request_duration_seconds_bucket{endpoint=\"/bird\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("birdlg_proxy_request_duration_seconds_bucket{endpoint=\"/bird\",le=\"0.025\"} 2\n"));
+        assert!(text.contains("birdlg_proxy_request_duration_seconds_bucket{endpoint=\"/bird\",le=\"0.5\"} 3\n"));
+        assert!(text.contains("birdlg_proxy_request_duration_seconds_bucket{endpoint=\"/bird\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("birdlg_proxy_request_duration_seconds_count{endpoint=\"/bird\"} 3\n"));
+        assert!(text.contains("birdlg_proxy_bird_connect_errors_total 1\n"));
+        assert!(text.contains("birdlg_proxy_bird_command_errors_total{kind=\"bird_unavailable\"} 1\n"));
+        assert!(text.contains("birdlg_proxy_traceroute_in_flight 2\n"));
+        assert!(text.contains("birdlg_proxy_traceroute_capacity 10\n"));
+        assert!(text.contains("birdlg_proxy_auth_rejected_total{reason=\"invalid_token\"} 2\n"));
+        assert!(text.contains("birdlg_proxy_forbidden_ip_total 1\n"));
+        assert!(text.contains("# TYPE birdlg_proxy_request_duration_seconds histogram\n"));
+    }
+
+    #[test]
+    fn test_traceroute_gauges_only_when_initialized() {
+        let text = Metrics::default().render(None);
+        assert!(!text.contains("birdlg_proxy_traceroute"));
+        assert!(text.contains("birdlg_proxy_forbidden_ip_total 0\n"));
+    }
+}
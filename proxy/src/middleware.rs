@@ -1,12 +1,19 @@
 use axum::{
-    extract::{Request, ConnectInfo},
+    extract::{Request, ConnectInfo, MatchedPath, Query},
     http::{StatusCode, header::AUTHORIZATION},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use chrono::Utc;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Instant;
 use tracing::debug;
+use crate::metrics::Metrics;
 use crate::settings::Settings;
+use crate::signature::{self, AuthScheme, NonceCache};
+use crate::tokens::Denial;
+use subtle::ConstantTimeEq;
 
 pub async fn access_control(
     request: Request,
@@ -19,35 +26,114 @@ pub async fn access_control(
         let remote_ip = addr.ip().to_string();
         debug!("Request IP: {}", remote_ip);
         if !settings.has_access(&remote_ip) {
+            Metrics::global().forbidden_ip();
             return Err((StatusCode::FORBIDDEN, "403 Forbidden\n").into_response());
         }
     }
 
     // Check token authentication if enabled
     if settings.auth_enabled {
-        let auth_header = request.headers().get(AUTHORIZATION);
-        match auth_header {
-            Some(header_value) => {
-                let header_str = header_value.to_str().unwrap_or("");
-                if !header_str.starts_with("Bearer ") {
-                    debug!("Invalid authorization header format");
+        if settings.tokens.is_empty() {
+            debug!("Auth enabled but no token configured");
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "500 Internal Server Error\n").into_response());
+        }
+
+        let token = match settings.auth_scheme {
+            AuthScheme::Bearer => {
+                let provided = request
+                    .headers()
+                    .get(AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "));
+                let Some(provided) = provided else {
+                    debug!("Authorization header missing or not a Bearer token");
+                    Metrics::global().auth_rejected("missing_token");
                     return Err((StatusCode::UNAUTHORIZED, "401 Unauthorized\n").into_response());
-                }
-                let token = &header_str[7..]; // Skip "Bearer "
-                if let Some(expected_token) = &settings.auth_token {
-                    if token != expected_token {
+                };
+                match settings.tokens.iter().find(|token| bool::from(provided.as_bytes().ct_eq(token.secret.as_bytes()))) {
+                    Some(token) => token,
+                    None => {
                         debug!("Invalid token provided");
+                        Metrics::global().auth_rejected("invalid_token");
                         return Err((StatusCode::UNAUTHORIZED, "401 Unauthorized\n").into_response());
                     }
-                } else {
-                    debug!("Auth enabled but no token configured");
-                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "500 Internal Server Error\n").into_response());
                 }
             }
-            None => {
-                debug!("Authorization header missing");
-                return Err((StatusCode::UNAUTHORIZED, "401 Unauthorized\n").into_response());
+            AuthScheme::Hmac => {
+                let secrets: Vec<&str> = settings.tokens.iter().map(|token| token.secret.as_str()).collect();
+                let verified = signature::verify(
+                    &secrets,
+                    &settings.auth_hosts,
+                    settings.auth_max_skew,
+                    signature::unix_time(),
+                    &request,
+                    NonceCache::global(),
+                );
+                match verified {
+                    Ok(index) => &settings.tokens[index],
+                    Err(rejection) => {
+                        debug!("Request signature rejected: {:?}", rejection);
+                        Metrics::global().auth_rejected(rejection.reason());
+                        return Err((StatusCode::UNAUTHORIZED, "401 Unauthorized\n").into_response());
+                    }
+                }
             }
+        };
+
+        let command = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(mut params)| params.remove("q"));
+        let remote_ip = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+        if let Err(denial) = token.permits(request.uri().path(), command.as_deref(), remote_ip, Utc::now()) {
+            debug!("Token '{}' denied: {:?}", token.name, denial);
+            Metrics::global().auth_rejected(denial.reason());
+            let status = match denial {
+                Denial::Expired => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::FORBIDDEN,
+            };
+            return Err((status, format!("{}\n", status)).into_response());
+        }
+        debug!("Request authorized by token '{}'", token.name);
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Count requests and time them until the response headers are ready
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "other".to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    Metrics::global().observe_request(&endpoint, response.status().as_u16(), started.elapsed());
+    response
+}
+
+// Access control of /metrics, separate from the API: metrics_allowed and metrics_token
+pub async fn metrics_access(request: Request, next: Next) -> Result<Response, Response> {
+    let settings = Settings::global();
+
+    if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        if !settings.metrics_allowed.iter().any(|net| net.contains(&addr.ip())) {
+            debug!("Metrics request from {} refused", addr.ip());
+            return Err((StatusCode::FORBIDDEN, "403 Forbidden\n").into_response());
+        }
+    }
+
+    if let Some(expected_token) = &settings.metrics_token {
+        let provided = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !bool::from(provided.as_bytes().ct_eq(expected_token.as_bytes())) {
+            debug!("Invalid or missing metrics token");
+            return Err((StatusCode::UNAUTHORIZED, "401 Unauthorized\n").into_response());
         }
     }
 
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use std::sync::OnceLock;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+use crate::settings::Settings;
+use crate::target::{self, AddressFamily};
+use crate::traceroute;
+
+#[derive(Debug, Clone)]
+struct PingConfig {
+    bin: String,
+    flags: Vec<String>,
+}
+
+static PING_CONFIG: OnceLock<Option<PingConfig>> = OnceLock::new();
+static PING_SEMAPHORE: OnceLock<Semaphore> = OnceLock::new();
+
+/// Flag sets tried in order; each one must limit the number of packets
+fn candidate_flags() -> Vec<Vec<String>> {
+    vec![
+        // Linux iputils, allowed for unprivileged users
+        vec!["-c4".to_string(), "-i0.2".to_string(), "-W1".to_string()],
+        vec!["-c4".to_string(), "-W1".to_string()],
+        // Busybox and BSD
+        vec!["-c4".to_string()],
+    ]
+}
+
+/// Test if a ping configuration works
+async fn ping_detect(cmd: &str, args: &[String]) -> bool {
+    let target = vec!["127.0.0.1".to_string()];
+
+    match traceroute::try_execute(cmd, args, &target).await {
+        Ok(_) => {
+            info!("Ping autodetect success: {}", traceroute::args_to_string(cmd, args, &target));
+            true
+        }
+        Err(e) => {
+            info!("Ping autodetect fail, continuing: {} ({})",
+                  traceroute::args_to_string(cmd, args, &target), e);
+            false
+        }
+    }
+}
+
+/// Auto-detect a working ping configuration
+pub async fn init() {
+    let settings = Settings::global();
+
+    PING_SEMAPHORE
+        .set(Semaphore::new(settings.ping_max_concurrent))
+        .expect("Semaphore already initialized");
+
+    // If both bin and flags are set, use them directly
+    if let Some(bin) = settings.ping_bin.as_ref().filter(|_| !settings.ping_flags.is_empty()) {
+        let config = PingConfig {
+            bin: bin.clone(),
+            flags: settings.ping_flags.clone(),
+        };
+        PING_CONFIG.set(Some(config)).expect("Config already initialized");
+        return;
+    }
+
+    let bin = settings.ping_bin.clone().unwrap_or_else(|| "ping".to_string());
+    let mut detected_config = None;
+    for flags in candidate_flags() {
+        if ping_detect(&bin, &flags).await {
+            detected_config = Some(PingConfig { bin, flags });
+            break;
+        }
+    }
+
+    if detected_config.is_none() {
+        warn!("Ping autodetect failed! Ping will be disabled");
+    }
+
+    PING_CONFIG.set(detected_config).expect("Config already initialized");
+}
+
+/// Keep the first line and the statistics of ping output, dropping the
+/// line of every reply
+fn summarize(output: &str) -> String {
+    let lines: Vec<&str> = output.trim().lines().collect();
+    let Some(statistics) = lines.iter().position(|line| line.starts_with("---") && line.contains("statistics")) else {
+        return output.trim().to_string();
+    };
+
+    let mut summary = vec![lines[0], ""];
+    summary.extend_from_slice(&lines[statistics..]);
+    summary.join("\n")
+}
+
+/// Execute ping command
+pub async fn execute_ping(query: &str, family: AddressFamily) -> Result<String> {
+    let settings = Settings::global();
+
+    let config = PING_CONFIG
+        .get()
+        .ok_or_else(|| anyhow!("Ping not initialized"))?
+        .as_ref()
+        .ok_or_else(|| anyhow!("Ping not supported on this node"))?;
+
+    let target = target::resolve(query, family).await?;
+
+    let semaphore = PING_SEMAPHORE
+        .get()
+        .ok_or_else(|| anyhow!("Ping semaphore not initialized"))?;
+
+    // Acquire semaphore permit to limit concurrent requests
+    let _permit = semaphore.acquire().await
+        .map_err(|e| anyhow!("Failed to acquire ping semaphore: {}", e))?;
+
+    let mut command = Command::new(&config.bin);
+    command.args(&config.flags);
+    command.arg(target.to_string());
+
+    let output = command.output().await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // ping exits with 1 when no reply came back, which is still a result
+    if output.status.success() || (output.status.code() == Some(1) && !stdout.trim().is_empty()) {
+        if settings.ping_raw {
+            Ok(stdout.to_string())
+        } else {
+            Ok(summarize(&stdout))
+        }
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.trim().is_empty() {
+            Err(anyhow!("Error executing ping: command failed with status: {}", output.status))
+        } else {
+            Err(anyhow!("Error executing ping: {}", stderr.trim()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPUTILS: &str = "PING 192.0.2.1 (192.0.2.1) 56(84) bytes of data.
+64 bytes from 192.0.2.1: icmp_seq=1 ttl=57 time=11.2 ms
+64 bytes from 192.0.2.1: icmp_seq=2 ttl=57 time=11.0 ms
+
+--- 192.0.2.1 ping statistics ---
+2 packets transmitted, 2 received, 0% packet loss, time 201ms
+rtt min/avg/max/mdev = 11.012/11.106/11.200/0.094 ms
+";
+
+    const BUSYBOX_LOSS: &str = "PING 192.0.2.1 (192.0.2.1): 56 data bytes
+
+--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 0 packets received, 100% packet loss
+";
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(
+            summarize(IPUTILS),
+            "PING 192.0.2.1 (192.0.2.1) 56(84) bytes of data.
+
+--- 192.0.2.1 ping statistics ---
+2 packets transmitted, 2 received, 0% packet loss, time 201ms
+rtt min/avg/max/mdev = 11.012/11.106/11.200/0.094 ms"
+        );
+        assert_eq!(
+            summarize(BUSYBOX_LOSS),
+            "PING 192.0.2.1 (192.0.2.1): 56 data bytes
+
+--- 192.0.2.1 ping statistics ---
+4 packets transmitted, 0 packets received, 100% packet loss"
+        );
+        // Output without statistics is kept as it is
+        assert_eq!(summarize("ping: sendmsg: Network is unreachable\n"), "ping: sendmsg: Network is unreachable");
+    }
+}
@@ -1,43 +1,117 @@
 use crate::Args;
-use anyhow::Result;
+use crate::signature::AuthScheme;
+use crate::tls::TlsFiles;
+use crate::tokens::ApiToken;
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use ipnet::IpNet;
 use std::net::IpAddr;
-use std::sync::OnceLock;
-use tracing::{info, debug};
+use std::sync::{Arc, OnceLock};
+use tracing::{info, debug, warn};
+
+/// Networks allowed to read /metrics unless `metrics_allowed` is set
+const DEFAULT_METRICS_ALLOWED: [&str; 2] = ["127.0.0.0/8", "::1/128"];
+
+/// Networks traceroute and ping may not target unless `denied_targets` is set:
+/// unspecified, loopback, RFC 1918, link-local and unique local addresses
+const DEFAULT_DENIED_TARGETS: [&str; 10] = [
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+];
 
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub bird_socket: String,
+    pub bird_pool_size: usize,
+    pub bird_timeout: u64,
+    pub bird_idle_timeout: u64,
+    pub bird_max_lines: Option<usize>,
+    pub bird_max_bytes: Option<usize>,
     pub listen: String,
     pub allowed_nets: Vec<IpNet>,
     pub traceroute_bin: Option<String>,
     pub traceroute_flags: Vec<String>,
     pub traceroute_raw: bool,
     pub traceroute_max_concurrent: usize,
+    pub ping_bin: Option<String>,
+    pub ping_flags: Vec<String>,
+    pub ping_raw: bool,
+    pub ping_max_concurrent: usize,
+    /// Networks traceroute and ping may not target
+    pub denied_targets: Vec<IpNet>,
     pub bird_restrict_cmds: bool,
+    /// Commands allowed besides show protocols and show route
+    pub bird_extra_cmds: Vec<String>,
     pub auth_enabled: bool,
-    pub auth_token: Option<String>,
+    /// Accepted API tokens, including `auth_token`
+    pub tokens: Vec<ApiToken>,
+    pub auth_scheme: AuthScheme,
+    /// Largest accepted clock difference of signed requests, in seconds
+    pub auth_max_skew: u64,
+    /// Host names signed requests may be addressed to; empty takes the Host header
+    pub auth_hosts: Vec<String>,
+    pub tls: Option<TlsFiles>,
+    /// Networks allowed to read /metrics
+    pub metrics_allowed: Vec<IpNet>,
+    pub metrics_token: Option<String>,
 }
 
-static SETTINGS: OnceLock<Settings> = OnceLock::new();
+static SETTINGS: OnceLock<ArcSwap<Settings>> = OnceLock::new();
 
 impl Settings {
     pub async fn init(args: Args) -> Result<()> {
-        let mut allowed_nets = Vec::new();
-
-        // Parse allowed IPs/networks
-        if let Some(allowed) = args.allowed {
-            for addr_str in allowed {
-                if let Ok(ip) = addr_str.parse::<IpAddr>() {
-                    // Single IP address - convert to /32 or /128 network
-                    allowed_nets.push(IpNet::from(ip));
-                } else if let Ok(net) = addr_str.parse::<IpNet>() {
-                    // Network range
-                    allowed_nets.push(net);
-                } else {
-                    anyhow::bail!("Invalid IP address or network: {}", addr_str);
-                }
-            }
+        let settings = Self::from_args(args)?;
+        info!("Settings initialized: {:?}", settings);
+
+        SETTINGS.set(ArcSwap::from_pointee(settings)).map_err(|_| anyhow::anyhow!("Settings already initialized"))?;
+        Ok(())
+    }
+
+    /// Replace the running settings. Invalid `args` leave them untouched.
+    pub fn reload(args: Args) -> Result<()> {
+        let settings = Self::from_args(args)?;
+        let handle = SETTINGS.get().context("Settings not initialized")?;
+
+        let restart_required = handle.load().restart_required(&settings);
+        if !restart_required.is_empty() {
+            warn!("Changes to {} only apply after a restart", restart_required.join(", "));
+        }
+
+        info!("Settings reloaded: {:?}", settings);
+        handle.store(Arc::new(settings));
+        Ok(())
+    }
+
+    fn from_args(args: Args) -> Result<Settings> {
+        let allowed_nets = parse_nets(args.allowed.as_deref().unwrap_or_default())?;
+        let metrics_allowed = match &args.metrics_allowed {
+            Some(allowed) => parse_nets(allowed)?,
+            None => parse_nets(&DEFAULT_METRICS_ALLOWED)?,
+        };
+        // An empty list allows every target
+        let denied_targets = match &args.denied_targets {
+            Some(denied) => parse_nets(&denied.iter().filter(|net| !net.trim().is_empty()).collect::<Vec<_>>())?,
+            None => parse_nets(&DEFAULT_DENIED_TARGETS)?,
+        };
+
+        // The token table, plus auth_token with access to everything.
+        // Configuring a token table turns authentication on.
+        let auth_enabled = args.auth_enabled || !args.tokens.is_empty();
+        let mut tokens = args
+            .tokens
+            .into_iter()
+            .map(ApiToken::from_config)
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(token) = &args.auth_token {
+            tokens.push(ApiToken::unrestricted(token.clone()));
         }
 
         // Parse traceroute flags
@@ -47,27 +121,88 @@ impl Settings {
             Vec::new()
         };
 
+        let ping_flags = args
+            .ping_flags
+            .map(|flags| shlex::split(&flags).unwrap_or_default())
+            .unwrap_or_default();
+
+        let tls = match (args.tls_cert, args.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsFiles { cert, key, client_ca: args.tls_client_ca }),
+            (None, None) if args.tls_client_ca.is_some() => anyhow::bail!("tls_client_ca requires tls_cert and tls_key"),
+            (None, None) => None,
+            _ => anyhow::bail!("tls_cert and tls_key must be set together"),
+        };
+        // Catch unreadable or mismatched files before they replace working ones
+        if let Some(files) = &tls {
+            files.load()?;
+        }
+
         let settings = Settings {
             bird_socket: args.bird,
+            bird_pool_size: args.bird_pool_size,
+            bird_timeout: args.bird_timeout,
+            bird_idle_timeout: args.bird_idle_timeout,
+            bird_max_lines: args.bird_max_lines,
+            bird_max_bytes: args.bird_max_bytes,
             listen: args.listen,
             allowed_nets,
             traceroute_bin: args.traceroute_bin,
             traceroute_flags,
             traceroute_raw: args.traceroute_raw,
             traceroute_max_concurrent: args.traceroute_max_concurrent,
+            ping_bin: args.ping_bin,
+            ping_flags,
+            ping_raw: args.ping_raw,
+            ping_max_concurrent: args.ping_max_concurrent,
+            denied_targets,
             bird_restrict_cmds: args.bird_restrict_cmds,
-            auth_enabled: args.auth_enabled,
-            auth_token: args.auth_token,
+            bird_extra_cmds: args.bird_extra_cmds.unwrap_or_default(),
+            auth_enabled,
+            tokens,
+            auth_scheme: AuthScheme::parse(&args.auth_scheme)?,
+            auth_max_skew: args.auth_max_skew,
+            auth_hosts: args.auth_hosts.unwrap_or_default(),
+            tls,
+            metrics_allowed,
+            metrics_token: args.metrics_token,
         };
 
-        info!("Settings initialized: {:?}", settings);
+        Ok(settings)
+    }
 
-        SETTINGS.set(settings).map_err(|_| anyhow::anyhow!("Settings already initialized"))?;
-        Ok(())
+    /// Settings only read at startup that differ in `new`
+    fn restart_required(&self, new: &Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.listen != new.listen {
+            changed.push("listen");
+        }
+        if self.bird_socket != new.bird_socket
+            || self.bird_pool_size != new.bird_pool_size
+            || self.bird_timeout != new.bird_timeout
+            || self.bird_idle_timeout != new.bird_idle_timeout
+        {
+            changed.push("bird connection pool");
+        }
+        if self.traceroute_bin != new.traceroute_bin
+            || self.traceroute_flags != new.traceroute_flags
+            || self.traceroute_max_concurrent != new.traceroute_max_concurrent
+        {
+            changed.push("traceroute_bin/traceroute_flags/traceroute_max_concurrent");
+        }
+        if self.ping_bin != new.ping_bin
+            || self.ping_flags != new.ping_flags
+            || self.ping_max_concurrent != new.ping_max_concurrent
+        {
+            changed.push("ping_bin/ping_flags/ping_max_concurrent");
+        }
+        if self.tls != new.tls {
+            changed.push("tls_cert/tls_key/tls_client_ca");
+        }
+        changed
     }
 
-    pub fn global() -> &'static Settings {
-        SETTINGS.get().expect("Settings not initialized")
+    pub fn global() -> Arc<Settings> {
+        SETTINGS.get().expect("Settings not initialized").load_full()
     }
 
     pub fn has_access(&self, remote_addr: &str) -> bool {
@@ -97,4 +232,20 @@ impl Settings {
 
         false
     }
-} 
+}
+
+/// Parse IPs and networks, single IPs becoming /32 or /128 networks
+pub fn parse_nets<S: AsRef<str>>(list: &[S]) -> Result<Vec<IpNet>> {
+    list.iter()
+        .map(|addr_str| {
+            let addr_str = addr_str.as_ref();
+            if let Ok(ip) = addr_str.parse::<IpAddr>() {
+                Ok(IpNet::from(ip))
+            } else if let Ok(net) = addr_str.parse::<IpNet>() {
+                Ok(net)
+            } else {
+                anyhow::bail!("Invalid IP address or network: {}", addr_str)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,224 @@
+use axum::extract::Request;
+use axum::http::header::HOST;
+use bird_lg_common::signature::{SignedRequest, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use hmac::Mac;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use bird_lg_common::signature::AuthScheme;
+
+/// Accepted nonce lengths, in characters
+const NONCE_LENGTH: std::ops::RangeInclusive<usize> = 16..=64;
+
+/// Why a signed request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Missing,
+    Malformed,
+    Stale,
+    BadSignature,
+    Replayed,
+}
+
+impl Rejection {
+    /// Label for the rejected-auth metric
+    pub fn reason(self) -> &'static str {
+        match self {
+            Rejection::Missing => "missing_signature",
+            Rejection::Malformed => "malformed_signature",
+            Rejection::Stale => "stale_signature",
+            Rejection::BadSignature => "invalid_signature",
+            Rejection::Replayed => "replayed_signature",
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+/// Check that `request` was signed for one of `hosts` with one of `secrets`,
+/// at most `max_skew` seconds away from `now`, and remember its nonce. Without
+/// `hosts`, the request's own host is taken. Returns the index of the matching
+/// secret.
+pub fn verify(
+    secrets: &[&str],
+    hosts: &[String],
+    max_skew: u64,
+    now: u64,
+    request: &Request,
+    nonces: &NonceCache,
+) -> Result<usize, Rejection> {
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let (Some(timestamp), Some(nonce), Some(signature)) =
+        (header(TIMESTAMP_HEADER), header(NONCE_HEADER), header(SIGNATURE_HEADER))
+    else {
+        return Err(Rejection::Missing);
+    };
+
+    let signed_at: u64 = timestamp.parse().map_err(|_| Rejection::Malformed)?;
+    if !NONCE_LENGTH.contains(&nonce.len()) || !nonce.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err(Rejection::Malformed);
+    }
+    let signature = hex::decode(signature).map_err(|_| Rejection::Malformed)?;
+
+    if signed_at.abs_diff(now) > max_skew {
+        return Err(Rejection::Stale);
+    }
+
+    let uri = request.uri();
+    let request_host = uri.host().or_else(|| header(HOST.as_str())).unwrap_or("");
+    let hosts: Vec<&str> = match hosts {
+        [] => vec![request_host],
+        hosts => hosts.iter().map(String::as_str).collect(),
+    };
+    let signed = |host| SignedRequest {
+        method: request.method().as_str(),
+        host,
+        path: uri.path(),
+        query: uri.query().unwrap_or(""),
+        timestamp,
+        nonce,
+    };
+
+    // verify_slice compares in constant time
+    let matched = secrets
+        .iter()
+        .position(|secret| hosts.iter().any(|host| signed(host).mac(secret).verify_slice(&signature).is_ok()))
+        .ok_or(Rejection::BadSignature)?;
+
+    // Only authentic requests reach the cache, so it cannot be flooded
+    if !nonces.insert(nonce, signed_at + max_skew, now) {
+        return Err(Rejection::Replayed);
+    }
+    Ok(matched)
+}
+
+/// Nonces of accepted requests, kept until their timestamp is too old to be
+/// accepted again
+#[derive(Default)]
+pub struct NonceCache {
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl NonceCache {
+    pub fn global() -> &'static NonceCache {
+        static NONCES: OnceLock<NonceCache> = OnceLock::new();
+        NONCES.get_or_init(NonceCache::default)
+    }
+
+    /// Record `nonce` until `expires`; false if it was already seen
+    fn insert(&self, nonce: &str, expires: u64, now: u64) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        seen.retain(|_, expiry| *expiry >= now);
+        if seen.contains_key(nonce) {
+            return false;
+        }
+        seen.insert(nonce.to_string(), expires);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    const SECRET: &str = "node-secret";
+    const NONCE: &str = "0123456789abcdef0123456789abcdef";
+    const NOW: u64 = 1700000000;
+    // HMAC-SHA256 of "GET\nlg1.example\n/bird\nq=show+protocols\n1700000000\n<NONCE>", also checked by the frontend
+    const SIGNATURE: &str = "976eba941d942d54e83bcc00ac0f6ef58d92a7554f3cb913bb9f96cc7e029c9a";
+
+    fn request(uri: &str, timestamp: u64, nonce: &str, signature: &str) -> Request {
+        Request::builder()
+            .uri(uri)
+            .header(HOST, "lg1.example:8000")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(NONCE_HEADER, nonce)
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn signed() -> Request {
+        request("/bird?q=show+protocols", NOW, NONCE, SIGNATURE)
+    }
+
+    fn check(now: u64, request: &Request, nonces: &NonceCache) -> Result<usize, Rejection> {
+        verify(&[SECRET], &[], 30, now, request, nonces)
+    }
+
+    #[test]
+    fn test_accepts_signed_request_once() {
+        let nonces = NonceCache::default();
+        assert_eq!(check(NOW + 5, &signed(), &nonces), Ok(0));
+        assert_eq!(check(NOW + 6, &signed(), &nonces), Err(Rejection::Replayed));
+    }
+
+    #[test]
+    fn test_reports_matching_secret() {
+        let nonces = NonceCache::default();
+        let secrets = ["monitoring-secret", SECRET, "other-secret"];
+        assert_eq!(verify(&secrets, &[], 30, NOW, &signed(), &nonces), Ok(1));
+    }
+
+    #[test]
+    fn test_rejects_tampered_or_foreign_requests() {
+        let nonces = NonceCache::default();
+
+        let query = request("/bird?q=show+route", NOW, NONCE, SIGNATURE);
+        assert_eq!(check(NOW, &query, &nonces), Err(Rejection::BadSignature));
+        let path = request("/bird6?q=show+protocols", NOW, NONCE, SIGNATURE);
+        assert_eq!(check(NOW, &path, &nonces), Err(Rejection::BadSignature));
+        assert_eq!(verify(&["other-secret"], &[], 30, NOW, &signed(), &nonces), Err(Rejection::BadSignature));
+        // The signature covers the timestamp
+        let moved = request("/bird?q=show+protocols", NOW + 1, NONCE, SIGNATURE);
+        assert_eq!(check(NOW, &moved, &nonces), Err(Rejection::BadSignature));
+
+        // A request signed for another proxy sharing the secret
+        let mut other_host = signed();
+        other_host.headers_mut().insert(HOST, "lg2.example".parse().unwrap());
+        assert_eq!(check(NOW, &other_host, &nonces), Err(Rejection::BadSignature));
+
+        // Failed attempts do not burn the nonce
+        assert_eq!(check(NOW, &signed(), &nonces), Ok(0));
+    }
+
+    #[test]
+    fn test_rejects_stale_and_malformed_requests() {
+        let nonces = NonceCache::default();
+        assert_eq!(check(NOW + 31, &signed(), &nonces), Err(Rejection::Stale));
+        assert_eq!(check(NOW - 31, &signed(), &nonces), Err(Rejection::Stale));
+
+        let unsigned = Request::builder().uri("/bird?q=show+protocols").body(Body::empty()).unwrap();
+        assert_eq!(check(NOW, &unsigned, &nonces), Err(Rejection::Missing));
+        let short_nonce = request("/bird?q=show+protocols", NOW, "short", SIGNATURE);
+        assert_eq!(check(NOW, &short_nonce, &nonces), Err(Rejection::Malformed));
+        let not_hex = request("/bird?q=show+protocols", NOW, NONCE, "not-hex");
+        assert_eq!(check(NOW, &not_hex, &nonces), Err(Rejection::Malformed));
+    }
+
+    #[test]
+    fn test_configured_hosts() {
+        let nonces = NonceCache::default();
+        let hosts = ["lg2.example".to_string()];
+        // Once the proxy knows its names, the Host header is not used
+        assert_eq!(verify(&[SECRET], &hosts, 30, NOW, &signed(), &nonces), Err(Rejection::BadSignature));
+
+        let hosts = ["lg1.example".to_string(), "lg2.example".to_string()];
+        let mut other_host = signed();
+        other_host.headers_mut().insert(HOST, "lg2.example".parse().unwrap());
+        assert_eq!(verify(&[SECRET], &hosts, 30, NOW, &other_host, &nonces), Ok(0));
+    }
+
+    #[test]
+    fn test_nonce_cache_forgets_expired_entries() {
+        let nonces = NonceCache::default();
+        assert!(nonces.insert(NONCE, NOW + 30, NOW));
+        assert!(!nonces.insert(NONCE, NOW + 30, NOW + 30));
+        assert!(nonces.insert(NONCE, NOW + 61, NOW + 31));
+        assert_eq!(nonces.seen.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use axum::http::StatusCode;
+use ipnet::IpNet;
+use std::fmt;
+use std::net::IpAddr;
+use crate::dns;
+use crate::settings::Settings;
+
+/// Address family a traceroute or ping has to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Whichever the target resolves to first
+    Any,
+    Ipv6,
+}
+
+/// Why a traceroute or ping target was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetError {
+    Invalid(String),
+    Unresolved(String),
+    Denied(IpAddr),
+}
+
+impl fmt::Display for TargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetError::Invalid(reason) => write!(f, "Invalid target: {}", reason),
+            TargetError::Unresolved(reason) => write!(f, "Failed to resolve target: {}", reason),
+            TargetError::Denied(address) => write!(f, "Target {} is in a denied network", address),
+        }
+    }
+}
+
+impl std::error::Error for TargetError {}
+
+impl TargetError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            TargetError::Invalid(_) | TargetError::Unresolved(_) => StatusCode::BAD_REQUEST,
+            TargetError::Denied(_) => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Status for a traceroute or ping failure: the target's, or a server error
+pub fn error_status(error: &anyhow::Error) -> StatusCode {
+    error
+        .downcast_ref::<TargetError>()
+        .map_or(StatusCode::INTERNAL_SERVER_ERROR, TargetError::status)
+}
+
+/// An IP literal, or a host name that still has to be resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target<'a> {
+    Address(IpAddr),
+    Name(&'a str),
+}
+
+fn parse(query: &str, family: AddressFamily) -> Result<Target<'_>, TargetError> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(TargetError::Invalid("query is empty".to_string()));
+    }
+
+    if let Ok(address) = query.parse::<IpAddr>() {
+        // IPv4-mapped IPv6 addresses are checked and traced as IPv4
+        let address = address.to_canonical();
+        if family == AddressFamily::Ipv6 && !address.is_ipv6() {
+            return Err(TargetError::Invalid(format!("{} is not an IPv6 address", query)));
+        }
+        return Ok(Target::Address(address));
+    }
+
+    if !dns::is_host_name(query) {
+        return Err(TargetError::Invalid(format!("'{}' is neither an IP address nor a host name", query)));
+    }
+    Ok(Target::Name(query))
+}
+
+/// The denied network containing `address`, if any
+fn denied_by(address: IpAddr, denied: &[IpNet]) -> Option<&IpNet> {
+    denied.iter().find(|net| net.contains(&address))
+}
+
+async fn lookup(name: &str, family: AddressFamily) -> Result<IpAddr, TargetError> {
+    let resolver = dns::resolver().map_err(TargetError::Unresolved)?;
+    // Fully qualified, so search domains are not tried
+    let fqdn = format!("{}.", name.strip_suffix('.').unwrap_or(name));
+    let unresolved = |e: hickory_resolver::error::ResolveError| {
+        TargetError::Unresolved(format!("{}: {}", name, dns::describe_error(&e)))
+    };
+
+    let address = match family {
+        AddressFamily::Any => resolver.lookup_ip(fqdn).await.map_err(unresolved)?.iter().next(),
+        AddressFamily::Ipv6 => resolver
+            .ipv6_lookup(fqdn)
+            .await
+            .map_err(unresolved)?
+            .iter()
+            .next()
+            .map(|aaaa| IpAddr::V6(aaaa.0)),
+    };
+    address
+        .map(|address| address.to_canonical())
+        .ok_or_else(|| TargetError::Unresolved(format!("{}: no addresses", name)))
+}
+
+/// Check a traceroute or ping target, resolving host names once. The address
+/// returned is the one to hand to the binary.
+pub async fn resolve(query: &str, family: AddressFamily) -> Result<IpAddr, TargetError> {
+    let address = match parse(query, family)? {
+        Target::Address(address) => address,
+        Target::Name(name) => lookup(name, family).await?,
+    };
+
+    if denied_by(address, &Settings::global().denied_targets).is_some() {
+        return Err(TargetError::Denied(address));
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::parse_nets;
+
+    #[test]
+    fn test_parse() {
+        let address = |s: &str| Ok(Target::Address(s.parse().unwrap()));
+        assert_eq!(parse(" 192.0.2.1 ", AddressFamily::Any), address("192.0.2.1"));
+        assert_eq!(parse("2001:db8::1", AddressFamily::Ipv6), address("2001:db8::1"));
+        assert_eq!(parse("::ffff:127.0.0.1", AddressFamily::Any), address("127.0.0.1"));
+        assert_eq!(parse("one.one.one.one", AddressFamily::Ipv6), Ok(Target::Name("one.one.one.one")));
+
+        assert!(matches!(parse("192.0.2.1", AddressFamily::Ipv6), Err(TargetError::Invalid(_))));
+        assert!(matches!(parse("", AddressFamily::Any), Err(TargetError::Invalid(_))));
+        assert!(matches!(parse("-w1", AddressFamily::Any), Err(TargetError::Invalid(_))));
+        assert!(matches!(parse("--help", AddressFamily::Any), Err(TargetError::Invalid(_))));
+        assert!(matches!(parse("example.com -m 255", AddressFamily::Any), Err(TargetError::Invalid(_))));
+        assert!(matches!(parse("192.0.2.0/24", AddressFamily::Any), Err(TargetError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_denied_by() {
+        let denied = parse_nets(&["10.0.0.0/8", "127.0.0.0/8", "fe80::/10", "203.0.113.7"]).unwrap();
+        let denied_by = |s: &str| denied_by(s.parse().unwrap(), &denied).map(ToString::to_string);
+
+        assert_eq!(denied_by("10.1.2.3").as_deref(), Some("10.0.0.0/8"));
+        assert_eq!(denied_by("fe80::1").as_deref(), Some("fe80::/10"));
+        assert_eq!(denied_by("203.0.113.7").as_deref(), Some("203.0.113.7/32"));
+        assert_eq!(denied_by("203.0.113.8"), None);
+        assert_eq!(denied_by("2001:db8::1"), None);
+        assert_eq!(TargetError::Denied("10.1.2.3".parse().unwrap()).status(), StatusCode::FORBIDDEN);
+    }
+}
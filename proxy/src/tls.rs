@@ -0,0 +1,243 @@
+use anyhow::{bail, Context, Result};
+use arc_swap::ArcSwap;
+use axum::{extract::ConnectInfo, Router};
+use hyper::{body::Incoming, Request};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::crypto::aws_lc_rs;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+use tracing::{info, warn};
+
+/// Time allowed for a client to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the certificate files are checked for rotation
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Certificate, key and optional client CA of the TLS listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub cert: String,
+    pub key: String,
+    /// CA bundle client certificates must chain to; enables mTLS
+    pub client_ca: Option<String>,
+}
+
+impl TlsFiles {
+    pub fn load(&self) -> Result<ServerConfig> {
+        let certs = read_certs(&self.cert)?;
+        let key = PrivateKeyDer::from_pem_slice(&read_file(&self.key)?)
+            .with_context(|| format!("No private key found in {}", self.key))?;
+
+        let builder = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca {
+            Some(client_ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(client_ca)? {
+                    roots.add(cert).with_context(|| format!("Invalid CA certificate in {}", client_ca))?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), Arc::new(aws_lc_rs::default_provider()))
+                    .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .with_context(|| format!("Certificate {} does not match key {}", self.cert, self.key))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        [Some(&self.cert), Some(&self.key), self.client_ca.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path))
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_slice_iter(&read_file(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate in {}", path))?;
+    if certs.is_empty() {
+        bail!("No certificates found in {}", path);
+    }
+    Ok(certs)
+}
+
+/// Reload the TLS configuration whenever one of the files changes, keeping
+/// the running one if the new files are invalid
+fn watch(files: TlsFiles, config: Arc<ArcSwap<ServerConfig>>) {
+    tokio::spawn(async move {
+        let mut last_modified = files.modified_times();
+        let mut poll = tokio::time::interval(FILE_POLL_INTERVAL);
+
+        loop {
+            poll.tick().await;
+            let modified = files.modified_times();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match files.load() {
+                Ok(new_config) => {
+                    config.store(Arc::new(new_config));
+                    info!("TLS certificate reloaded from {}", files.cert);
+                }
+                Err(e) => warn!("Keeping the running TLS certificate, reload failed: {:#}", e),
+            }
+        }
+    });
+}
+
+/// Serve `app` over TLS on `listener`
+pub async fn serve(listener: TcpListener, app: Router, files: TlsFiles) -> Result<()> {
+    let config = Arc::new(ArcSwap::from_pointee(files.load()?));
+    watch(files, config.clone());
+
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept TCP connection: {}", e);
+                continue;
+            }
+        };
+
+        let acceptor = TlsAcceptor::from(config.load_full());
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    info!("TLS handshake with {} failed: {}", remote_addr, e);
+                    return;
+                }
+                Err(_) => {
+                    info!("TLS handshake with {} timed out", remote_addr);
+                    return;
+                }
+            };
+
+            // Expose the peer address to the access control middleware, like axum::serve does
+            let hyper_service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(remote_addr));
+                app.clone().call(request)
+            });
+
+            if let Err(err) = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new())
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), hyper_service)
+                .await
+            {
+                warn!("Error serving connection: {:?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::TlsConnector;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn server_files(client_ca: bool) -> TlsFiles {
+        TlsFiles {
+            cert: fixture("server.pem"),
+            key: fixture("server.key"),
+            client_ca: client_ca.then(|| fixture("ca.pem")),
+        }
+    }
+
+    async fn start(files: TlsFiles) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route(
+            "/",
+            get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }),
+        );
+        tokio::spawn(serve(listener, app, files));
+        addr
+    }
+
+    /// GET / over TLS, returning the response or the connection error
+    async fn get_root(addr: SocketAddr, client_cert: bool) -> Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(read_certs(&fixture("ca.pem"))?.remove(0))?;
+        let builder = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+        let config = if client_cert {
+            let key = PrivateKeyDer::from_pem_slice(&read_file(&fixture("client.key"))?)?;
+            builder.with_client_auth_cert(read_certs(&fixture("client.pem"))?, key)?
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let tcp = tokio::net::TcpStream::connect(addr).await?;
+        let mut tls = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost")?, tcp)
+            .await?;
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+        let mut response = String::new();
+        tls.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[test]
+    fn test_load_rejects_mismatched_files() {
+        assert!(server_files(true).load().is_ok());
+
+        let wrong_key = TlsFiles { key: fixture("client.key"), ..server_files(false) };
+        assert!(wrong_key.load().is_err());
+
+        let key_as_cert = TlsFiles { cert: fixture("server.key"), ..server_files(false) };
+        assert!(key_as_cert.load().is_err());
+
+        let missing = TlsFiles { cert: fixture("missing.pem"), ..server_files(false) };
+        assert!(missing.load().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serves_over_tls() {
+        let addr = start(server_files(false)).await;
+        let response = get_root(addr, false).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("127.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_required_with_client_ca() {
+        let addr = start(server_files(true)).await;
+
+        let response = get_root(addr, true).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        // TLS 1.3 reports the rejected certificate on the first read or write
+        assert!(get_root(addr, false).await.is_err());
+    }
+}
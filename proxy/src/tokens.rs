@@ -0,0 +1,232 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+/// Name of the unrestricted token configured with `auth_token`
+const LEGACY_TOKEN_NAME: &str = "auth_token";
+
+/// One entry of the `tokens` table in the configuration file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    /// Name shown in logs
+    pub name: String,
+    pub token: String,
+    /// Endpoints this token may call, like `/bird` (which also covers `/bird6`); all when unset
+    #[serde(default)]
+    pub endpoints: Option<Vec<String>>,
+    /// BIRD command prefixes this token may run, like `show protocols`; all when unset
+    #[serde(default)]
+    pub commands: Option<Vec<String>>,
+    /// IPs or networks this token may be used from; anywhere when unset
+    #[serde(default)]
+    pub allowed: Option<Vec<String>>,
+    /// RFC 3339 time or date after which the token is refused
+    #[serde(default)]
+    pub expires: Option<String>,
+}
+
+/// An API token and what it may be used for
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    pub secret: String,
+    endpoints: Option<Vec<String>>,
+    commands: Option<Vec<String>>,
+    allowed_nets: Vec<IpNet>,
+    expires: Option<DateTime<Utc>>,
+}
+
+/// Why an authentic token may not make a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    Expired,
+    Endpoint,
+    Command,
+    Source,
+}
+
+impl Denial {
+    /// Label for the rejected-auth metric
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Denial::Expired => "token_expired",
+            Denial::Endpoint => "endpoint_not_allowed",
+            Denial::Command => "command_not_allowed",
+            Denial::Source => "source_not_allowed",
+        }
+    }
+}
+
+impl ApiToken {
+    /// Token with access to everything, from `auth_token`
+    pub fn unrestricted(secret: String) -> Self {
+        ApiToken {
+            name: LEGACY_TOKEN_NAME.to_string(),
+            secret,
+            endpoints: None,
+            commands: None,
+            allowed_nets: Vec::new(),
+            expires: None,
+        }
+    }
+
+    pub fn from_config(config: TokenConfig) -> Result<Self> {
+        if config.token.is_empty() {
+            bail!("Token '{}' is empty", config.name);
+        }
+
+        let endpoints = config.endpoints.map(|endpoints| {
+            endpoints
+                .iter()
+                .map(|endpoint| format!("/{}", endpoint.trim().trim_start_matches('/')))
+                .collect()
+        });
+        let commands = config.commands.map(|commands| commands.iter().map(|command| normalize_command(command)).collect());
+        let allowed_nets = crate::settings::parse_nets(config.allowed.as_deref().unwrap_or_default())?;
+        let expires = config
+            .expires
+            .as_deref()
+            .map(parse_expiry)
+            .transpose()
+            .map_err(|e| anyhow!("Token '{}': {}", config.name, e))?;
+
+        Ok(ApiToken {
+            name: config.name,
+            secret: config.token,
+            endpoints,
+            commands,
+            allowed_nets,
+            expires,
+        })
+    }
+
+    /// Check a request for `path` with BIRD `command` (the `q` parameter of
+    /// `/bird`) from `remote_ip` against the token's restrictions
+    pub fn permits(&self, path: &str, command: Option<&str>, remote_ip: Option<IpAddr>, now: DateTime<Utc>) -> Result<(), Denial> {
+        if self.expires.is_some_and(|expires| now >= expires) {
+            return Err(Denial::Expired);
+        }
+
+        if let Some(endpoints) = &self.endpoints {
+            let family = path.strip_suffix('6').unwrap_or(path);
+            if !endpoints.iter().any(|endpoint| endpoint == path || endpoint == family) {
+                return Err(Denial::Endpoint);
+            }
+        }
+
+        if let Some(commands) = &self.commands {
+            if path.starts_with("/bird") {
+                let command = normalize_command(command.unwrap_or_default());
+                let allowed = commands.iter().any(|prefix| {
+                    command == *prefix || command.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with(' '))
+                });
+                if !allowed {
+                    return Err(Denial::Command);
+                }
+            }
+        }
+
+        // Unix socket clients have no address and are local
+        if let (false, Some(ip)) = (self.allowed_nets.is_empty(), remote_ip) {
+            if !self.allowed_nets.iter().any(|net| net.contains(&ip)) {
+                return Err(Denial::Source);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lowercase with single spaces, so `Show  Protocols` matches `show protocols`
+fn normalize_command(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn parse_expiry(expires: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(expires) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(expires, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc())
+        .map_err(|_| anyhow!("Invalid expiry '{}': expected an RFC 3339 time or YYYY-MM-DD", expires))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoped(extra: &str) -> ApiToken {
+        ApiToken::from_config(toml_like(extra)).unwrap()
+    }
+
+    /// Build a token config from `key = value` lines on top of a name and secret
+    fn toml_like(extra: &str) -> TokenConfig {
+        let source = format!("name = \"monitoring\"\ntoken = \"secret\"\n{}", extra);
+        config::Config::builder()
+            .add_source(config::File::from_str(&source, config::FileFormat::Toml))
+            .build()
+            .and_then(config::Config::try_deserialize)
+            .unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        parse_expiry("2026-06-01T12:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn test_unrestricted_token() {
+        let token = ApiToken::unrestricted("secret".to_string());
+        assert_eq!(token.permits("/traceroute", None, Some("192.0.2.1".parse().unwrap()), now()), Ok(()));
+        assert_eq!(token.permits("/bird", Some("show route all"), None, now()), Ok(()));
+    }
+
+    #[test]
+    fn test_endpoint_scope() {
+        let token = scoped("endpoints = [\"/bird\"]");
+        assert_eq!(token.permits("/bird", Some("show route"), None, now()), Ok(()));
+        assert_eq!(token.permits("/bird6", Some("show route"), None, now()), Ok(()));
+        assert_eq!(token.permits("/traceroute", None, None, now()), Err(Denial::Endpoint));
+        assert_eq!(token.permits("/traceroute6", None, None, now()), Err(Denial::Endpoint));
+
+        let v6_only = scoped("endpoints = [\"bird6\"]");
+        assert_eq!(v6_only.permits("/bird6", Some("show route"), None, now()), Ok(()));
+        assert_eq!(v6_only.permits("/bird", Some("show route"), None, now()), Err(Denial::Endpoint));
+    }
+
+    #[test]
+    fn test_command_allowlist() {
+        let token = scoped("commands = [\"show protocols\"]");
+        assert_eq!(token.permits("/bird", Some("show protocols"), None, now()), Ok(()));
+        assert_eq!(token.permits("/bird", Some("Show  Protocols all bgp1"), None, now()), Ok(()));
+        assert_eq!(token.permits("/bird", Some("show protocolsx"), None, now()), Err(Denial::Command));
+        assert_eq!(token.permits("/bird", Some("show route"), None, now()), Err(Denial::Command));
+        assert_eq!(token.permits("/bird", None, None, now()), Err(Denial::Command));
+        // Command lists only restrict BIRD queries
+        assert_eq!(token.permits("/traceroute", Some("192.0.2.1"), None, now()), Ok(()));
+    }
+
+    #[test]
+    fn test_source_networks() {
+        let token = scoped("allowed = [\"192.0.2.0/24\", \"2001:db8::1\"]");
+        assert_eq!(token.permits("/bird", None, Some("192.0.2.7".parse().unwrap()), now()), Ok(()));
+        assert_eq!(token.permits("/bird", None, Some("2001:db8::1".parse().unwrap()), now()), Ok(()));
+        assert_eq!(token.permits("/bird", None, Some("198.51.100.1".parse().unwrap()), now()), Err(Denial::Source));
+        assert_eq!(token.permits("/bird", None, None, now()), Ok(()));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let token = scoped("expires = \"2026-06-01T12:00:00Z\"");
+        assert_eq!(token.permits("/bird", None, None, now() - chrono::Duration::seconds(1)), Ok(()));
+        assert_eq!(token.permits("/bird", None, None, now()), Err(Denial::Expired));
+
+        let date = scoped("expires = \"2026-07-01\"");
+        assert_eq!(date.permits("/bird", None, None, now()), Ok(()));
+
+        assert!(ApiToken::from_config(toml_like("expires = \"next week\"")).is_err());
+        assert!(ApiToken::from_config(toml_like("allowed = [\"not-a-network\"]")).is_err());
+    }
+}
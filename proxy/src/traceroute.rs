@@ -1,22 +1,126 @@
 use anyhow::{anyhow, Result};
+use futures_util::{stream, Stream};
 use regex::Regex;
+use serde::Serialize;
+use std::process::Stdio;
 use std::sync::OnceLock;
-use tokio::process::Command;
-use tokio::sync::Semaphore;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tracing::{info, warn};
 use crate::settings::Settings;
+use crate::target::{self, AddressFamily};
+use crate::traceroute_parser::{self, Hop};
 
 #[derive(Debug, Clone)]
 struct TracerouteConfig {
     bin: String,
     flags: Vec<String>,
+    /// Flags for `mtr --json`, when the binary is an mtr that supports it
+    json_flags: Option<Vec<String>>,
+    /// traceroute binary and flags to stream with, when the binary is an mtr
+    stream: Option<(String, Vec<String>)>,
+}
+
+/// Flags tried with traceroute binaries, Debian style, FreeBSD style, then none
+const TRACEROUTE_FLAG_SETS: [&[&str]; 3] = [&["-q1", "-N32", "-w1"], &["-q1", "-w1"], &[]];
+
+/// Hops of a traceroute, for `format=json`
+#[derive(Debug, Serialize)]
+pub struct TracerouteReport {
+    /// Address traced, after resolving the query
+    pub target: String,
+    /// `mtr` or `traceroute`
+    pub tool: &'static str,
+    pub hops: Vec<Hop>,
+    /// The same run as text, like without `format=json`
+    pub text: String,
+}
+
+/// Progress of a streamed traceroute
+#[derive(Debug)]
+pub enum TracerouteEvent {
+    /// A line of output, as soon as the binary prints it
+    Line(String),
+    /// The parsed run, once the binary has exited
+    Report(TracerouteReport),
+    Error(String),
+}
+
+/// A running traceroute, killed when dropped
+struct RunningTraceroute {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    target: String,
+    output: String,
+    _permit: SemaphorePermit<'static>,
+}
+
+impl RunningTraceroute {
+    fn spawn(bin: &str, flags: &[String], target: String, permit: SemaphorePermit<'static>) -> Result<Self> {
+        let mut child = Command::new(bin)
+            .args(flags)
+            .arg(&target)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("Error executing traceroute: {}", e))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("Traceroute output not captured"))?;
+
+        Ok(RunningTraceroute {
+            child,
+            stdout: BufReader::new(stdout),
+            target,
+            output: String::new(),
+            _permit: permit,
+        })
+    }
+
+    /// Lines as they are printed, then the report or an error
+    fn events(self) -> impl Stream<Item = TracerouteEvent> {
+        stream::unfold(Some(self), |running| async move {
+            let mut running = running?;
+            match running.next_event().await {
+                event @ TracerouteEvent::Line(_) => Some((event, Some(running))),
+                event => Some((event, None)),
+            }
+        })
+    }
+
+    async fn next_event(&mut self) -> TracerouteEvent {
+        let mut line = Vec::new();
+        match self.stdout.read_until(b'\n', &mut line).await {
+            Ok(0) => {}
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&line).to_string();
+                self.output.push_str(&line);
+                return TracerouteEvent::Line(line.trim_end_matches(['\r', '\n']).to_string());
+            }
+            Err(e) => return TracerouteEvent::Error(format!("Error reading traceroute output: {}", e)),
+        }
+
+        match self.child.wait().await {
+            Ok(status) if status.success() => {
+                let (tool, hops) = traceroute_parser::parse_text(&self.output);
+                TracerouteEvent::Report(TracerouteReport {
+                    target: std::mem::take(&mut self.target),
+                    tool,
+                    hops,
+                    text: process_output(&self.output),
+                })
+            }
+            Ok(status) => TracerouteEvent::Error(format!("Error executing traceroute: command failed with status: {}", status)),
+            Err(e) => TracerouteEvent::Error(format!("Error executing traceroute: {}", e)),
+        }
+    }
 }
 
 static TRACEROUTE_CONFIG: OnceLock<Option<TracerouteConfig>> = OnceLock::new();
-static TRACEROUTE_SEMAPHORE: OnceLock<Semaphore> = OnceLock::new();
+static TRACEROUTE_SEMAPHORE: OnceLock<(Semaphore, usize)> = OnceLock::new();
 
 /// Convert command and args to string for display
-fn args_to_string(cmd: &str, args: &[String], target: &[String]) -> String {
+pub fn args_to_string(cmd: &str, args: &[String], target: &[String]) -> String {
     let mut combined = vec![cmd.to_string()];
     combined.extend_from_slice(args);
     combined.extend_from_slice(target);
@@ -24,7 +128,7 @@ fn args_to_string(cmd: &str, args: &[String], target: &[String]) -> String {
 }
 
 /// Try to execute traceroute with given parameters to test if it works
-async fn try_execute(cmd: &str, args: &[String], target: &[String]) -> Result<Vec<u8>> {
+pub async fn try_execute(cmd: &str, args: &[String], target: &[String]) -> Result<Vec<u8>> {
     let mut command = Command::new(cmd);
     command.args(args);
     command.args(target);
@@ -55,20 +159,73 @@ async fn traceroute_detect(cmd: &str, args: &[String]) -> bool {
     }
 }
 
+/// Check whether the configured binary is an mtr with `--json`, returning
+/// the flags to run it with
+async fn json_detect(cmd: &str, flags: &[String]) -> Option<Vec<String>> {
+    if !is_mtr(cmd) {
+        return None;
+    }
+
+    // Report modes take precedence over --json
+    let mut json_flags: Vec<String> = flags
+        .iter()
+        .filter(|flag| !matches!(flag.as_str(), "-w" | "-r" | "--report" | "--report-wide"))
+        .cloned()
+        .collect();
+    json_flags.push("--json".to_string());
+
+    let target = vec!["127.0.0.1".to_string()];
+    let output = try_execute(cmd, &json_flags, &target).await.ok()?;
+    if traceroute_parser::parse_mtr_json(&String::from_utf8_lossy(&output)).is_some() {
+        info!("Traceroute JSON output enabled: {}", args_to_string(cmd, &json_flags, &target));
+        Some(json_flags)
+    } else {
+        info!("mtr does not support --json, parsing text output instead");
+        None
+    }
+}
+
+/// Find a traceroute to stream with in place of mtr, whose report modes
+/// only print once done
+async fn stream_detect(cmd: &str) -> Option<(String, Vec<String>)> {
+    if !is_mtr(cmd) {
+        return None;
+    }
+
+    for flags in TRACEROUTE_FLAG_SETS {
+        let flags: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
+        if traceroute_detect("traceroute", &flags).await {
+            return Some(("traceroute".to_string(), flags));
+        }
+    }
+    warn!("No traceroute found to stream with, streamed mtr output arrives once done");
+    None
+}
+
+fn is_mtr(cmd: &str) -> bool {
+    std::path::Path::new(cmd)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("mtr"))
+}
+
 /// Auto-detect the best available traceroute configuration
 pub async fn init() {
     let settings = Settings::global();
     
     // Initialize semaphore for limiting concurrent traceroute requests
     let semaphore = Semaphore::new(settings.traceroute_max_concurrent);
-    TRACEROUTE_SEMAPHORE.set(semaphore).expect("Semaphore already initialized");
+    TRACEROUTE_SEMAPHORE
+        .set((semaphore, settings.traceroute_max_concurrent))
+        .expect("Semaphore already initialized");
 
     let mut detected_config = None;
 
     // If both bin and flags are set, use them directly
-    if settings.traceroute_bin.is_some() && !settings.traceroute_flags.is_empty() {
+    if let Some(bin) = settings.traceroute_bin.as_ref().filter(|_| !settings.traceroute_flags.is_empty()) {
         let config = TracerouteConfig {
-            bin: settings.traceroute_bin.as_ref().expect("Bin should be set").clone(),
+            json_flags: json_detect(bin, &settings.traceroute_flags).await,
+            stream: stream_detect(bin).await,
+            bin: bin.clone(),
             flags: settings.traceroute_flags.clone(),
         };
         TRACEROUTE_CONFIG.set(Some(config)).expect("Config already initialized");
@@ -78,15 +235,12 @@ pub async fn init() {
     // Custom binary tests
     if let Some(ref custom_bin) = settings.traceroute_bin {
         // Try different flag combinations for custom binary
-        let flag_sets = vec![
-            vec!["-q1".to_string(), "-N32".to_string(), "-w1".to_string()],
-            vec!["-q1".to_string(), "-w1".to_string()],
-            vec![],
-        ];
-        
-        for flags in flag_sets {
+        for flags in TRACEROUTE_FLAG_SETS {
+            let flags: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
             if traceroute_detect(custom_bin, &flags).await {
                 detected_config = Some(TracerouteConfig {
+                    json_flags: json_detect(custom_bin, &flags).await,
+                    stream: stream_detect(custom_bin).await,
                     bin: custom_bin.clone(),
                     flags,
                 });
@@ -111,6 +265,8 @@ pub async fn init() {
         for (bin, flags) in tool_configs {
             if traceroute_detect(bin, &flags).await {
                 detected_config = Some(TracerouteConfig {
+                    json_flags: json_detect(bin, &flags).await,
+                    stream: stream_detect(bin).await,
                     bin: bin.to_string(),
                     flags,
                 });
@@ -126,63 +282,140 @@ pub async fn init() {
     TRACEROUTE_CONFIG.set(detected_config).expect("Config already initialized");
 }
 
-/// Execute traceroute command
-pub async fn execute_traceroute(query: &str) -> Result<String> {
-    let settings = Settings::global();
+/// Traceroutes running and allowed to run at once, once initialized
+pub fn slots() -> Option<(usize, usize)> {
+    TRACEROUTE_SEMAPHORE
+        .get()
+        .map(|(semaphore, capacity)| (capacity.saturating_sub(semaphore.available_permits()), *capacity))
+}
+
+/// Run the binary against `target`, returning its stdout
+async fn run(bin: &str, flags: &[String], target: &str) -> Result<String> {
+    // Execute traceroute with target as single argument
+    let mut command = Command::new(bin);
+    command.args(flags);
+    command.arg(target);
+
+    let output = command.output().await?;
 
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(anyhow!("Error executing traceroute: command failed with status: {}", output.status))
+    }
+}
+
+/// Output as returned to clients: raw, or without unresponsive hops
+fn process_output(output: &str) -> String {
+    if Settings::global().traceroute_raw {
+        return output.to_string();
+    }
+
+    // Process output to remove unresponsive hops and count them
+    let re = Regex::new(r"(?m)^\s*(\d*)\s*\*\n").expect("Invalid regex pattern");
+    let mut skipped_counter = 0;
+
+    let processed = re.replace_all(output, |_: &regex::Captures| {
+        skipped_counter += 1;
+        ""
+    });
+
+    let mut result = processed.trim().to_string();
+    if skipped_counter > 0 {
+        result.push_str(&format!("\n\n{} hops not responding.", skipped_counter));
+    }
+    result
+}
+
+/// Check the target and wait for a free slot, returning the address to trace
+async fn prepare(query: &str, family: AddressFamily) -> Result<(&'static TracerouteConfig, SemaphorePermit<'static>, String)> {
     let config = TRACEROUTE_CONFIG
         .get()
         .ok_or_else(|| anyhow!("Traceroute not initialized"))?
         .as_ref()
         .ok_or_else(|| anyhow!("Traceroute not supported on this node"))?;
 
-    let semaphore = TRACEROUTE_SEMAPHORE
+    let target = target::resolve(query, family).await?.to_string();
+
+    let (semaphore, _) = TRACEROUTE_SEMAPHORE
         .get()
         .ok_or_else(|| anyhow!("Traceroute semaphore not initialized"))?;
-    
+
     // Acquire semaphore permit to limit concurrent requests
-    let _permit = semaphore.acquire().await
+    let permit = semaphore.acquire().await
         .map_err(|e| anyhow!("Failed to acquire traceroute semaphore: {}", e))?;
-    
-    // Validate query: prevent parameter injection by rejecting targets with spaces
-    let trimmed_query = query.trim();
-    if trimmed_query.is_empty() {
-        return Err(anyhow!("Invalid target: query is empty"));
-    }
-    if trimmed_query.contains(' ') {
-        return Err(anyhow!("Invalid target: contains spaces (parameter injection not allowed)"));
-    }
-    
-    // Execute traceroute with target as single argument
-    let mut command = Command::new(&config.bin);
-    command.args(&config.flags);
-    command.arg(trimmed_query);
-    
-    let output = command.output().await?;
-    
-    if output.status.success() {
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        
-        if settings.traceroute_raw {
-            Ok(output_str.to_string())
-        } else {
-            // Process output to remove unresponsive hops and count them
-            let re = Regex::new(r"(?m)^\s*(\d*)\s*\*\n").expect("Invalid regex pattern");
-            let mut skipped_counter = 0;
-            
-            let processed = re.replace_all(&output_str, |_: &regex::Captures| {
-                skipped_counter += 1;
-                ""
+
+    Ok((config, permit, target))
+}
+
+/// Execute traceroute command
+pub async fn execute_traceroute(query: &str, family: AddressFamily) -> Result<String> {
+    let (config, _permit, target) = prepare(query, family).await?;
+    let output = run(&config.bin, &config.flags, &target).await?;
+    Ok(process_output(&output))
+}
+
+/// Start traceroute and stream its output line by line, ending with the parsed
+/// report or an error. Dropping the stream kills the binary.
+pub async fn stream_traceroute(query: &str, family: AddressFamily) -> Result<impl Stream<Item = TracerouteEvent>> {
+    let (config, permit, target) = prepare(query, family).await?;
+
+    // mtr prints its report, like its JSON, only once done, so traceroute
+    // streams in its place when there is one
+    let (bin, flags) = match &config.stream {
+        Some((bin, flags)) => (bin, flags),
+        None => (&config.bin, &config.flags),
+    };
+    Ok(RunningTraceroute::spawn(bin, flags, target, permit)?.events())
+}
+
+/// Execute traceroute command and parse its hops
+pub async fn execute_traceroute_report(query: &str, family: AddressFamily) -> Result<TracerouteReport> {
+    let (config, _permit, target) = prepare(query, family).await?;
+
+    if let Some(json_flags) = &config.json_flags {
+        let output = run(&config.bin, json_flags, &target).await?;
+        if let Some(hops) = traceroute_parser::parse_mtr_json(&output) {
+            return Ok(TracerouteReport {
+                target,
+                tool: "mtr",
+                text: traceroute_parser::format_hops(&hops),
+                hops,
             });
-            
-            let mut result = processed.trim().to_string();
-            if skipped_counter > 0 {
-                result.push_str(&format!("\n\n{} hops not responding.", skipped_counter));
-            }
-            
-            Ok(result)
         }
-    } else {
-        Err(anyhow!("Error executing traceroute: command failed with status: {}", output.status))
+        warn!("Failed to parse mtr JSON output, running again as text");
     }
-} 
\ No newline at end of file
+
+    let output = run(&config.bin, &config.flags, &target).await?;
+    let (tool, hops) = traceroute_parser::parse_text(&output);
+    Ok(TracerouteReport {
+        target,
+        tool,
+        hops,
+        text: process_output(&output),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_stream_lines_arrive_while_running() {
+        static SEMAPHORE: Semaphore = Semaphore::const_new(1);
+        let permit = SEMAPHORE.acquire().await.unwrap();
+
+        // Prints the header at once and its hop only after a while
+        let flags = ["-c", "echo \"traceroute to $1\"; sleep 10; echo \" 1  192.0.2.1  1.0 ms\"", "traceroute"];
+        let flags: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
+        let running = RunningTraceroute::spawn("sh", &flags, "192.0.2.1".to_string(), permit).unwrap();
+        let mut events = std::pin::pin!(running.events());
+
+        let first = tokio::time::timeout(Duration::from_secs(5), events.next())
+            .await
+            .expect("The line should arrive before the binary exits");
+        assert!(matches!(first, Some(TracerouteEvent::Line(line)) if line == "traceroute to 192.0.2.1"));
+    }
+}
@@ -0,0 +1,378 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+/// One hop of a traceroute or mtr run
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct Hop {
+    pub hop: u32,
+    /// Address that answered, if shown
+    pub address: Option<String>,
+    /// Reverse DNS name of the address
+    pub hostname: Option<String>,
+    /// Other addresses that answered for the same hop; traceroute only
+    pub alternates: Vec<String>,
+    /// Origin AS, with traceroute `-A` or mtr `-z`
+    pub asn: Option<u32>,
+    /// Probes sent
+    pub sent: u32,
+    /// Share of probes without an answer, in percent
+    pub loss: f64,
+    /// Round-trip time of every answered probe in milliseconds; traceroute only
+    pub rtts: Vec<f64>,
+    pub avg: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+}
+
+/// Split mtr's `name (address)`, a bare address or name, or `???` for no answer
+fn split_host(host: &str) -> (Option<String>, Option<String>) {
+    let host = host.trim();
+    if host.is_empty() || host == "???" {
+        return (None, None);
+    }
+    if let Some((name, address)) = host.strip_suffix(')').and_then(|host| host.split_once(" (")) {
+        return (Some(address.to_string()), Some(name.to_string()));
+    }
+    if host.parse::<IpAddr>().is_ok() {
+        (Some(host.to_string()), None)
+    } else {
+        (None, Some(host.to_string()))
+    }
+}
+
+/// Parse `AS13335`, `[AS13335]` or `[AS13335/AS1234]`; `AS???` and `[*]` are unknown
+fn parse_asn(text: &str) -> Option<u32> {
+    let text = text.trim_start_matches('[');
+    let digits: String = text
+        .strip_prefix("AS")
+        .unwrap_or(text)
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        // Older mtr versions quote some numbers
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parse `mtr --json` output
+pub fn parse_mtr_json(output: &str) -> Option<Vec<Hop>> {
+    let report: Value = serde_json::from_str(output).ok()?;
+    let hubs = report.get("report")?.get("hubs")?.as_array()?;
+
+    let hops = hubs
+        .iter()
+        .filter_map(|hub| {
+            let (address, hostname) = split_host(hub.get("host")?.as_str()?);
+            let sent = hub.get("Snt").and_then(number).unwrap_or(0.0) as u32;
+            let answered = address.is_some() || hostname.is_some();
+            let rtt = |key: &str| hub.get(key).and_then(number).filter(|_| answered);
+            Some(Hop {
+                hop: hub.get("count").and_then(number)? as u32,
+                address,
+                hostname,
+                alternates: Vec::new(),
+                asn: hub.get("ASN").and_then(Value::as_str).and_then(parse_asn),
+                sent,
+                loss: hub.get("Loss%").and_then(number).unwrap_or(100.0),
+                rtts: Vec::new(),
+                avg: rtt("Avg"),
+                best: rtt("Best"),
+                worst: rtt("Wrst"),
+            })
+        })
+        .collect();
+    Some(hops)
+}
+
+fn mtr_report_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^\s*(?P<hop>\d+)\.(?:\|--)?\s+(?:(?P<asn>AS(?:\d+|\?+))\s+)?(?P<host>\S+(?: \([^)]+\))?)\s+",
+            r"(?P<loss>[\d.]+)%?\s+(?P<sent>\d+)\s+(?P<last>[\d.]+)\s+(?P<avg>[\d.]+)\s+(?P<best>[\d.]+)\s+(?P<worst>[\d.]+)",
+        ))
+        .expect("Invalid regex pattern")
+    })
+}
+
+/// Parse mtr `--report` or `--report-wide` output
+fn parse_mtr_report(output: &str) -> Vec<Hop> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let captures = mtr_report_re().captures(line)?;
+            let (address, hostname) = split_host(&captures["host"]);
+            let answered = address.is_some() || hostname.is_some();
+            let rtt = |name: &str| captures[name].parse().ok().filter(|_| answered);
+            Some(Hop {
+                hop: captures["hop"].parse().ok()?,
+                address,
+                hostname,
+                alternates: Vec::new(),
+                asn: captures.name("asn").and_then(|asn| parse_asn(asn.as_str())),
+                sent: captures["sent"].parse().unwrap_or(0),
+                loss: captures["loss"].parse().unwrap_or(100.0),
+                rtts: Vec::new(),
+                avg: rtt("avg"),
+                best: rtt("best"),
+                worst: rtt("worst"),
+            })
+        })
+        .collect()
+}
+
+/// Record a host that answered for `hop`. Addresses after the first are kept
+/// as alternates.
+fn add_host(hop: &mut Hop, address: Option<String>, hostname: Option<String>) {
+    if hop.address.is_none() && hop.hostname.is_none() {
+        hop.hostname = hostname.filter(|name| Some(name) != address.as_ref());
+        hop.address = address;
+    } else if let Some(address) = address {
+        if hop.address.as_ref() != Some(&address) && !hop.alternates.contains(&address) {
+            hop.alternates.push(address);
+        }
+    }
+}
+
+/// A host given without `(address)`: a name, or with `-n` just the address
+fn add_bare_host(hop: &mut Hop, host: Option<&str>) {
+    if let Some(host) = host {
+        let (address, hostname) = split_host(host);
+        add_host(hop, address, hostname);
+    }
+}
+
+/// Add the hosts and probes of one traceroute line, like
+/// `one.one.one.one (1.1.1.1) [AS13335]  10.212 ms  *  11.003 ms !H`, to `hop`
+fn add_probes<'a>(hop: &mut Hop, tokens: impl Iterator<Item = &'a str>) {
+    let mut tokens = tokens.peekable();
+    let mut name = None;
+
+    while let Some(token) = tokens.next() {
+        if token == "*" {
+            add_bare_host(hop, name.take());
+            hop.sent += 1;
+        } else if let (Ok(rtt), Some(&"ms")) = (token.parse::<f64>(), tokens.peek()) {
+            add_bare_host(hop, name.take());
+            hop.sent += 1;
+            hop.rtts.push(rtt);
+            tokens.next();
+        } else if let Some(address) = token.strip_prefix('(').and_then(|token| token.strip_suffix(')')) {
+            add_host(hop, Some(address.to_string()), name.take().map(str::to_string));
+        } else if token.starts_with('[') {
+            hop.asn = hop.asn.or_else(|| parse_asn(token));
+        } else if !token.starts_with('!') {
+            add_bare_host(hop, name.replace(token));
+        }
+    }
+    add_bare_host(hop, name);
+}
+
+/// Fill in loss and round-trip statistics from the probes of a hop
+fn summarize(hop: &mut Hop) {
+    let answered = hop.rtts.len();
+    let lost = hop.sent as usize - answered;
+    hop.loss = if hop.sent == 0 { 100.0 } else { lost as f64 * 100.0 / hop.sent as f64 };
+    if answered > 0 {
+        hop.avg = Some(hop.rtts.iter().sum::<f64>() / answered as f64);
+        hop.best = hop.rtts.iter().copied().reduce(f64::min);
+        hop.worst = hop.rtts.iter().copied().reduce(f64::max);
+    }
+}
+
+/// Parse traceroute output. Indented lines without a hop number list further
+/// hosts that answered for the hop above, and are merged into it.
+fn parse_traceroute(output: &str) -> Vec<Hop> {
+    let mut hops: Vec<Hop> = Vec::new();
+    for line in output.lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(first) = tokens.peek() else {
+            continue;
+        };
+
+        if let Ok(number) = first.parse() {
+            tokens.next();
+            let mut hop = Hop { hop: number, ..Default::default() };
+            add_probes(&mut hop, tokens);
+            hops.push(hop);
+        } else if line.starts_with(char::is_whitespace) {
+            if let Some(hop) = hops.last_mut() {
+                add_probes(hop, tokens);
+            }
+        }
+    }
+
+    hops.iter_mut().for_each(summarize);
+    hops
+}
+
+/// Parse traceroute or mtr text output into hops, with the tool it came from
+pub fn parse_text(output: &str) -> (&'static str, Vec<Hop>) {
+    let mtr = parse_mtr_report(output);
+    if !mtr.is_empty() {
+        return ("mtr", mtr);
+    }
+    ("traceroute", parse_traceroute(output))
+}
+
+/// Render hops as an mtr-style report, for output that was not text to begin with
+pub fn format_hops(hops: &[Hop]) -> String {
+    let host = |hop: &Hop| match (&hop.hostname, &hop.address) {
+        (Some(hostname), Some(address)) => format!("{} ({})", hostname, address),
+        (Some(name), None) | (None, Some(name)) => name.clone(),
+        (None, None) => "???".to_string(),
+    };
+    let rtt = |rtt: Option<f64>| rtt.map(|rtt| format!("{:.1}", rtt)).unwrap_or_else(|| "-".to_string());
+
+    let mut lines = vec![format!("{:<4} {:<50} {:>6} {:>4} {:>7} {:>7} {:>7}", "", "Host", "Loss%", "Snt", "Avg", "Best", "Wrst")];
+    for hop in hops {
+        let asn = hop.asn.map(|asn| format!("AS{} ", asn)).unwrap_or_default();
+        lines.push(format!(
+            "{:>3}. {:<50} {:>5.1}% {:>4} {:>7} {:>7} {:>7}",
+            hop.hop,
+            format!("{}{}", asn, host(hop)),
+            hop.loss,
+            hop.sent,
+            rtt(hop.avg),
+            rtt(hop.best),
+            rtt(hop.worst)
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceroute() {
+        let output = "traceroute to one.one.one.one (1.1.1.1), 30 hops max, 60 byte packets
+ 1  _gateway (192.168.1.1)  0.421 ms  0.380 ms  0.377 ms
+ 2  *
+ 3  ae1.cr1.fra1.example.net (198.51.100.1) [AS64500]  10.212 ms  *  11.003 ms !H
+ 4  one.one.one.one (1.1.1.1) [AS13335/AS1234]  12.000 ms
+";
+        let (tool, hops) = parse_text(output);
+        assert_eq!(tool, "traceroute");
+        assert_eq!(hops.len(), 4);
+
+        assert_eq!(hops[0].address.as_deref(), Some("192.168.1.1"));
+        assert_eq!(hops[0].hostname.as_deref(), Some("_gateway"));
+        assert_eq!(hops[0].rtts, vec![0.421, 0.380, 0.377]);
+        assert_eq!(hops[0].sent, 3);
+        assert_eq!(hops[0].loss, 0.0);
+        assert_eq!(hops[0].best, Some(0.377));
+        assert_eq!(hops[0].worst, Some(0.421));
+
+        assert_eq!(hops[1], Hop { hop: 2, sent: 1, loss: 100.0, ..Default::default() });
+
+        assert_eq!(hops[2].asn, Some(64500));
+        assert_eq!(hops[2].rtts, vec![10.212, 11.003]);
+        assert_eq!(hops[2].sent, 3);
+        assert!((hops[2].loss - 33.333).abs() < 0.01);
+        assert_eq!(hops[3].asn, Some(13335));
+    }
+
+    #[test]
+    fn test_parse_traceroute_numeric() {
+        let (_, hops) = parse_text(" 1  2001:db8::1  0.512 ms\n 2  192.0.2.1  1.2 ms");
+        assert_eq!(hops[0].address.as_deref(), Some("2001:db8::1"));
+        assert_eq!(hops[0].hostname, None);
+        assert_eq!(hops[1].avg, Some(1.2));
+    }
+
+    #[test]
+    fn test_parse_traceroute_continuation() {
+        const CONTINUATION: &str = include_str!("../tests/fixtures/traceroute_continuation.txt");
+        let (tool, hops) = parse_text(CONTINUATION);
+        assert_eq!(tool, "traceroute");
+        assert_eq!(hops.iter().map(|hop| hop.hop).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+
+        assert_eq!(hops[1].address.as_deref(), Some("192.0.2.4"));
+        assert_eq!(hops[1].hostname.as_deref(), Some("a.example"));
+        assert_eq!(hops[1].alternates, vec!["192.0.2.5"]);
+        assert_eq!(hops[1].rtts, vec![10.114, 11.020, 12.008]);
+        assert_eq!(hops[1].sent, 3);
+        assert_eq!(hops[1].loss, 0.0);
+        assert_eq!(hops[1].worst, Some(12.008));
+
+        assert_eq!(hops[2].sent, 3);
+        assert_eq!(hops[2].loss, 100.0);
+
+        // With -n
+        assert_eq!(hops[3].address.as_deref(), Some("198.51.100.1"));
+        assert_eq!(hops[3].alternates, vec!["198.51.100.2"]);
+        assert_eq!(hops[3].sent, 3);
+        assert!((hops[3].loss - 33.333).abs() < 0.01);
+
+        // The same address again is not an alternate
+        assert_eq!(hops[4].alternates, vec!["203.0.113.8"]);
+        assert_eq!(hops[4].rtts, vec![30.002, 30.771]);
+        assert_eq!(hops[4].sent, 3);
+
+        assert!(hops[5].alternates.is_empty());
+        assert_eq!(hops[5].sent, 3);
+    }
+
+    #[test]
+    fn test_parse_mtr_report() {
+        let output = "Start: 2024-03-02T12:00:00+0000
+HOST: lg1                                   Loss%   Snt   Last   Avg  Best  Wrst StDev
+  1.|-- _gateway (192.168.1.1)               0.0%     1    0.5   0.5   0.5   0.5   0.0
+  2.|-- ???                                 100.0     1    0.0   0.0   0.0   0.0   0.0
+  3.|-- one.one.one.one (1.1.1.1)            0.0%     1   10.1  10.1  10.1  10.1   0.0
+";
+        let (tool, hops) = parse_text(output);
+        assert_eq!(tool, "mtr");
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].address.as_deref(), Some("192.168.1.1"));
+        assert_eq!(hops[0].hostname.as_deref(), Some("_gateway"));
+        assert_eq!(hops[0].avg, Some(0.5));
+        assert_eq!(hops[1], Hop { hop: 2, sent: 1, loss: 100.0, ..Default::default() });
+        assert_eq!(hops[2].worst, Some(10.1));
+
+        let (_, with_asn) = parse_text("  1. AS13335  one.one.one.one (1.1.1.1)  0.0%  1  10.1  10.1  10.1  10.1  0.0");
+        assert_eq!(with_asn[0].asn, Some(13335));
+        assert_eq!(with_asn[0].address.as_deref(), Some("1.1.1.1"));
+    }
+
+    #[test]
+    fn test_parse_mtr_json() {
+        let output = r#"{"report": {"mtr": {"src": "lg1", "dst": "1.1.1.1", "tests": 1},
+            "hubs": [
+                {"count": 1, "host": "_gateway (192.168.1.1)", "ASN": "AS???", "Loss%": 0.0, "Snt": 1, "Last": 0.5, "Avg": 0.5, "Best": 0.5, "Wrst": 0.5, "StDev": 0.0},
+                {"count": "2", "host": "???", "Loss%": 100.0, "Snt": 1, "Last": 0.0, "Avg": 0.0, "Best": 0.0, "Wrst": 0.0, "StDev": 0.0},
+                {"count": 3, "host": "1.1.1.1", "ASN": "AS13335", "Loss%": 0.0, "Snt": 1, "Last": 10.1, "Avg": 10.1, "Best": 10.1, "Wrst": 10.1, "StDev": 0.0}
+            ]}}"#;
+        let hops = parse_mtr_json(output).unwrap();
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].hostname.as_deref(), Some("_gateway"));
+        assert_eq!(hops[0].asn, None);
+        assert_eq!(hops[1], Hop { hop: 2, sent: 1, loss: 100.0, ..Default::default() });
+        assert_eq!(hops[2].address.as_deref(), Some("1.1.1.1"));
+        assert_eq!(hops[2].asn, Some(13335));
+        assert_eq!(hops[2].avg, Some(10.1));
+
+        assert!(parse_mtr_json("mtr: unknown option --json").is_none());
+
+        let text = format_hops(&hops);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("  1. _gateway (192.168.1.1) "));
+        assert!(lines[2].starts_with("  2. ??? "));
+        assert!(lines[2].ends_with("100.0%    1       -       -       -"));
+        assert!(lines[3].starts_with("  3. AS13335 1.1.1.1 "));
+        assert!(lines[3].ends_with("0.0%    1    10.1    10.1    10.1"));
+    }
+}